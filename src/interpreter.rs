@@ -1,112 +1,1007 @@
+mod binary;
+mod conditional;
 mod decoder;
+mod diagnostic;
+mod disassembler;
+mod elf;
+mod encoder;
+mod layout;
+mod listing;
+mod macros;
+mod preprocessor;
+mod source;
+mod source_map;
+mod symbols;
 
 use eeric_core::prelude::*;
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    ops::Range,
+    rc::Rc,
+};
 
-use decoder::{Decoder, LineClassification};
+use conditional::Conditionals;
+use decoder::{expression::Context, Condition, Decoder, LineClassification};
+use encoder::Encoder;
+use layout::LocationCounter;
+use macros::Macros;
+use preprocessor::Preprocessor;
+use source::{ColumnMap, SourceLine, Sources};
 
-use self::decoder::{AssemblerDirective, Section};
+use self::decoder::{AssemblerDirective, PreprocDirective};
+
+pub use binary::BinaryDecoder;
+pub use decoder::Section;
+pub use diagnostic::{Code, Diagnostic, Severity};
+pub use disassembler::Disassembler;
+pub use layout::MemoryLayout;
+pub use listing::{Listing, ListingRow};
+pub use source::{FileSystem, SourceProvider};
+pub use source_map::{Mapping, SourceLocation, SourceMap};
+pub use symbols::Symbol;
 
 pub struct Interpreter;
 
+const MAX_MACRO_DEPTH: usize = 64;
+
 pub struct CompilationResult {
     pub instructions: Vec<Instruction>,
     pub instructions_addresses: Vec<usize>,
+    /// Text labels, addressed like `instructions`, i.e. 4 bytes per instruction
+    pub labels: HashMap<String, usize>,
     pub memory: Memory,
+    /// Initial contents of the data section, placed at the data base of `memory`
+    pub data: Vec<u8>,
+    /// Data labels, addressed like `memory`
+    pub data_labels: HashMap<String, usize>,
+    /// Addresses of the data sections, sorted by address
+    pub data_sections: Vec<(Section, Range<usize>)>,
+    /// Little-endian machine code of the text section
+    pub text: Vec<u8>,
+    /// Addressed like `text`, i.e. by machine code, empty for programs loaded from ELF files
+    pub listing: Listing,
+    /// Text symbols followed by data symbols, each sorted by address
+    pub symbols: Vec<Symbol>,
+    /// Empty for programs loaded from ELF files
+    pub source_map: SourceMap,
+    /// Address the program counter has to start at, the text base for assembled programs
+    pub entry: usize,
+    /// Layout the program was assembled for, with defaults filled in
+    pub layout: MemoryLayout,
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl CompilationResult {
+    /// Canonical assembly of each instruction, showing what pseudo-instructions expanded to
+    pub fn disassemble(&self) -> Vec<String> {
+        let disassembler = Disassembler::new(&self.labels);
+
+        self.instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| disassembler.disassemble(instruction, index * 4))
+            .collect()
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// RV64 executable with `.text`, the data sections and a symbol table, e.g. for objdump or differential testing
+    ///
+    /// Fails if a data section overlaps `.text`.
+    pub fn to_elf(&self) -> Result<Vec<u8>, Diagnostic> {
+        elf::write(self)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct CompileOptions {
+    /// Fails the compilation on any warning, e.g. for lint runs in CI
+    pub warnings_as_errors: bool,
+    /// Resolves `.include` and `.incbin` directives, which fail to compile without one
+    pub source_provider: Option<Rc<dyn SourceProvider>>,
+    /// Where text and data are placed, text starts at 0 and data follows it by default
+    pub layout: MemoryLayout,
+    /// Aligns `.half`, `.word`, `.dword`, `.float` and `.double` values to their size
+    pub auto_align: bool,
+    /// Encodes strings as UTF-8, which are limited to ASCII by default
+    pub utf8: bool,
 }
 
 impl Interpreter {
     pub fn compile(
         program: String,
         memory_size: usize,
-    ) -> Result<CompilationResult, HashMap<usize, String>> {
+    ) -> Result<CompilationResult, Vec<Diagnostic>> {
+        Self::compile_with_options(program, memory_size, CompileOptions::default())
+    }
+
+    /// Loads a statically linked RV64 ELF executable, e.g. built with `-march=rv64imfdv -nostdlib -static`
+    ///
+    /// Only RV64IMFD, Zicsr and V instructions are decoded, so the C, A and Zifencei extensions
+    /// must be disabled. `ecall` and `fence` are rejected as well, which rules out a C runtime.
+    /// Labels are imported from the symbol table, `instructions_addresses` is left empty.
+    pub fn load_elf(elf: &[u8], memory_size: usize) -> Result<CompilationResult, Vec<Diagnostic>> {
+        elf::load(elf, memory_size)
+    }
+
+    /// On failure, the returned diagnostics contain both errors and warnings
+    pub fn compile_with_options(
+        program: String,
+        memory_size: usize,
+        options: CompileOptions,
+    ) -> Result<CompilationResult, Vec<Diagnostic>> {
         let mut instruction_labels = HashMap::new();
         let mut memory_labels = HashMap::new();
         let mut instructions = Vec::new();
         let mut lines_addresses = Vec::new();
-        let mut program_line_address = 0;
-        let mut memory_data_address = 0;
+        let mut program_line_address = options.layout.text_base;
+        // Data sections in order of appearance, labels and data are placed relative to them until the layout is known
+        let mut location_counters = Vec::new();
+        let mut memory_labels_sections = HashMap::new();
+        // Alignment padding of data, filled after the layout is known
+        let mut paddings = Vec::new();
+        // Alignments of `.text` and how many of them precede each text label, padded once the instruction sizes are known
+        let mut text_alignments = Vec::new();
+        let mut labels_alignments = HashMap::new();
 
         let mut to_decode = Vec::new();
-        let mut errors = HashMap::new();
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+        let mut labels_lines = Vec::new();
 
         let mut lines_sections = Vec::new();
+        let mut lines_symbols = Vec::new();
+        let mut lines_columns = Vec::new();
         let mut section_ctx = Section::Text;
 
+        // Lines whose expressions resolved each label
+        let mut references = HashMap::<String, Vec<SourceLocation>>::new();
+
         let mut constants = Vec::<Vec<u8>>::new();
+        let mut data_to_decode = Vec::new();
+        let mut data_columns = Vec::new();
+
+        let mut preprocessor = Preprocessor::new(&program);
+        let mut macros = Macros::default();
+        let mut conditionals = Conditionals::default();
+        // Snapshot per line, as `.set` may redefine a symbol after its use
+        let mut symbols = Rc::new(HashMap::<String, i64>::new());
+        let mut sources = Sources::new(&program);
+
+        // Lines produced by macro expansions keep the address of the invocation
+        let mut pending_lines = program
+            .lines()
+            .enumerate()
+            .map(|(line_address, line)| (line_address, line.to_owned(), 0))
+            .collect::<VecDeque<_>>();
+
+        while let Some((line_address, pending_line, depth)) = pending_lines.pop_front() {
+            if macros.record(&pending_line) {
+                continue;
+            }
+
+            if !conditionals.is_assembling() && !Decoder::is_conditional_directive(&pending_line) {
+                continue;
+            }
 
-        for (line_address, line) in program.lines().enumerate() {
-            let result = Decoder::classify(line);
+            let line = sources[line_address].clone();
+            let expanded = preprocessor.expand(&pending_line, sources.origin(line_address));
+            // Lines of macro expansions are not in the sources, so their tokens are searched for in the invocation
+            let columns = match &expanded {
+                Ok((_, columns)) if depth == 0 => columns.clone(),
+                _ => ColumnMap::default(),
+            };
+            let result = expanded.and_then(|(expanded, _)| {
+                Decoder::classify(&expanded)
+                    .map_err(|diagnostic| diagnostic.within(&expanded, &columns))
+            });
 
             match result {
                 Ok(class) => match class {
-                    LineClassification::PreprocDirective(_) => todo!(),
+                    LineClassification::PreprocDirective(PreprocDirective::Define(name, value)) => {
+                        if let Err(diagnostic) =
+                            preprocessor.define(name, value, sources.origin(line_address))
+                        {
+                            errors.push(diagnostic.locate(&line));
+                        }
+                    }
                     LineClassification::AssemblerDirective(AssemblerDirective::Section(
                         section,
                     )) => {
                         section_ctx = section;
                     }
-                    LineClassification::AssemblerDirective(AssemblerDirective::Data(data)) => {
-                        let vec: Vec<u8> = data.into();
-                        memory_data_address += vec.len();
-                        constants.push(vec);
+                    LineClassification::AssemblerDirective(AssemblerDirective::Data(directive)) => {
+                        let data_section = data_section(&section_ctx);
+                        let counter = LocationCounter::of(&mut location_counters, &data_section);
+
+                        if options.auto_align {
+                            let alignment = Decoder::natural_alignment(&directive);
+                            let aligned = counter.offset.next_multiple_of(alignment);
+
+                            // Labels right before the directive refer to its aligned values
+                            for (label, address) in &mut memory_labels {
+                                if *address == counter.offset
+                                    && memory_labels_sections[label] == data_section
+                                {
+                                    *address = aligned;
+                                }
+                            }
+
+                            counter.offset = aligned;
+                            counter.alignment = counter.alignment.max(alignment);
+                        }
+
+                        // Labels defined later are not known yet, they only matter for the size here
+                        let ctx = Context {
+                            symbols: Some(&symbols),
+                            lenient: true,
+                            source_provider: options.source_provider.as_deref(),
+                            utf8: options.utf8,
+                            ..Context::new(&instruction_labels, &memory_labels, counter.offset)
+                        };
+
+                        match Decoder::decode_data_section(&directive, &ctx) {
+                            // Checked before `.space` and `.fill` are expanded
+                            Ok(data) if data.len() > memory_size.saturating_sub(counter.offset) => {
+                                errors.push(
+                                    Diagnostic::error(
+                                        Code::InvalidData,
+                                        format!(
+                                            "Data of {} bytes does not fit in {} bytes of memory",
+                                            data.len(),
+                                            memory_size
+                                        ),
+                                    )
+                                    .with_help("increase the memory size")
+                                    .locate(&line),
+                                );
+                            }
+                            Ok(data) => {
+                                let vec: Vec<u8> = data.into();
+                                data_to_decode.push((
+                                    directive,
+                                    line_address,
+                                    data_section,
+                                    counter.offset,
+                                    Rc::clone(&symbols),
+                                ));
+                                counter.offset += vec.len();
+                                constants.push(vec);
+                                data_columns.push(columns);
+                            }
+                            Err(diagnostic) => {
+                                errors.push(diagnostic.within(&directive, &columns).locate(&line));
+                            }
+                        }
+                    }
+                    LineClassification::AssemblerDirective(AssemblerDirective::Macro(
+                        name,
+                        parameters,
+                    )) => {
+                        if let Err(diagnostic) = macros.start(name, parameters, line_address) {
+                            errors.push(diagnostic.locate(&line));
+                        }
+                    }
+                    LineClassification::AssemblerDirective(AssemblerDirective::Equ(name, expr)) => {
+                        let resolved = RefCell::default();
+                        let ctx = Context {
+                            symbols: Some(&symbols),
+                            resolved: Some(&resolved),
+                            ..Context::new(&instruction_labels, &memory_labels, 0)
+                        };
+                        let value = ctx.evaluate(&expr);
+                        record_references(&mut references, resolved.into_inner(), &line);
+
+                        match value {
+                            Ok(value) => {
+                                Rc::make_mut(&mut symbols).insert(name, value);
+                            }
+                            Err(diagnostic) => {
+                                errors.push(diagnostic.locate(&line));
+                            }
+                        }
+                    }
+                    LineClassification::AssemblerDirective(AssemblerDirective::Align(
+                        directive,
+                        arguments,
+                    )) => {
+                        let ctx = Context {
+                            symbols: Some(&symbols),
+                            ..Context::new(&instruction_labels, &memory_labels, 0)
+                        };
+
+                        match Decoder::decode_alignment(&directive, &arguments, &ctx) {
+                            Ok(alignment) if section_ctx == Section::Text => {
+                                text_alignments.push((
+                                    to_decode.len(),
+                                    alignment,
+                                    line_address,
+                                    Rc::clone(&symbols),
+                                ));
+                            }
+                            Ok(alignment) => {
+                                let counter =
+                                    LocationCounter::of(&mut location_counters, &section_ctx);
+                                let padding = alignment.padding(counter.offset);
+
+                                if alignment.fill != 0 && section_ctx == Section::Bss {
+                                    errors.push(
+                                        Diagnostic::error(
+                                            Code::InvalidData,
+                                            "Section .bss can only hold zeros",
+                                        )
+                                        .locate(&line),
+                                    );
+                                }
+
+                                paddings.push((
+                                    section_ctx.clone(),
+                                    counter.offset,
+                                    padding,
+                                    alignment.fill,
+                                ));
+                                counter.offset += padding;
+                                counter.alignment = counter.alignment.max(alignment.bytes);
+                            }
+                            Err(diagnostic) => {
+                                errors.push(diagnostic.locate(&line));
+                            }
+                        }
+                    }
+                    LineClassification::AssemblerDirective(AssemblerDirective::Include(path)) => {
+                        let provider = options.source_provider.as_deref();
+
+                        match sources.include(&path, line_address, provider) {
+                            Ok(included) => {
+                                for included_address in included.rev() {
+                                    let included_line = sources[included_address].text.to_string();
+                                    pending_lines.push_front((
+                                        included_address,
+                                        included_line,
+                                        depth,
+                                    ));
+                                }
+                            }
+                            Err(diagnostic) => {
+                                errors.push(diagnostic.locate(&line));
+                            }
+                        }
+                    }
+                    LineClassification::AssemblerDirective(AssemblerDirective::If(condition)) => {
+                        let is_defined = |symbol: &str| {
+                            preprocessor.is_defined(symbol)
+                                || symbols.contains_key(symbol)
+                                || instruction_labels.contains_key(symbol)
+                                || memory_labels.contains_key(symbol)
+                        };
+                        let resolved = RefCell::default();
+                        let ctx = Context {
+                            symbols: Some(&symbols),
+                            resolved: Some(&resolved),
+                            ..Context::new(&instruction_labels, &memory_labels, 0)
+                        };
+
+                        let condition = match condition {
+                            _ if !conditionals.is_assembling() => Ok(false),
+                            Condition::Expression(expr) => {
+                                ctx.evaluate(&expr).map(|value| value != 0)
+                            }
+                            Condition::Defined(symbol) => Ok(is_defined(&symbol)),
+                            Condition::Undefined(symbol) => Ok(!is_defined(&symbol)),
+                        };
+                        record_references(&mut references, resolved.into_inner(), &line);
+
+                        if let Err(diagnostic) = &condition {
+                            errors.push(diagnostic.clone().locate(&line));
+                        }
+
+                        conditionals.open(condition.unwrap_or(false), line_address);
+                    }
+                    LineClassification::AssemblerDirective(AssemblerDirective::ElseIf(expr)) => {
+                        let resolved = RefCell::default();
+                        let ctx = Context {
+                            symbols: Some(&symbols),
+                            resolved: Some(&resolved),
+                            ..Context::new(&instruction_labels, &memory_labels, 0)
+                        };
+
+                        let result = match conditionals.is_pending() {
+                            true => ctx.evaluate(&expr).map(|value| value != 0),
+                            false => Ok(false),
+                        }
+                        .and_then(|condition| conditionals.alternative(condition, ".elseif"));
+                        record_references(&mut references, resolved.into_inner(), &line);
+
+                        if let Err(diagnostic) = result {
+                            errors.push(diagnostic.locate(&line));
+                        }
+                    }
+                    LineClassification::AssemblerDirective(AssemblerDirective::Else) => {
+                        if let Err(diagnostic) = conditionals.alternative(true, ".else") {
+                            errors.push(diagnostic.locate(&line));
+                        }
+                    }
+                    LineClassification::AssemblerDirective(AssemblerDirective::EndIf) => {
+                        if let Err(diagnostic) = conditionals.close() {
+                            errors.push(diagnostic.locate(&line));
+                        }
+                    }
+                    LineClassification::AssemblerDirective(AssemblerDirective::EndMacro) => {
+                        errors.push(
+                            Diagnostic::error(Code::InvalidMacro, ".endm without matching .macro")
+                                .locate(&line),
+                        );
                     }
                     LineClassification::Instruction(decodable) => {
-                        program_line_address += 4;
-                        to_decode.push(decodable);
-                        lines_addresses.push(line_address);
-                        lines_sections.push(section_ctx.clone());
-                    }
-                    LineClassification::Label(label) => match section_ctx {
-                        Section::Text => {
-                            instruction_labels.insert(label, program_line_address);
+                        let (mnemonic, arguments) = decodable
+                            .split_once(char::is_whitespace)
+                            .unwrap_or((&decodable, ""));
+
+                        match macros.expand(mnemonic, arguments) {
+                            Some(Ok(_)) if depth == MAX_MACRO_DEPTH => {
+                                errors.push(
+                                    Diagnostic::error(
+                                        Code::InvalidMacro,
+                                        format!(
+                                            "Expansion of macro {} is nested too deeply",
+                                            mnemonic
+                                        ),
+                                    )
+                                    .with_token(mnemonic)
+                                    .locate(&line),
+                                );
+                            }
+                            Some(Ok(expansion)) => {
+                                for expanded_line in expansion.into_iter().rev() {
+                                    pending_lines.push_front((
+                                        line_address,
+                                        expanded_line,
+                                        depth + 1,
+                                    ));
+                                }
+                            }
+                            Some(Err(diagnostic)) => {
+                                errors.push(diagnostic.locate(&line));
+                            }
+                            None => {
+                                program_line_address += 4;
+                                to_decode.push(decodable);
+                                lines_addresses.push(line_address);
+                                lines_sections.push(section_ctx.clone());
+                                lines_symbols.push(Rc::clone(&symbols));
+                                lines_columns.push(columns);
+                            }
                         }
-                        Section::Data => {
-                            memory_labels.insert(label, memory_data_address);
+                    }
+                    LineClassification::Label(label) => {
+                        labels_lines.push((label.clone(), section_ctx.clone(), line_address));
+
+                        match section_ctx {
+                            Section::Text => {
+                                instruction_labels.insert(label.clone(), program_line_address);
+                                labels_alignments.insert(label, text_alignments.len());
+                            }
+                            _ => {
+                                let counter =
+                                    LocationCounter::of(&mut location_counters, &section_ctx);
+                                memory_labels.insert(label.clone(), counter.offset);
+                                memory_labels_sections.insert(label, section_ctx.clone());
+                            }
                         }
-                    },
+                    }
                     LineClassification::Empty => {}
                 },
-                Err(msg) => {
-                    errors.insert(line_address, msg);
+                Err(diagnostic) => {
+                    errors.push(diagnostic.locate(&line));
                 }
             }
         }
 
-        for (decodable_line_index, decodable_line) in to_decode.into_iter().enumerate() {
-            let maybe_instruction = Decoder::decode_text_section(
-                &decodable_line,
-                &instruction_labels,
-                &memory_labels,
-                decodable_line_index * 4,
+        for line_address in conditionals.unterminated() {
+            errors.push(
+                Diagnostic::error(Code::InvalidConditional, ".if without matching .endif")
+                    .locate(&sources[line_address]),
+            );
+        }
+
+        if let Some(line_address) = macros.unterminated() {
+            errors.push(
+                Diagnostic::error(Code::InvalidMacro, ".macro without matching .endm")
+                    .locate(&sources[line_address]),
+            );
+        }
+
+        for (label, section, line_address) in &labels_lines {
+            if *section != Section::Text && instruction_labels.contains_key(label) {
+                warnings.push(
+                    Diagnostic::warning(
+                        Code::ShadowedLabel,
+                        format!("Data label {} shadows a text label of the same name", label),
+                    )
+                    .with_token(label)
+                    .with_help("loads and stores resolve it to data, jumps and branches to code")
+                    .locate(&sources[*line_address]),
+                );
+            }
+        }
+
+        let text_base = options.layout.text_base;
+        let addresses = (0..to_decode.len())
+            .map(|decodable_line_index| text_base + decodable_line_index * 4)
+            .collect::<Vec<_>>();
+        // Data labels are still relative to their sections, which are placed after the machine code by default
+        let sizes = decode_text_section(
+            &to_decode,
+            &lines_symbols,
+            &addresses,
+            &instruction_labels,
+            &memory_labels,
+        )
+        .iter()
+        .map(|(decoded, _)| decoded.as_ref().map_or(4, Encoder::size))
+        .collect::<Vec<_>>();
+
+        // `.align` pads the machine code, so its `nop`s are only known once the size of each instruction is
+        let mut machine_address = text_base;
+        let mut previous_index = 0;
+        let mut nops = Vec::new();
+
+        for (index, alignment, ..) in &text_alignments {
+            machine_address += sizes[previous_index..*index].iter().sum::<usize>();
+            previous_index = *index;
+            nops.push(alignment.padding(machine_address) / 4);
+            machine_address += nops[nops.len() - 1] * 4;
+        }
+
+        let text_end = machine_address + sizes[previous_index..].iter().sum::<usize>();
+
+        for (label, address) in &mut instruction_labels {
+            *address += nops[..labels_alignments[label]].iter().sum::<usize>() * 4;
+        }
+
+        for ((index, _, line_address, symbols), &count) in text_alignments.iter().zip(&nops).rev() {
+            for _ in 0..count {
+                to_decode.insert(*index, "nop".to_owned());
+                lines_addresses.insert(*index, *line_address);
+                lines_sections.insert(*index, Section::Text);
+                lines_symbols.insert(*index, Rc::clone(symbols));
+                lines_columns.insert(*index, ColumnMap::default());
+            }
+        }
+
+        let layout =
+            match options
+                .layout
+                .resolve(memory_size, text_base..text_end, &location_counters)
+            {
+                Ok(layout) => layout,
+                Err(diagnostic) => {
+                    errors.push(diagnostic);
+                    errors.extend(warnings);
+                    return Err(sorted(errors));
+                }
+            };
+        let section_base = |section: &Section| layout.section_bases[section.name()];
+
+        for (label, address) in &mut memory_labels {
+            *address += section_base(&memory_labels_sections[label]);
+        }
+
+        for (_, _, section, data_address, _) in &mut data_to_decode {
+            *data_address += section_base(section);
+        }
+
+        for ((constant, (directive, line_address, section, data_address, symbols)), columns) in
+            constants.iter_mut().zip(&data_to_decode).zip(&data_columns)
+        {
+            let resolved = RefCell::default();
+            let ctx = Context {
+                symbols: Some(symbols),
+                source_provider: options.source_provider.as_deref(),
+                utf8: options.utf8,
+                resolved: Some(&resolved),
+                ..Context::new(&instruction_labels, &memory_labels, *data_address)
+            };
+            let decoded = Decoder::decode_data_section(directive, &ctx);
+            record_references(
+                &mut references,
+                resolved.into_inner(),
+                &sources[*line_address],
             );
 
-            match maybe_instruction {
-                Ok(instruction) => instructions.push(instruction),
-                Err(msg) => {
-                    errors.insert(lines_addresses[decodable_line_index], msg);
+            match decoded {
+                Ok(data) if data.len() != constant.len() => {
+                    errors.push(
+                        Diagnostic::error(
+                            Code::InvalidData,
+                            "Size of data cannot depend on labels defined later",
+                        )
+                        .locate(&sources[*line_address]),
+                    );
+                }
+                Ok(data) => {
+                    let vec: Vec<u8> = data.into();
+
+                    if *section == Section::Bss && vec.iter().any(|&byte| byte != 0) {
+                        errors.push(
+                            Diagnostic::error(
+                                Code::InvalidData,
+                                "Section .bss can only hold zeros",
+                            )
+                            .with_help("move initialized data to .data")
+                            .locate(&sources[*line_address]),
+                        );
+                    } else {
+                        *constant = vec;
+                    }
+                }
+                Err(diagnostic) => {
+                    errors.push(
+                        diagnostic
+                            .within(directive, columns)
+                            .locate(&sources[*line_address]),
+                    );
+                }
+            }
+        }
+
+        // Sections are not placed in the order they appear in, so the listing and source map are sorted by address
+        let mut placed_data = constants.iter().zip(&data_to_decode).collect::<Vec<_>>();
+        placed_data.sort_by_key(|(_, (.., data_address, _))| *data_address);
+
+        let addresses = (0..to_decode.len())
+            .map(|decodable_line_index| text_base + decodable_line_index * 4)
+            .collect::<Vec<_>>();
+        let decoded = decode_text_section(
+            &to_decode,
+            &lines_symbols,
+            &addresses,
+            &instruction_labels,
+            &memory_labels,
+        );
+
+        for ((((decoded_line, resolved), decodable_line), line_address), columns) in decoded
+            .into_iter()
+            .zip(&to_decode)
+            .zip(&lines_addresses)
+            .zip(&lines_columns)
+        {
+            let line = &sources[*line_address];
+            record_references(&mut references, resolved, line);
+
+            match decoded_line {
+                Ok(instruction) => {
+                    warnings.extend(
+                        Decoder::lint_text_section(decodable_line, &instruction)
+                            .into_iter()
+                            .map(|warning| warning.within(decodable_line, columns).locate(line)),
+                    );
+                    instructions.push(instruction);
+                }
+                Err(diagnostic) => {
+                    errors.push(diagnostic.within(decodable_line, columns).locate(line));
                 }
             };
         }
 
+        for (label, _, line_address) in &labels_lines {
+            if !references.contains_key(label) && !is_entry_point(label) {
+                warnings.push(
+                    Diagnostic::warning(
+                        Code::UnusedLabel,
+                        format!("Label {} is never used", label),
+                    )
+                    .with_token(label)
+                    .locate(&sources[*line_address]),
+                );
+            }
+        }
+
+        // Fused pseudo-instructions take a single slot in the simulator, but all of their words in machine code
+        let mut machine_addresses = vec![text_base];
+
+        for instruction in &instructions {
+            machine_addresses
+                .push(machine_addresses[machine_addresses.len() - 1] + Encoder::size(instruction));
+        }
+
+        let mut text = Vec::new();
+        let mut listing = Listing::default();
+
+        if errors.is_empty() {
+            let machine_labels = instruction_labels
+                .iter()
+                .map(|(label, address)| {
+                    (label.clone(), machine_addresses[(address - text_base) / 4])
+                })
+                .collect::<HashMap<_, _>>();
+
+            let relocated = decode_text_section(
+                &to_decode,
+                &lines_symbols,
+                &machine_addresses,
+                &machine_labels,
+                &memory_labels,
+            );
+
+            let disassembler = Disassembler::new(&machine_labels);
+            let mut previous_line_address = None;
+
+            for ((((relocated_line, instruction), line_address), decodable_line), columns) in
+                relocated
+                    .into_iter()
+                    .map(|(relocated_line, _)| relocated_line)
+                    .zip(&instructions)
+                    .zip(&lines_addresses)
+                    .zip(&to_decode)
+                    .zip(&lines_columns)
+            {
+                let encoded = relocated_line.and_then(|relocated| {
+                    if Encoder::size(&relocated) != Encoder::size(instruction) {
+                        return Err(Diagnostic::error(
+                            Code::InvalidImmediate,
+                            "Size of instruction cannot depend on the machine code layout",
+                        ));
+                    }
+
+                    Encoder::encode(&relocated).map(|words| (relocated, words))
+                });
+
+                match encoded {
+                    Ok((relocated, words)) => {
+                        // Macro expansions list their invocation only once
+                        let source = (previous_line_address != Some(line_address))
+                            .then(|| &*sources[*line_address].text);
+
+                        let address = text_base + text.len();
+
+                        listing.push_instruction(
+                            address,
+                            &words,
+                            &disassembler.disassemble(&relocated, address),
+                            source,
+                        );
+                        text.extend(words.into_iter().flat_map(u32::to_le_bytes));
+                        previous_line_address = Some(line_address);
+                    }
+                    Err(diagnostic) => errors.push(
+                        diagnostic
+                            .within(decodable_line, columns)
+                            .locate(&sources[*line_address]),
+                    ),
+                }
+            }
+
+            for (constant, (_, line_address, _, data_address, _)) in &placed_data {
+                listing.push_data(*data_address, constant, &sources[*line_address].text);
+            }
+
+            listing = listing.with_labels(&machine_labels, &memory_labels);
+
+            // Instructions may have grown once data labels were placed, so the text is checked again
+            if let Err(diagnostic) = layout.resolve(
+                memory_size,
+                text_base..text_base + text.len(),
+                &location_counters,
+            ) {
+                errors.push(diagnostic);
+            }
+        }
+
+        if options.warnings_as_errors {
+            errors.extend(warnings.drain(..).map(|warning| Diagnostic {
+                severity: Severity::Error,
+                ..warning
+            }));
+        }
+
         if errors.is_empty() {
-            let mut memory = Memory::new((0..memory_size).map(|_| 0));
+            let mut raw = vec![0; memory_size];
+
+            for (section, offset, padding, fill) in &paddings {
+                let address = section_base(section) + offset;
+                raw[address..address + padding].fill(*fill);
+            }
+
+            for (constant, (.., data_address, _)) in constants.iter().zip(&data_to_decode) {
+                raw[*data_address..*data_address + constant.len()].copy_from_slice(constant);
+            }
 
-            for constant in constants {
-                memory.assign(&constant);
+            // `.bss` is zero-initialized, so it is left out like in an executable
+            let data_end = location_counters
+                .iter()
+                .filter(|counter| counter.section != Section::Bss)
+                .map(|counter| section_base(&counter.section) + counter.offset)
+                .max()
+                .unwrap_or_default();
+            let data = raw
+                .get(layout.data_base.unwrap_or_default()..data_end)
+                .unwrap_or_default()
+                .to_vec();
+
+            let mut source_map = SourceMap::default();
+
+            for (index, line_address) in lines_addresses.iter().enumerate() {
+                source_map.push_text(
+                    text_base + index * 4,
+                    machine_addresses[index]..machine_addresses[index + 1],
+                    &sources[*line_address],
+                );
+            }
+
+            for (constant, (_, line_address, _, data_address, _)) in &placed_data {
+                source_map.push_data(
+                    *data_address..*data_address + constant.len(),
+                    &sources[*line_address],
+                );
+            }
+
+            let text_end = text_base + instructions.len() * 4;
+            let mut symbols = Symbol::section(&instruction_labels, Section::Text, text_end);
+            let mut placed_sections = location_counters.iter().collect::<Vec<_>>();
+            placed_sections.sort_by_key(|counter| section_base(&counter.section));
+
+            let data_sections = placed_sections
+                .iter()
+                .map(|counter| {
+                    let base = section_base(&counter.section);
+                    (counter.section.clone(), base..base + counter.offset)
+                })
+                .collect();
+
+            for LocationCounter {
+                section,
+                offset: size,
+                ..
+            } in placed_sections
+            {
+                let section_labels = memory_labels
+                    .iter()
+                    .filter(|(label, _)| memory_labels_sections[*label] == *section)
+                    .map(|(label, &address)| (label.clone(), address))
+                    .collect::<HashMap<_, _>>();
+
+                symbols.extend(Symbol::section(
+                    &section_labels,
+                    section.clone(),
+                    section_base(section) + size,
+                ));
+            }
+
+            for symbol in &mut symbols {
+                if symbol.section == Section::Text {
+                    symbol.machine_address = machine_addresses[(symbol.address - text_base) / 4];
+                }
+
+                // Like the label maps, the last definition wins
+                symbol.line = labels_lines
+                    .iter()
+                    .rev()
+                    .find(|(label, section, _)| *label == symbol.name && *section == symbol.section)
+                    .map(|(.., line_address)| SourceLocation::of(&sources[*line_address]));
+
+                if let Some(locations) = references.get(&symbol.name) {
+                    symbol.references = locations.clone();
+                    symbol
+                        .references
+                        .sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+                    symbol.references.dedup();
+                }
             }
 
+            // The simulator fetches `instructions[pc / 4]`, so everything below the text base is a nop,
+            // which the layout bounds by the memory size
+            let padding = text_base / 4;
+            let nop = Instruction::Addi(format::I {
+                rd: 0,
+                rs1: 0,
+                imm12: 0,
+            });
+
             Ok(CompilationResult {
-                instructions,
-                instructions_addresses: lines_addresses,
-                memory,
+                instructions: [vec![nop; padding], instructions].concat(),
+                instructions_addresses: [
+                    vec![0; padding],
+                    lines_addresses
+                        .into_iter()
+                        .map(|line_address| sources.origin(line_address))
+                        .collect(),
+                ]
+                .concat(),
+                labels: instruction_labels,
+                memory: Memory::new(raw.into_iter()),
+                data,
+                data_labels: memory_labels,
+                data_sections,
+                text,
+                listing,
+                symbols,
+                source_map,
+                entry: text_base,
+                layout,
+                warnings: sorted(warnings),
             })
         } else {
-            Err(errors)
+            errors.extend(warnings);
+            Err(sorted(errors))
         }
     }
 }
 
+/// Data directives in `.text` are placed in `.data`
+fn data_section(section: &Section) -> Section {
+    match section {
+        Section::Text => Section::Data,
+        section => section.clone(),
+    }
+}
+
+/// Decodes the text section with each line placed at the given address, along with the labels each line resolved
+fn decode_text_section(
+    to_decode: &[String],
+    lines_symbols: &[Rc<HashMap<String, i64>>],
+    addresses: &[usize],
+    instruction_labels: &HashMap<String, usize>,
+    memory_labels: &HashMap<String, usize>,
+) -> Vec<(Result<Instruction, Diagnostic>, Vec<String>)> {
+    let lines = to_decode.iter().zip(lines_symbols).zip(addresses);
+
+    let pcrel_targets = lines
+        .clone()
+        .filter_map(|((decodable_line, symbols), &current_address)| {
+            let ctx = Context {
+                symbols: Some(symbols),
+                ..Context::new(instruction_labels, memory_labels, current_address)
+            };
+
+            Decoder::pcrel_hi_target(decodable_line, &ctx).map(|target| (current_address, target))
+        })
+        .collect::<HashMap<_, _>>();
+
+    lines
+        .map(|((decodable_line, symbols), &current_address)| {
+            let resolved = RefCell::default();
+            let ctx = Context {
+                symbols: Some(symbols),
+                pcrel_targets: Some(&pcrel_targets),
+                resolved: Some(&resolved),
+                ..Context::new(instruction_labels, memory_labels, current_address)
+            };
+            let decoded = Decoder::decode_text_section(decodable_line, &ctx);
+
+            (decoded, resolved.into_inner())
+        })
+        .collect()
+}
+
+fn sorted(mut diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    diagnostics.sort_by(|a, b| {
+        (&a.file, a.line, a.columns.start).cmp(&(&b.file, b.line, b.columns.start))
+    });
+    diagnostics
+}
+
+/// Records the line as a reference to each label its expressions resolved
+fn record_references(
+    references: &mut HashMap<String, Vec<SourceLocation>>,
+    resolved: Vec<String>,
+    line: &SourceLine,
+) {
+    for label in resolved {
+        references
+            .entry(label)
+            .or_default()
+            .push(SourceLocation::of(line));
+    }
+}
+
+fn is_entry_point(label: &str) -> bool {
+    matches!(label, "main" | "_start")
+}
+
 #[cfg(test)]
 mod tests {
     use eeric_core::fuse;
@@ -136,7 +1031,7 @@ mod tests {
         "#
         .trim_start();
 
-        let compilation_result = Interpreter::compile(input.to_owned(), 12).unwrap();
+        let compilation_result = Interpreter::compile(input.to_owned(), 44).unwrap();
 
         assert_eq!(
             compilation_result.instructions,
@@ -159,24 +1054,24 @@ mod tests {
                 Instruction::Ld(format::I {
                     rd: 10,
                     rs1: 0,
-                    imm12: 0
+                    imm12: 32
                 }),
                 Instruction::Ld(format::I {
                     rd: 10,
                     rs1: 0,
-                    imm12: 4
+                    imm12: 36
                 }),
                 Instruction::Ld(format::I {
                     rd: 10,
                     rs1: 0,
-                    imm12: 7
+                    imm12: 40
                 }),
                 fuse![
                     Instruction::Auipc(format::U { rd: 11, imm20: 0 }),
                     Instruction::Addi(format::I {
                         rd: 11,
                         rs1: 11,
-                        imm12: 4
+                        imm12: 12
                     })
                 ]
             ]
@@ -193,7 +1088,1537 @@ mod tests {
                 .snapshot()
                 .into_iter()
                 .collect::<Vec<_>>(),
-            vec![37, 6, 73, 64, 97, 98, 99, 100, 101, 102, 0, 0]
+            [
+                vec![0; 32],
+                vec![37, 6, 73, 64, 97, 98, 99, 0, 100, 101, 102, 0]
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn defines_are_substituted() {
+        let input = r#"
+        #define STEP 8
+        #define DOUBLE_STEP STEP # comments are not part of the value
+        #define ACC t0
+        #define TARGET loop
+        loop:
+            addi ACC, ACC, DOUBLE_STEP
+            bnez ACC, TARGET
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 0).unwrap();
+
+        assert_eq!(
+            compilation_result.instructions,
+            vec![
+                Instruction::Addi(format::I {
+                    rd: 5,
+                    rs1: 5,
+                    imm12: 8
+                }),
+                Instruction::Bne(format::S {
+                    rs1: 5,
+                    rs2: 0,
+                    imm12: -4
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn define_errors() {
+        let input = r#"
+        addi a0, a0, LATE
+        #define LATE 1
+        #define EARLY 2
+        #define EARLY 3
+        "#;
+
+        let Err(errors) = Interpreter::compile(input.to_owned(), 0) else {
+            panic!("Expected compilation to fail");
+        };
+
+        let messages = errors
+            .iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.message.as_str()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            vec![
+                (1, "LATE is used before its #define on line 3"),
+                (4, "Redefinition of EARLY (previously defined on line 4)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn defines_which_may_not_be_assembled() {
+        let input = r#"
+        main:
+            la a0, value
+            j done
+        .if 0
+        #define value 0
+        .endif
+        .macro unused
+        #define done 1
+        .endm
+        done:
+        .data
+        value:
+            .word 1
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 20).unwrap();
+
+        assert_eq!(compilation_result.labels["done"], 8);
+    }
+
+    #[test]
+    fn diagnostics_point_at_operand() {
+        let input = r#"
+        addi a0, a0, 1
+        add a0, a9, a1
+        frobnicate a0
+        "#;
+
+        let Err(errors) = Interpreter::compile(input.to_owned(), 0) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(errors.len(), 2);
+
+        assert_eq!(errors[0].line, 2);
+        assert_eq!(errors[0].columns, 16..18);
+        assert_eq!(errors[0].severity, Severity::Error);
+        assert_eq!(errors[0].code, Code::InvalidRegister);
+        assert_eq!(errors[0].token.as_deref(), Some("a9"));
+
+        assert_eq!(errors[1].line, 3);
+        assert_eq!(errors[1].columns, 8..18);
+        assert_eq!(errors[1].code, Code::UnknownMnemonic);
+        assert_eq!(
+            errors[1].to_string(),
+            "4:9: error[E0001]: Unknown mnemonic: frobnicate"
+        );
+    }
+
+    #[test]
+    fn diagnostics_point_at_parsed_operand() {
+        let input = r#"
+        #define LIMIT a2
+        addi a0, a1, a1
+        addi a0, a0, LIMIT
+        "#;
+
+        let Err(errors) = Interpreter::compile(input.to_owned(), 0) else {
+            panic!("Expected compilation to fail");
+        };
+
+        let columns = errors
+            .iter()
+            .map(|error| (error.line, error.columns.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(columns, vec![(2, 21..23), (3, 21..26)]);
+    }
+
+    #[test]
+    fn warnings_are_reported() {
+        let input = r#"
+        main:
+            li t0, 0x123
+            addi zero, t0, 1
+            addi x0, x0, 0
+            addi zero, x0, 2 - 2
+        unused:
+            j main
+        .data
+        main:
+            .word 1
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 28).unwrap();
+
+        let warnings = compilation_result
+            .warnings
+            .iter()
+            .map(|warning| (warning.line, warning.code, warning.severity))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            warnings,
+            vec![
+                (3, Code::WriteToZero, Severity::Warning),
+                (6, Code::UnusedLabel, Severity::Warning),
+                (9, Code::ShadowedLabel, Severity::Warning),
+            ]
+        );
+        assert_eq!(
+            compilation_result
+                .warnings
+                .iter()
+                .map(|warning| warning.code.as_str())
+                .collect::<Vec<_>>(),
+            vec!["W0003", "W0001", "W0004"]
+        );
+    }
+
+    #[test]
+    fn warnings_as_errors() {
+        let input = r#"
+        unused:
+            add x0, x1, x1
+        "#;
+
+        let options = CompileOptions {
+            warnings_as_errors: true,
+            ..Default::default()
+        };
+
+        let Err(errors) = Interpreter::compile_with_options(input.to_owned(), 0, options) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.code, error.severity))
+                .collect::<Vec<_>>(),
+            vec![
+                (Code::UnusedLabel, Severity::Error),
+                (Code::WriteToZero, Severity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    fn expressions_in_immediates_and_data() {
+        let input = r#"
+        .text
+        main:
+            addi a0, zero, (end - start) / 4
+            lw a1, start + 4(zero)
+            li a2, ~0xf & 0xff | 1 << 8
+            beq a0, a1, main + 4
+        .data
+        start:
+            .word end - start, 3 * (2 + 1)
+            .byte 'a' + 1, -(-2) % 3
+        end:
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 26).unwrap();
+
+        assert_eq!(
+            compilation_result.instructions,
+            vec![
+                Instruction::Addi(format::I {
+                    rd: 10,
+                    rs1: 0,
+                    imm12: 2
+                }),
+                Instruction::Lw(format::I {
+                    rd: 11,
+                    rs1: 0,
+                    imm12: 20
+                }),
+                Instruction::Addi(format::I {
+                    rd: 12,
+                    rs1: 0,
+                    imm12: 0x1f0
+                }),
+                Instruction::Beq(format::S {
+                    rs1: 10,
+                    rs2: 11,
+                    imm12: -8
+                }),
+            ]
+        );
+
+        assert_eq!(
+            compilation_result
+                .memory
+                .snapshot()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [vec![0; 16], vec![10, 0, 0, 0, 9, 0, 0, 0, 98, 2]].concat()
+        );
+
+        // A leading 0 makes a number octal, like in GNU as
+        let compilation_result =
+            Interpreter::compile(".data\n.byte 010, 0, 0x10, 0b10".to_owned(), 4).unwrap();
+
+        assert_eq!(compilation_result.data, [8, 0, 16, 2]);
+
+        let Err(errors) = Interpreter::compile(".data\n.byte 09".to_owned(), 1) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(errors[0].code, Code::InvalidImmediate);
+        assert!(errors[0].help.is_some());
+    }
+
+    #[test]
+    fn data_refers_to_later_labels() {
+        let input = r#"
+        .data
+            .zero 200
+        start:
+            .byte end - start
+            .byte 100 / (end - start)
+            .byte 1 << (end - start)
+        end:
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 256).unwrap();
+
+        assert_eq!(compilation_result.data[200..], [3, 33, 8]);
+
+        let input = r#"
+        .data
+            .byte big - small
+        small:
+            .zero 300
+        big:
+        "#;
+
+        let Err(errors) = Interpreter::compile(input.to_owned(), 512) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "Value 300 does not fit in 8 bits");
+    }
+
+    #[test]
+    fn expression_errors() {
+        let input = r#"
+            addi a0, a0, 4 / (2 - 2)
+            addi a0, a0, missing + 1
+        .data
+            .zero later + 1
+        later:
+        "#;
+
+        let Err(errors) = Interpreter::compile(input.to_owned(), 16) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.line, error.code))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, Code::InvalidImmediate),
+                (2, Code::UnknownLabel),
+                (4, Code::InvalidData),
+            ]
+        );
+        assert_eq!(errors[1].token.as_deref(), Some("missing"));
+    }
+
+    #[test]
+    fn relocation_operators() {
+        let input = r#"
+        .text
+            lui a0, %hi(far)
+            addi a0, a0, %lo(far)
+            lw a1, %lo(far)(a0)
+        pcrel:
+            auipc a2, %pcrel_hi(far)
+            addi a2, a2, %pcrel_lo(pcrel)
+            sw a1, %pcrel_lo(pcrel)(a2)
+        .data
+            .zero 0x7ff
+            .zero 1
+            .zero 0x800
+        far:
+            .word %hi(0x12345fff), %lo(0x12345678)
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 0x1020).unwrap();
+
+        assert_eq!(
+            compilation_result.instructions,
+            vec![
+                Instruction::Lui(format::U { rd: 10, imm20: 1 }),
+                Instruction::Addi(format::I {
+                    rd: 10,
+                    rs1: 10,
+                    imm12: 0x18
+                }),
+                Instruction::Lw(format::I {
+                    rd: 11,
+                    rs1: 10,
+                    imm12: 0x18
+                }),
+                Instruction::Auipc(format::U { rd: 12, imm20: 1 }),
+                Instruction::Addi(format::I {
+                    rd: 12,
+                    rs1: 12,
+                    imm12: 12
+                }),
+                Instruction::Sw(format::S {
+                    rs1: 12,
+                    rs2: 11,
+                    imm12: 12
+                }),
+            ]
+        );
+
+        let far = compilation_result
+            .memory
+            .snapshot()
+            .into_iter()
+            .skip(0x1018)
+            .collect::<Vec<_>>();
+
+        assert_eq!(far, vec![0x46, 0x23, 0x01, 0x00, 0x78, 0x06, 0x00, 0x00]);
+
+        // Rounding wraps around like the 64-bit address arithmetic
+        let input = r#"
+        .data
+            .dword %hi(0x7fffffffffffffff), %lo(0x7fffffffffffffff)
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 16).unwrap();
+
+        assert_eq!(
+            compilation_result.data,
+            [(-1i64 << 51).to_le_bytes(), (-1i64).to_le_bytes()].concat()
+        );
+    }
+
+    #[test]
+    fn macros_are_expanded() {
+        let input = r#"
+        .macro inc reg, by=1
+            addi \reg, \reg, \by
+        .endm
+        .macro countdown reg, from
+            li \reg, \from
+        loop\@:
+            inc \reg, -1
+            bnez \reg, loop\@
+        .endm
+        countdown a0, 3
+        inc a1
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 0).unwrap();
+
+        assert_eq!(
+            compilation_result.instructions,
+            vec![
+                Instruction::Addi(format::I {
+                    rd: 10,
+                    rs1: 0,
+                    imm12: 3
+                }),
+                Instruction::Addi(format::I {
+                    rd: 10,
+                    rs1: 10,
+                    imm12: -1
+                }),
+                Instruction::Bne(format::S {
+                    rs1: 10,
+                    rs2: 0,
+                    imm12: -4
+                }),
+                Instruction::Addi(format::I {
+                    rd: 11,
+                    rs1: 11,
+                    imm12: 1
+                }),
+            ]
+        );
+
+        assert_eq!(
+            compilation_result.instructions_addresses,
+            vec![10, 10, 10, 11]
+        );
+    }
+
+    #[test]
+    fn macro_defaults_with_spaces() {
+        let input = r#"
+        .macro set reg = a0, value = 2 * 3
+            addi \reg, zero, \value
+        .endm
+        set
+        set a1
+        set a2, 1 + 1
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 0).unwrap();
+
+        assert_eq!(
+            compilation_result.instructions,
+            [(10, 6), (11, 6), (12, 2)]
+                .map(|(rd, imm12)| Instruction::Addi(format::I { rd, rs1: 0, imm12 }))
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn macro_arguments_keep_their_position() {
+        let input = r#"
+        .macro m a, b=1, c
+            addi \a, \c, \b
+        .endm
+        m a0, , a1
+        m a2, 5, a3
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 0).unwrap();
+
+        assert_eq!(
+            compilation_result.instructions,
+            [(10, 11, 1), (12, 13, 5)]
+                .map(|(rd, rs1, imm12)| Instruction::Addi(format::I { rd, rs1, imm12 }))
+                .to_vec()
+        );
+
+        let Err(errors) = Interpreter::compile(input.replace("a0, , a1", "a0, 2,"), 0) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(
+            errors[0].message,
+            "Missing value for parameter c of macro m"
+        );
+    }
+
+    #[test]
+    fn macro_errors() {
+        let input = r#"
+        .macro pair a, b
+            add \a, \a, \b
+        .endm
+        pair a0
+        pair a0, a1, a2
+        .endm
+        .macro forever
+            forever
+        .endm
+        forever
+        .macro open
+        "#;
+
+        let Err(errors) = Interpreter::compile(input.to_owned(), 0) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.line, error.code))
+                .collect::<Vec<_>>(),
+            vec![
+                (4, Code::InvalidMacro),
+                (5, Code::InvalidMacro),
+                (6, Code::InvalidMacro),
+                (10, Code::InvalidMacro),
+                (11, Code::InvalidMacro),
+            ]
+        );
+    }
+
+    #[test]
+    fn conditional_assembly() {
+        let input = r#"
+        #define VLEN 256
+        #define HAS_F 0
+        .ifdef HAS_F
+            .if VLEN >= 512 && HAS_F
+                addi a0, zero, 1
+            .elseif VLEN == 256
+                addi a0, zero, 2
+                .ifndef VLEN
+                    addi a0, zero, 3
+                .else
+                    addi a1, zero, 4
+                .endif
+            .else
+                addi a0, zero, 5
+            .endif
+        .endif
+        .ifdef MISSING
+            not an instruction
+        .endif
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 0).unwrap();
+
+        assert_eq!(
+            compilation_result.instructions,
+            vec![
+                Instruction::Addi(format::I {
+                    rd: 10,
+                    rs1: 0,
+                    imm12: 2
+                }),
+                Instruction::Addi(format::I {
+                    rd: 11,
+                    rs1: 0,
+                    imm12: 4
+                }),
+            ]
+        );
+        assert_eq!(compilation_result.instructions_addresses, vec![7, 11]);
+    }
+
+    #[test]
+    fn conditional_errors() {
+        let input = r#"
+        .else
+        .if 1
+        .else
+        .elseif 1
+        .endif
+        .endif
+        .if 1
+        "#;
+
+        let Err(errors) = Interpreter::compile(input.to_owned(), 0) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.line, error.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, ".else without matching .if"),
+                (4, ".elseif after .else"),
+                (6, ".endif without matching .if"),
+                (7, ".if without matching .endif"),
+            ]
+        );
+    }
+
+    #[test]
+    fn add_with_carry() {
+        let input = r#"
+            vsetivli zero, 4, e32, m1, ta, ma
+            vmv.v.i v0, 5
+            vmv.v.i v2, 10
+            vmv.v.i v3, 3
+            vmv.v.i v9, -1
+            vmv.v.i v10, 0
+            vadc.vvm v4, v2, v3, v0
+            vadc.vim v5, v2, 7, v0
+            vmadc.vi v6, v2, -1
+            vmadc.vvm v7, v9, v10, v0
+            vmadc.vv v8, v2, v3
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 16).unwrap();
+
+        // The mask flag is set for instructions using v0, like the encoding of the vm bit does
+        assert_eq!(
+            BinaryDecoder::decode_bytes(&compilation_result.text).unwrap(),
+            compilation_result.instructions
+        );
+        assert!(matches!(
+            compilation_result.instructions[7],
+            Instruction::Vadcvim(format::Opivi { vm: true, .. })
+        ));
+        assert!(matches!(
+            compilation_result.instructions[10],
+            Instruction::Vmadcvv(format::Opivv { vm: false, .. })
+        ));
+
+        let mut core = RvCoreBuilder::default()
+            .instructions(compilation_result.instructions)
+            .memory(compilation_result.memory)
+            .build();
+
+        for result in core.run() {
+            result.unwrap();
+        }
+
+        let registers = core.registers.snapshot();
+        let vlenb = registers.v.len() / 32;
+        let elements = |nth: usize| {
+            registers.v[nth * vlenb..nth * vlenb + 16]
+                .chunks(4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(elements(4), vec![14, 13, 14, 13]);
+        assert_eq!(elements(5), vec![18, 17, 18, 17]);
+    }
+
+    #[test]
+    fn equ_and_set_symbols() {
+        let input = r#"
+        .equ ELEMENTS, 4
+        .set STRIDE, ELEMENTS * 2
+            vsetivli t0, ELEMENTS, e16, m1, ta, ma
+            addi a0, zero, STRIDE
+            csrrwi zero, fcsr, ELEMENTS - 3
+        .set STRIDE, STRIDE + 1
+            addi a1, zero, STRIDE
+        .if STRIDE == 9
+            addi a2, zero, 1
+        .endif
+        .data
+        start:
+            .half STRIDE, ELEMENTS
+        end:
+        .equ SIZE, end - start
+            .byte SIZE
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 29).unwrap();
+
+        assert_eq!(
+            compilation_result.instructions,
+            vec![
+                Instruction::Vsetivli(format::Vsetivli {
+                    rd: 5,
+                    uimm: 4,
+                    vtypei: 0b11001000
+                }),
+                Instruction::Addi(format::I {
+                    rd: 10,
+                    rs1: 0,
+                    imm12: 8
+                }),
+                Instruction::Csrrwi(format::Csri {
+                    rd: 0,
+                    uimm: 1,
+                    csr: 3
+                }),
+                Instruction::Addi(format::I {
+                    rd: 11,
+                    rs1: 0,
+                    imm12: 9
+                }),
+                Instruction::Addi(format::I {
+                    rd: 12,
+                    rs1: 0,
+                    imm12: 1
+                }),
+            ]
+        );
+
+        assert_eq!(
+            compilation_result
+                .memory
+                .snapshot()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [vec![0; 24], vec![9, 0, 4, 0, 4]].concat()
+        );
+    }
+
+    #[test]
+    fn include_depth_is_bounded() {
+        // Like a program including itself, whose own path is not known
+        struct SelfIncluding;
+
+        impl SourceProvider for SelfIncluding {
+            fn read(&self, path: &str) -> Result<String, String> {
+                Ok(format!(".include \"./{}\"", path))
+            }
+        }
+
+        let options = CompileOptions {
+            source_provider: Some(Rc::new(SelfIncluding)),
+            ..Default::default()
+        };
+
+        let Err(errors) =
+            Interpreter::compile_with_options(".include \"main.s\"".to_owned(), 0, options)
+        else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, Code::InvalidInclude);
+        assert!(errors[0].message.ends_with("is nested too deeply"));
+    }
+
+    #[test]
+    fn includes_are_resolved() {
+        let files = HashMap::from([
+            (
+                "lib.s".to_owned(),
+                ".equ STEP, 2\n.include \"macros.s\"\n".to_owned(),
+            ),
+            (
+                "macros.s".to_owned(),
+                ".macro bump reg\n    addi \\reg, \\reg, STEP\n.endm\n".to_owned(),
+            ),
+            ("table.s".to_owned(), ".byte 1, 2\n.byte 3".to_owned()),
+            ("broken.s".to_owned(), "\n    frob a0".to_owned()),
+            ("cycle.s".to_owned(), ".include \"cycle.s\"".to_owned()),
+        ]);
+        let options = CompileOptions {
+            source_provider: Some(Rc::new(files)),
+            ..Default::default()
+        };
+
+        let input = r#"
+        .include "lib.s"
+            bump a0
+        .data
+        .include "table.s"
+        "#;
+
+        let compilation_result =
+            Interpreter::compile_with_options(input.to_owned(), 11, options.clone()).unwrap();
+
+        assert_eq!(
+            compilation_result.instructions,
+            vec![Instruction::Addi(format::I {
+                rd: 10,
+                rs1: 10,
+                imm12: 2
+            })]
+        );
+        assert_eq!(compilation_result.instructions_addresses, vec![2]);
+        assert_eq!(
+            compilation_result
+                .memory
+                .snapshot()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [vec![0; 8], vec![1, 2, 3]].concat()
+        );
+
+        let input = r#"
+        .include "broken.s"
+        .include "missing.s"
+        .include "cycle.s"
+        "#;
+
+        let Err(errors) = Interpreter::compile_with_options(input.to_owned(), 0, options) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| (error.file.as_deref(), error.line, error.code))
+                .collect::<Vec<_>>(),
+            vec![
+                (None, 2, Code::InvalidInclude),
+                (Some("broken.s"), 1, Code::UnknownMnemonic),
+                (Some("cycle.s"), 0, Code::InvalidInclude),
+            ]
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "broken.s:2:5: error[E0001]: Unknown mnemonic: frob"
+        );
+
+        let Err(errors) = Interpreter::compile(input.to_owned(), 0) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert!(errors
+            .iter()
+            .all(|error| error.file.is_none() && error.code == Code::InvalidInclude));
+    }
+
+    #[test]
+    fn machine_code_layout() {
+        let input = r#"
+        main:
+            call func
+            j end
+        func:
+            ret
+        end:
+            nop
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 0).unwrap();
+
+        // The fused call takes two words, so the labels after it move in machine code
+        let words = compilation_result
+            .text
+            .chunks(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            words,
+            vec![0x00000097, 0x00c080e7, 0x0080006f, 0x00008067, 0x00000013]
+        );
+
+        let Err(errors) = Interpreter::compile("addi a0, a0, 2048".to_owned(), 0) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(errors[0].code, Code::InvalidImmediate);
+        assert_eq!(errors[0].columns, 13..17);
+    }
+
+    #[test]
+    fn listing_shows_expansions_and_data() {
+        let input = r#"
+        .macro inc reg
+            addi \reg, \reg, 1
+            addi \reg, \reg, 1
+        .endm
+        main:
+            li a0, 0x12345
+            inc a1
+            call main
+        .data
+        value:
+            .word 42
+            .string "hello, world"
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 48).unwrap();
+
+        let expected = [
+            ".text",
+            "00000000                           main:",
+            "00000000  00012537                 lui a0, 0x12                      li a0, 0x12345",
+            "00000004  3455051b                 addiw a0, a0, 837",
+            "00000008  00158593                 addi a1, a1, 1                    inc a1",
+            "0000000c  00158593                 addi a1, a1, 1",
+            "00000010  00000097                 auipc ra, 0x0                     call main",
+            "00000014  ff0080e7                 jalr ra, -16(ra)  # main",
+            ".data",
+            "00000018                           value:",
+            "00000018  2a 00 00 00                                                .word 42",
+            "0000001c  68 65 6c 6c 6f 2c 20 77                                    .string \"hello, world\"",
+            "00000024  6f 72 6c 64 00",
+        ];
+
+        assert_eq!(
+            compilation_result
+                .listing
+                .to_string()
+                .lines()
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn symbol_table() {
+        let input = r#"
+        main:
+            la a0, table
+        loop:
+            lw a1, 0(a0)
+            bnez a1, loop
+            j main
+        .data
+        table:
+            .word 1, 2, 0
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 36).unwrap();
+
+        let symbols = compilation_result
+            .symbols
+            .iter()
+            .map(|symbol| {
+                (
+                    symbol.name.as_str(),
+                    symbol.section.clone(),
+                    symbol.address,
+                    symbol.size,
+                    symbol.line.as_ref().map(|location| location.line),
+                    symbol
+                        .references
+                        .iter()
+                        .map(|location| location.line)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            symbols,
+            vec![
+                ("main", Section::Text, 0, Some(4), Some(1), vec![6]),
+                ("loop", Section::Text, 4, Some(12), Some(3), vec![5]),
+                ("table", Section::Data, 24, Some(12), Some(8), vec![2]),
+            ]
+        );
+        assert_eq!(compilation_result.symbol("loop").unwrap().address, 4);
+        assert!(compilation_result.symbol("end").is_none());
+    }
+
+    #[test]
+    fn simulator_and_machine_addresses_agree() {
+        let input = r#"
+        main:
+            la a0, value
+        next:
+            addi a0, a0, 1
+            j next
+        .data
+        value:
+            .word 1
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 64).unwrap();
+
+        // `la` takes one slot in `instructions`, but two words of machine code
+        let next = compilation_result.symbol("next").unwrap();
+        assert_eq!((next.address, next.machine_address), (4, 8));
+        assert_eq!(compilation_result.labels["next"], next.address);
+
+        let mapping = &compilation_result.source_map.text[1];
+        assert_eq!(mapping.addresses, 4..8);
+        assert_eq!(mapping.machine_addresses, 8..12);
+        assert_eq!(mapping.location.line, 4);
+
+        let word = u32::from_le_bytes(compilation_result.text[8..12].try_into().unwrap());
+        assert_eq!(
+            BinaryDecoder::decode(word).unwrap(),
+            compilation_result.instructions[1]
+        );
+        assert!(compilation_result
+            .listing
+            .text
+            .contains(&ListingRow::Label {
+                address: 8,
+                name: "next".to_owned()
+            }));
+    }
+
+    #[test]
+    fn symbol_locations() {
+        let files = HashMap::from([("lib.s".to_owned(), "helper:\n    ret\n".to_owned())]);
+        let options = CompileOptions {
+            source_provider: Some(Rc::new(files)),
+            ..Default::default()
+        };
+        let input = r#"
+        main:
+            call helper
+            addi t0, t0, 1
+        t0:
+            .include "lib.s"
+        "#;
+
+        let compilation_result =
+            Interpreter::compile_with_options(input.to_owned(), 16, options).unwrap();
+
+        let helper = compilation_result.symbol("helper").unwrap();
+        let line = helper.line.as_ref().unwrap();
+        assert_eq!((line.file.as_deref(), line.line), (Some("lib.s"), 0));
+        assert_eq!(
+            helper
+                .references
+                .iter()
+                .map(|location| (location.file.as_deref(), location.line))
+                .collect::<Vec<_>>(),
+            vec![(None, 2)]
+        );
+
+        // Registers are not references to a label of the same name
+        assert!(compilation_result
+            .symbol("t0")
+            .unwrap()
+            .references
+            .is_empty());
+        assert_eq!(
+            compilation_result
+                .warnings
+                .iter()
+                .map(|warning| (warning.line, warning.code))
+                .collect::<Vec<_>>(),
+            vec![(4, Code::UnusedLabel)]
+        );
+    }
+
+    #[test]
+    fn source_map() {
+        let files = HashMap::from([("double.s".to_owned(), "    add a0, a0, a0\n".to_owned())]);
+        let options = CompileOptions {
+            source_provider: Some(Rc::new(files)),
+            ..Default::default()
+        };
+
+        let input = r#"
+        .macro twice reg
+            addi \reg, \reg, 1
+            addi \reg, \reg, 1
+        .endm
+            la a0, value
+
+            twice a1
+        .include "double.s"
+        .data
+        value:
+            .half 1, 2
+        "#;
+
+        let compilation_result =
+            Interpreter::compile_with_options(input.to_owned(), 28, options).unwrap();
+        let source_map = &compilation_result.source_map;
+
+        let la = source_map.location(0).unwrap();
+        assert_eq!(
+            (la.file.as_deref(), la.line, la.columns.clone()),
+            (None, 5, 12..24)
+        );
+
+        assert_eq!(source_map.pcs(None, 7), vec![4, 8]);
+        assert_eq!(source_map.breakpoint(None, 6), Some(4));
+        assert_eq!(source_map.breakpoint(None, 9), None);
+
+        let add = source_map.location(12).unwrap();
+        assert_eq!((add.file.as_deref(), add.line), (Some("double.s"), 0));
+        assert_eq!(source_map.pcs(Some("double.s"), 0), vec![12]);
+        assert!(source_map.location(16).is_none());
+
+        assert_eq!(source_map.data_location(27).unwrap().line, 11);
+        assert_eq!(source_map.data_addresses(None, 11), vec![24..28]);
+    }
+
+    #[test]
+    fn memory_layout() {
+        let input = r#"
+        main:
+            la a0, value
+            ld a1, 0(a0)
+            call done
+        done:
+        .data
+            .byte 1
+        value:
+            .dword 42
+        "#;
+        let options = CompileOptions {
+            layout: MemoryLayout {
+                text_base: 0x100,
+                data_base: Some(0x200),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let compilation_result =
+            Interpreter::compile_with_options(input.to_owned(), 0x400, options).unwrap();
+
+        assert_eq!(compilation_result.entry, 0x100);
+        assert_eq!(compilation_result.labels["done"], 0x10c);
+        assert_eq!(compilation_result.data_labels["value"], 0x201);
+        assert_eq!(compilation_result.instructions.len(), 0x10c / 4);
+        assert_eq!(compilation_result.layout.stack_top, Some(0x400));
+        assert_eq!(compilation_result.layout.heap_base, Some(0x210));
+
+        let mut core = RvCoreBuilder::default()
+            .instructions(compilation_result.instructions)
+            .memory(compilation_result.memory)
+            .build();
+        core.registers.pc = compilation_result.entry as u64;
+
+        for result in core.run() {
+            result.unwrap();
+        }
+
+        let registers = core.registers.snapshot();
+        assert_eq!(registers.x[10], 0x201);
+        assert_eq!(registers.x[11], 42);
+        assert_eq!(registers.x[1], 0x10c);
+
+        let Err(errors) = Interpreter::compile(input.to_owned(), 8) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(errors[0].code, Code::InvalidLayout);
+
+        // By default, data follows the machine code of the text
+        let compilation_result = Interpreter::compile(input.to_owned(), 0x40).unwrap();
+
+        assert_eq!(compilation_result.layout.data_base, Some(0x18));
+        assert_eq!(compilation_result.data_labels["value"], 0x19);
+
+        let layouts = [
+            MemoryLayout {
+                text_base: 0x1000,
+                ..Default::default()
+            },
+            MemoryLayout {
+                data_base: Some(0x8),
+                ..Default::default()
+            },
+            MemoryLayout {
+                section_bases: HashMap::from([(".data".to_owned(), usize::MAX)]),
+                ..Default::default()
+            },
+        ];
+
+        for layout in layouts {
+            let options = CompileOptions {
+                layout,
+                ..Default::default()
+            };
+            let Err(errors) = Interpreter::compile_with_options(input.to_owned(), 0x400, options)
+            else {
+                panic!("Expected compilation to fail");
+            };
+
+            assert_eq!(errors[0].code, Code::InvalidLayout);
+        }
+    }
+
+    #[test]
+    fn sections() {
+        let input = r#"
+        .section .text.startup,"ax",@progbits
+        main:
+            la a0, message
+            la a1, counter
+            la a2, table
+            la a3, value
+        .section .rodata.str1.1,"aMS",@progbits,1
+        message:
+            .byte 1, 2, 3
+        .bss
+        counter:
+            .zero 8
+        .section .lookup, "a"
+        table:
+            .word 7
+        .data
+        value:
+            .byte 5
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 64).unwrap();
+
+        assert_eq!(compilation_result.labels["main"], 0);
+        assert_eq!(compilation_result.data_labels["message"], 32);
+        assert_eq!(compilation_result.data_labels["value"], 40);
+        assert_eq!(compilation_result.data_labels["table"], 48);
+        assert_eq!(compilation_result.data_labels["counter"], 56);
+        assert_eq!(
+            compilation_result.data,
+            [1, 2, 3, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 7, 0, 0, 0]
+        );
+        assert_eq!(
+            compilation_result.symbol("table").unwrap().section,
+            Section::Named(".lookup".to_owned())
+        );
+        assert_eq!(
+            compilation_result.symbol("counter").unwrap().section,
+            Section::Bss
+        );
+        assert_eq!(compilation_result.layout.heap_base, Some(64));
+
+        let options = CompileOptions {
+            layout: MemoryLayout {
+                section_bases: HashMap::from([(".bss".to_owned(), 72)]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let compilation_result =
+            Interpreter::compile_with_options(input.to_owned(), 96, options).unwrap();
+
+        assert_eq!(compilation_result.data_labels["counter"], 72);
+        assert_eq!(compilation_result.layout.section_bases[".lookup"], 48);
+
+        let Err(errors) = Interpreter::compile(".bss\n.byte 1".to_owned(), 64) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(errors[0].code, Code::InvalidData);
+    }
+
+    #[test]
+    fn sections_out_of_source_order() {
+        let input = r#"
+            la a0, value
+            la a1, message
+        .data
+        value:
+            .word 1
+        .rodata
+        message:
+            .byte 2, 3
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 28).unwrap();
+        let source_map = &compilation_result.source_map;
+
+        assert_eq!(source_map.data_location(16).unwrap().line, 8);
+        assert_eq!(source_map.data_location(25).unwrap().line, 5);
+        assert!(source_map.data_location(18).is_none());
+
+        let expected = [
+            ".data",
+            "00000010                           message:",
+            "00000010  02 03                                                      .byte 2, 3",
+            "00000018                           value:",
+            "00000018  01 00 00 00                                                .word 1",
+        ];
+
+        assert_eq!(
+            compilation_result
+                .listing
+                .to_string()
+                .lines()
+                .skip_while(|line| *line != ".data")
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn alignment() {
+        let input = r#"
+        main:
+            li a0, 1
+        .align 3
+        aligned:
+            ret
+        .data
+            .ascii "abc"
+        .balign 4, 0xff
+        word:
+            .word 7
+            .byte 1
+        .p2align 3
+        dword:
+            .dword 9
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 64).unwrap();
+
+        assert_eq!(compilation_result.labels["aligned"], 8);
+        assert_eq!(
+            compilation_result.instructions[1],
+            Instruction::Addi(format::I {
+                rd: 0,
+                rs1: 0,
+                imm12: 0
+            })
+        );
+        assert_eq!(compilation_result.data_labels["word"], 20);
+        assert_eq!(compilation_result.data_labels["dword"], 32);
+        assert_eq!(
+            compilation_result.data,
+            [b'a', b'b', b'c', 0xff, 7, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        let input = r#"
+        main:
+            li a0, 0x12345
+            addi a1, a1, 1
+        .align 3
+        aligned:
+            ret
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 64).unwrap();
+
+        // The fused li takes a single slot, but two words of machine code
+        assert_eq!(compilation_result.labels["aligned"], 12);
+        assert_eq!(compilation_result.text.len(), 20);
+        assert_eq!(compilation_result.text[16..], 0x00008067u32.to_le_bytes());
+        assert!(compilation_result
+            .listing
+            .text
+            .contains(&ListingRow::Label {
+                address: 16,
+                name: "aligned".to_owned()
+            }));
+
+        let input = r#"
+        .data
+            .byte 1
+        value:
+            .word 2
+        "#;
+        let options = CompileOptions {
+            auto_align: true,
+            ..Default::default()
+        };
+
+        let compilation_result =
+            Interpreter::compile_with_options(input.to_owned(), 64, options).unwrap();
+
+        assert_eq!(compilation_result.data_labels["value"], 4);
+        assert_eq!(compilation_result.data, [1, 0, 0, 0, 2, 0, 0, 0]);
+
+        let Err(errors) = Interpreter::compile(".balign 3".to_owned(), 64) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(errors[0].code, Code::InvalidData);
+    }
+
+    #[test]
+    fn data_directives() {
+        let files = HashMap::from([("table.bin".to_owned(), "\x01\x02\x03\x04".to_owned())]);
+        let options = CompileOptions {
+            source_provider: Some(Rc::new(files)),
+            ..Default::default()
+        };
+
+        let input = r#"
+        .data
+            .space 2, 0xaa
+            .skip 1
+            .fill 2, 2, 0x1234
+            .uleb128 624485
+            .sleb128 -123456, 63, -64
+            .incbin "table.bin", 1, 2
+        "#;
+
+        let compilation_result =
+            Interpreter::compile_with_options(input.to_owned(), 64, options).unwrap();
+
+        assert_eq!(
+            compilation_result.data,
+            [
+                0xaa, 0xaa, 0, 0x34, 0x12, 0x34, 0x12, 0xe5, 0x8e, 0x26, 0xc0, 0xbb, 0x78, 0x3f,
+                0x40, 2, 3
+            ]
+        );
+
+        let Err(errors) = Interpreter::compile(".incbin \"table.bin\"".to_owned(), 64) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(errors[0].code, Code::InvalidInclude);
+
+        // Sizes are checked against the memory before anything is allocated
+        for directive in [
+            ".space 1 << 62",
+            ".zero 0x7fffffffffffffff",
+            ".fill 0x7fffffffffffffff, 8",
+        ] {
+            let Err(errors) = Interpreter::compile(format!(".data\n{}", directive), 64) else {
+                panic!("Expected {} to fail", directive);
+            };
+
+            assert_eq!(errors[0].code, Code::InvalidData);
+        }
+    }
+
+    #[test]
+    fn data_literals() {
+        let input = r#"
+        main:
+            li a0, '\n'
+            addi a1, zero, 0b1_0
+        .data
+            .byte -5, 'A', '\x7f', ','
+            .half 0xffff, -1
+            .word -1, 0x1_0000
+            .dword 0x8000000000000000
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 64).unwrap();
+
+        assert_eq!(
+            compilation_result.instructions[1],
+            Instruction::Addi(format::I {
+                rd: 11,
+                rs1: 0,
+                imm12: 2
+            })
+        );
+        assert_eq!(
+            compilation_result.data,
+            [
+                [0xfb, b'A', 0x7f, b','].as_slice(),
+                &[0xff, 0xff, 0xff, 0xff],
+                &[0xff, 0xff, 0xff, 0xff, 0, 0, 1, 0],
+                &[0, 0, 0, 0, 0, 0, 0, 0x80],
+            ]
+            .concat()
+        );
+
+        for directive in [
+            ".byte 256",
+            ".byte -129",
+            ".half -32769",
+            ".word 0x1_0000_0000",
+        ] {
+            let Err(errors) = Interpreter::compile(format!(".data\n{}", directive), 64) else {
+                panic!("Expected {} to fail", directive);
+            };
+
+            assert_eq!(errors[0].code, Code::InvalidData);
+        }
+    }
+
+    #[test]
+    fn strings() {
+        let input = r##"
+        .data
+        greeting:
+            .ascii "hi\n", "\t\x41\0"
+            .asciz "say \"#1\"", "\\" # both terminated
+            .string "a,b"
+        "##;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 64).unwrap();
+
+        assert_eq!(
+            compilation_result.data,
+            b"hi\n\tA\0say \"#1\"\0\\\0a,b\0".to_vec()
+        );
+
+        let input = ".data\n.asciz \"zażółć\"";
+
+        let Err(errors) = Interpreter::compile(input.to_owned(), 64) else {
+            panic!("Expected compilation to fail");
+        };
+
+        assert_eq!(errors[0].code, Code::InvalidData);
+
+        let options = CompileOptions {
+            utf8: true,
+            ..Default::default()
+        };
+
+        let compilation_result =
+            Interpreter::compile_with_options(input.to_owned(), 64, options).unwrap();
+
+        assert_eq!(compilation_result.data, "zażółć\0".as_bytes());
+    }
+
+    #[test]
+    fn half_precision_data() {
+        let input = r#"
+        .data
+            .float16 1.0, -0.0, inf, -inf, nan, 65504, 65520, 0.1, 5.960464477539063e-8
+            .half_float 1.00048828125, 1.0004882812500001
+            .bfloat16 1.0, -2.5, 3.0e38, 0.1, nan, 1.00390625, 1.0039062500000001
+        "#;
+
+        let compilation_result = Interpreter::compile(input.to_owned(), 64).unwrap();
+
+        let halves = compilation_result
+            .data
+            .chunks(2)
+            .map(|half| u16::from_le_bytes([half[0], half[1]]))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            halves,
+            [
+                0x3c00, 0x8000, 0x7c00, 0xfc00, 0x7e00, 0x7bff, 0x7c00, 0x2e66, 0x0001, 0x3c00,
+                0x3c01, 0x3f80, 0xc020, 0x7f62, 0x3dcd, 0x7fc0, 0x3f80, 0x3f81
+            ]
         );
     }
 }
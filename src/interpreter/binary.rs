@@ -0,0 +1,1240 @@
+use eeric_core::prelude::*;
+
+use super::{
+    diagnostic::{Code, Diagnostic},
+    encoder::*,
+    source::{strip_comment, Sources},
+};
+
+/// Decodes RISC-V machine code into the instructions `Decoder` produces from assembly
+pub struct BinaryDecoder;
+
+impl BinaryDecoder {
+    /// Decodes a flat little-endian binary, e.g. the text section of an object file
+    ///
+    /// The `line` of returned diagnostics is the index of the offending word.
+    pub fn decode_bytes(bytes: &[u8]) -> Result<Vec<Instruction>, Vec<Diagnostic>> {
+        if !bytes.len().is_multiple_of(4) {
+            return Err(vec![Diagnostic::error(
+                Code::IllegalInstruction,
+                format!(
+                    "Binary of {} bytes is not made of 32-bit words",
+                    bytes.len()
+                ),
+            )
+            .with_help("compressed instructions are not supported")]);
+        }
+
+        let words = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect::<Vec<_>>();
+
+        Self::decode_words(&words)
+    }
+
+    /// The `line` of returned diagnostics is the index of the offending word
+    pub fn decode_words(words: &[u32]) -> Result<Vec<Instruction>, Vec<Diagnostic>> {
+        let mut instructions = Vec::new();
+        let mut errors = Vec::new();
+
+        for (index, &word) in words.iter().enumerate() {
+            match Self::decode(word) {
+                Ok(instruction) => instructions.push(instruction),
+                Err(diagnostic) => errors.push(Diagnostic {
+                    line: index,
+                    ..diagnostic
+                }),
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(instructions),
+            false => Err(errors),
+        }
+    }
+
+    /// Decodes whitespace separated hexadecimal words, `#` starts a comment
+    pub fn decode_hex(dump: &str) -> Result<Vec<Instruction>, Vec<Diagnostic>> {
+        let sources = Sources::new(dump);
+        let mut instructions = Vec::new();
+        let mut errors = Vec::new();
+
+        for (line_address, line) in dump.lines().enumerate() {
+            for token in strip_comment(line).split_whitespace() {
+                let digits = token
+                    .strip_prefix("0x")
+                    .or_else(|| token.strip_prefix("0X"))
+                    .unwrap_or(token);
+
+                let decoded = u32::from_str_radix(digits, 16)
+                    .map_err(|_| {
+                        Diagnostic::error(
+                            Code::IllegalInstruction,
+                            format!("Invalid instruction word: {}", token),
+                        )
+                        .with_token(token)
+                        .with_help("words are written as 8 hexadecimal digits, e.g. 00a50533")
+                    })
+                    .and_then(|word| Self::decode(word).map_err(|err| err.with_token(token)));
+
+                match decoded {
+                    Ok(instruction) => instructions.push(instruction),
+                    Err(diagnostic) => errors.push(diagnostic.locate(&sources[line_address])),
+                }
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(instructions),
+            false => Err(errors),
+        }
+    }
+
+    pub fn decode(word: u32) -> Result<Instruction, Diagnostic> {
+        Self::decode_single(word).ok_or_else(|| {
+            let diagnostic = Diagnostic::error(
+                Code::IllegalInstruction,
+                format!("Unknown or unsupported instruction {:#010x}", word),
+            )
+            .with_token(format!("{:#010x}", word));
+
+            match word & 0b11 {
+                0b11 => diagnostic.with_help("supported extensions are RV64IMFD, Zicsr and V"),
+                _ => diagnostic.with_help("compressed instructions are not supported"),
+            }
+        })
+    }
+
+    fn decode_single(word: u32) -> Option<Instruction> {
+        use Instruction::*;
+
+        let funct3 = (word >> 12) & 0b111;
+        let funct7 = word >> 25;
+
+        Some(match word & 0b1111111 {
+            LOAD => match funct3 {
+                0b011 => Ld(i(word)),
+                0b010 => Lw(i(word)),
+                0b001 => Lh(i(word)),
+                0b000 => Lb(i(word)),
+                0b110 => Lwu(i(word)),
+                0b101 => Lhu(i(word)),
+                0b100 => Lbu(i(word)),
+                _ => return None,
+            },
+            LOAD_FP => match funct3 {
+                0b010 => Flw(i(word)),
+                0b011 => Fld(i(word)),
+                _ => return Self::decode_vector_load(word),
+            },
+            OP_IMM => match (funct3, word >> 26) {
+                (0b001, 0b000000) => Slli(shift(word, 6)),
+                (0b101, 0b000000) => Srli(shift(word, 6)),
+                (0b101, 0b010000) => Srai(shift(word, 6)),
+                (0b001 | 0b101, _) => return None,
+                _ => match funct3 {
+                    0b000 => Addi(i(word)),
+                    0b010 => Slti(i(word)),
+                    0b011 => Sltiu(i(word)),
+                    0b111 => Andi(i(word)),
+                    0b110 => Ori(i(word)),
+                    0b100 => Xori(i(word)),
+                    _ => return None,
+                },
+            },
+            OP_IMM_32 => match (funct3, funct7) {
+                (0b000, _) => Addiw(i(word)),
+                (0b001, 0b0000000) => Slliw(shift(word, 5)),
+                (0b101, 0b0000000) => Srliw(shift(word, 5)),
+                (0b101, 0b0100000) => Sraiw(shift(word, 5)),
+                _ => return None,
+            },
+            AUIPC => Auipc(u(word)),
+            LUI => Lui(u(word)),
+            STORE => match funct3 {
+                0b011 => Sd(s(word)),
+                0b010 => Sw(s(word)),
+                0b001 => Sh(s(word)),
+                0b000 => Sb(s(word)),
+                _ => return None,
+            },
+            STORE_FP => match funct3 {
+                0b010 => Fsw(s(word)),
+                0b011 => Fsd(s(word)),
+                _ => return Self::decode_vector_store(word),
+            },
+            OP => match (funct3, funct7) {
+                (0b000, 0b0000000) => Add(r(word)),
+                (0b000, 0b0100000) => Sub(r(word)),
+                (0b010, 0b0000000) => Slt(r(word)),
+                (0b011, 0b0000000) => Sltu(r(word)),
+                (0b111, 0b0000000) => And(r(word)),
+                (0b110, 0b0000000) => Or(r(word)),
+                (0b100, 0b0000000) => Xor(r(word)),
+                (0b001, 0b0000000) => Sll(r(word)),
+                (0b101, 0b0000000) => Srl(r(word)),
+                (0b101, 0b0100000) => Sra(r(word)),
+                (0b000, 0b0000001) => Mul(r(word)),
+                (0b001, 0b0000001) => Mulh(r(word)),
+                (0b010, 0b0000001) => Mulhsu(r(word)),
+                (0b011, 0b0000001) => Mulhu(r(word)),
+                (0b100, 0b0000001) => Div(r(word)),
+                (0b101, 0b0000001) => Divu(r(word)),
+                (0b110, 0b0000001) => Rem(r(word)),
+                (0b111, 0b0000001) => Remu(r(word)),
+                _ => return None,
+            },
+            OP_32 => match (funct3, funct7) {
+                (0b000, 0b0000000) => Addw(r(word)),
+                (0b000, 0b0100000) => Subw(r(word)),
+                (0b001, 0b0000000) => Sllw(r(word)),
+                (0b101, 0b0000000) => Srlw(r(word)),
+                (0b101, 0b0100000) => Sraw(r(word)),
+                (0b000, 0b0000001) => Mulw(r(word)),
+                (0b100, 0b0000001) => Divw(r(word)),
+                (0b101, 0b0000001) => Divuw(r(word)),
+                (0b110, 0b0000001) => Remw(r(word)),
+                (0b111, 0b0000001) => Remuw(r(word)),
+                _ => return None,
+            },
+            MADD | MSUB | NMSUB | NMADD => return Self::decode_r4(word),
+            OP_FP => return Self::decode_float(word),
+            OP_V => return Self::decode_vector(word),
+            BRANCH => match funct3 {
+                0b000 => Beq(b(word)),
+                0b001 => Bne(b(word)),
+                0b101 => Bge(b(word)),
+                0b111 => Bgeu(b(word)),
+                0b100 => Blt(b(word)),
+                0b110 => Bltu(b(word)),
+                _ => return None,
+            },
+            JALR if funct3 == 0b000 => Jalr(i(word)),
+            JAL => Jal(j(word)),
+            SYSTEM => match funct3 {
+                0b001 => Csrrw(csrr(word)),
+                0b010 => Csrrs(csrr(word)),
+                0b011 => Csrrc(csrr(word)),
+                0b101 => Csrrwi(csri(word)),
+                0b110 => Csrrsi(csri(word)),
+                0b111 => Csrrci(csri(word)),
+                _ => return None,
+            },
+            _ => return None,
+        })
+    }
+
+    fn decode_r4(word: u32) -> Option<Instruction> {
+        use Instruction::*;
+
+        Some(match (word & 0b1111111, (word >> 25) & 0b11) {
+            (MADD, S) => Fmadds(r4(word)),
+            (MSUB, S) => Fmsubs(r4(word)),
+            (NMSUB, S) => Fnmsubs(r4(word)),
+            (NMADD, S) => Fnmadds(r4(word)),
+            (MADD, D) => Fmaddd(r4(word)),
+            (MSUB, D) => Fmsubd(r4(word)),
+            (NMSUB, D) => Fnmsubd(r4(word)),
+            (NMADD, D) => Fnmaddd(r4(word)),
+            _ => return None,
+        })
+    }
+
+    fn decode_float(word: u32) -> Option<Instruction> {
+        use Instruction::*;
+
+        let funct5 = word >> 27;
+        let fmt = (word >> 25) & 0b11;
+        let rm = (word >> 12) & 0b111;
+        // Operations with a single source operand select a variant with rs2
+        let variant = (word >> 20) & 0b11111;
+
+        Some(match (funct5, fmt, rm, variant) {
+            (0b00000, S, _, _) => Fadds(r(word)),
+            (0b00001, S, _, _) => Fsubs(r(word)),
+            (0b00010, S, _, _) => Fmuls(r(word)),
+            (0b00011, S, _, _) => Fdivs(r(word)),
+            (0b00100, S, 0b000, _) => Fsgnjs(r(word)),
+            (0b00100, S, 0b001, _) => Fsgnjns(r(word)),
+            (0b00100, S, 0b010, _) => Fsgnjxs(r(word)),
+            (0b00101, S, 0b000, _) => Fmins(r(word)),
+            (0b00101, S, 0b001, _) => Fmaxs(r(word)),
+            (0b10100, S, 0b010, _) => Feqs(r(word)),
+            (0b10100, S, 0b001, _) => Flts(r(word)),
+            (0b10100, S, 0b000, _) => Fles(r(word)),
+            (0b00000, D, _, _) => Faddd(r(word)),
+            (0b00001, D, _, _) => Fsubd(r(word)),
+            (0b00010, D, _, _) => Fmuld(r(word)),
+            (0b00011, D, _, _) => Fdivd(r(word)),
+            (0b00100, D, 0b000, _) => Fsgnjd(r(word)),
+            (0b00100, D, 0b001, _) => Fsgnjnd(r(word)),
+            (0b00100, D, 0b010, _) => Fsgnjxd(r(word)),
+            (0b00101, D, 0b000, _) => Fmind(r(word)),
+            (0b00101, D, 0b001, _) => Fmaxd(r(word)),
+            (0b10100, D, 0b010, _) => Feqd(r(word)),
+            (0b10100, D, 0b001, _) => Fltd(r(word)),
+            (0b10100, D, 0b000, _) => Fled(r(word)),
+            (0b01011, S, _, 0) => Fsqrts(fp_unary(word)),
+            (0b11000, S, _, 0) => Fcvtws(fp_unary(word)),
+            (0b11000, S, _, 1) => Fcvtwus(fp_unary(word)),
+            (0b11100, S, 0b000, 0) => Fmvxw(fp_unary(word)),
+            (0b11100, S, 0b001, 0) => Fclasss(fp_unary(word)),
+            (0b11010, S, _, 0) => Fcvtsw(fp_unary(word)),
+            (0b11010, S, _, 1) => Fcvtswu(fp_unary(word)),
+            (0b11110, S, 0b000, 0) => Fmvwx(fp_unary(word)),
+            (0b11000, S, _, 2) => Fcvtls(fp_unary(word)),
+            (0b11000, S, _, 3) => Fcvtlus(fp_unary(word)),
+            (0b11010, S, _, 2) => Fcvtsl(fp_unary(word)),
+            (0b11010, S, _, 3) => Fcvtslu(fp_unary(word)),
+            (0b01011, D, _, 0) => Fsqrtd(fp_unary(word)),
+            (0b01000, S, _, 1) => Fcvtsd(fp_unary(word)),
+            (0b01000, D, _, 0) => Fcvtds(fp_unary(word)),
+            (0b11100, D, 0b001, 0) => Fclassd(fp_unary(word)),
+            (0b11000, D, _, 0) => Fcvtwd(fp_unary(word)),
+            (0b11000, D, _, 1) => Fcvtwud(fp_unary(word)),
+            (0b11010, D, _, 0) => Fcvtdw(fp_unary(word)),
+            (0b11010, D, _, 1) => Fcvtdwu(fp_unary(word)),
+            (0b11000, D, _, 2) => Fcvtld(fp_unary(word)),
+            (0b11000, D, _, 3) => Fcvtlud(fp_unary(word)),
+            (0b11100, D, 0b000, 0) => Fmvxd(fp_unary(word)),
+            (0b11010, D, _, 2) => Fcvtdl(fp_unary(word)),
+            (0b11010, D, _, 3) => Fcvtdlu(fp_unary(word)),
+            (0b11110, D, 0b000, 0) => Fmvdx(fp_unary(word)),
+            _ => return None,
+        })
+    }
+
+    fn decode_vector(word: u32) -> Option<Instruction> {
+        use Instruction::*;
+
+        let funct3 = (word >> 12) & 0b111;
+        let funct6 = word >> 26;
+        let masked = (word >> 25) & 1 == 0;
+
+        if funct3 == OPCFG {
+            return Some(match word >> 30 {
+                0b00 | 0b01 => Vsetvli(format::Vsetvli {
+                    rd: rd(word),
+                    rs1: rs1(word),
+                    vtypei: (word >> 20) & 0x7ff,
+                }),
+                0b11 => Vsetivli(format::Vsetivli {
+                    rd: rd(word),
+                    uimm: rs1(word) as u32,
+                    vtypei: (word >> 20) & 0x3ff,
+                }),
+                _ if word >> 25 == 0b1000000 => Vsetvl(format::Vsetvl {
+                    rd: rd(word),
+                    rs1: rs1(word),
+                    rs2: rs2(word),
+                }),
+                _ => return None,
+            });
+        }
+
+        Some(match (funct3, funct6) {
+            (OPIVV, 0b000000) => Vaddvv(opivv(word)),
+            (OPIVV, 0b000010) => Vsubvv(opivv(word)),
+            (OPIVV, 0b000100) => Vminuvv(opivv(word)),
+            (OPIVV, 0b000101) => Vminvv(opivv(word)),
+            (OPIVV, 0b000110) => Vmaxuvv(opivv(word)),
+            (OPIVV, 0b000111) => Vmaxvv(opivv(word)),
+            (OPIVV, 0b001001) => Vandvv(opivv(word)),
+            (OPIVV, 0b001010) => Vorvv(opivv(word)),
+            (OPIVV, 0b001011) => Vxorvv(opivv(word)),
+            (OPIVV, 0b001100) => Vrgathervv(opivv(word)),
+            (OPIVV, 0b001110) => Vrgatherei16vv(opivv(word)),
+            (OPIVV, 0b011000) => Vmseqvv(opivv(word)),
+            (OPIVV, 0b011001) => Vmsnevv(opivv(word)),
+            (OPIVV, 0b011010) => Vmsltuvv(opivv(word)),
+            (OPIVV, 0b011011) => Vmsltvv(opivv(word)),
+            (OPIVV, 0b011100) => Vmsleuvv(opivv(word)),
+            (OPIVV, 0b011101) => Vmslevv(opivv(word)),
+            (OPIVV, 0b100000) => Vsadduvv(opivv(word)),
+            (OPIVV, 0b100001) => Vsaddvv(opivv(word)),
+            (OPIVV, 0b100010) => Vssubuvv(opivv(word)),
+            (OPIVV, 0b100011) => Vssubvv(opivv(word)),
+            (OPIVV, 0b100101) => Vsllvv(opivv(word)),
+            (OPIVV, 0b100111) => Vsmulvv(opivv(word)),
+            (OPIVV, 0b101000) => Vsrlvv(opivv(word)),
+            (OPIVV, 0b101001) => Vsravv(opivv(word)),
+            (OPIVV, 0b101010) => Vssrlvv(opivv(word)),
+            (OPIVV, 0b101011) => Vssravv(opivv(word)),
+            (OPIVV, 0b101100) => Vnsrlwv(opivv(word)),
+            (OPIVV, 0b101101) => Vnsrawv(opivv(word)),
+            (OPIVV, 0b101110) => Vnclipuwv(opivv(word)),
+            (OPIVV, 0b101111) => Vnclipwv(opivv(word)),
+            (OPIVV, 0b110000) => Vwredsumuvs(opivv(word)),
+            (OPIVV, 0b110001) => Vwredsumvs(opivv(word)),
+
+            (OPIVX, 0b000000) => Vaddvx(opivx(word)),
+            (OPIVX, 0b000010) => Vsubvx(opivx(word)),
+            (OPIVX, 0b000011) => Vrsubvx(opivx(word)),
+            (OPIVX, 0b000100) => Vminuvx(opivx(word)),
+            (OPIVX, 0b000101) => Vminvx(opivx(word)),
+            (OPIVX, 0b000110) => Vmaxuvx(opivx(word)),
+            (OPIVX, 0b000111) => Vmaxvx(opivx(word)),
+            (OPIVX, 0b001001) => Vandvx(opivx(word)),
+            (OPIVX, 0b001010) => Vorvx(opivx(word)),
+            (OPIVX, 0b001011) => Vxorvx(opivx(word)),
+            (OPIVX, 0b001100) => Vrgathervx(opivx(word)),
+            (OPIVX, 0b001110) => Vslideupvx(opivx(word)),
+            (OPIVX, 0b001111) => Vslidedownvx(opivx(word)),
+            (OPIVX, 0b011000) => Vmseqvx(opivx(word)),
+            (OPIVX, 0b011001) => Vmsnevx(opivx(word)),
+            (OPIVX, 0b011010) => Vmsltuvx(opivx(word)),
+            (OPIVX, 0b011011) => Vmsltvx(opivx(word)),
+            (OPIVX, 0b011100) => Vmsleuvx(opivx(word)),
+            (OPIVX, 0b011101) => Vmslevx(opivx(word)),
+            (OPIVX, 0b011110) => Vmsgtuvx(opivx(word)),
+            (OPIVX, 0b011111) => Vmsgtvx(opivx(word)),
+            (OPIVX, 0b100000) => Vsadduvx(opivx(word)),
+            (OPIVX, 0b100001) => Vsaddvx(opivx(word)),
+            (OPIVX, 0b100010) => Vssubuvx(opivx(word)),
+            (OPIVX, 0b100011) => Vssubvx(opivx(word)),
+            (OPIVX, 0b100101) => Vsllvx(opivx(word)),
+            (OPIVX, 0b100111) => Vsmulvx(opivx(word)),
+            (OPIVX, 0b101000) => Vsrlvx(opivx(word)),
+            (OPIVX, 0b101001) => Vsravx(opivx(word)),
+            (OPIVX, 0b101010) => Vssrlvx(opivx(word)),
+            (OPIVX, 0b101011) => Vssravx(opivx(word)),
+            (OPIVX, 0b101100) => Vnsrlwx(opivx(word)),
+            (OPIVX, 0b101101) => Vnsrawx(opivx(word)),
+            (OPIVX, 0b101110) => Vnclipuwx(opivx(word)),
+            (OPIVX, 0b101111) => Vnclipwx(opivx(word)),
+
+            (OPIVI, 0b000000) => Vaddvi(opivi(word)),
+            (OPIVI, 0b000011) => Vrsubvi(opivi(word)),
+            (OPIVI, 0b001001) => Vandvi(opivi(word)),
+            (OPIVI, 0b001010) => Vorvi(opivi(word)),
+            (OPIVI, 0b001011) => Vxorvi(opivi(word)),
+            (OPIVI, 0b001100) => Vrgathervi(opivi_unsigned(word)),
+            (OPIVI, 0b001110) => Vslideupvi(opivi_unsigned(word)),
+            (OPIVI, 0b001111) => Vslidedownvi(opivi_unsigned(word)),
+            (OPIVI, 0b011000) => Vmseqvi(opivi(word)),
+            (OPIVI, 0b011001) => Vmsnevi(opivi(word)),
+            (OPIVI, 0b011100) => Vmsleuvi(opivi(word)),
+            (OPIVI, 0b011101) => Vmslevi(opivi(word)),
+            (OPIVI, 0b011110) => Vmsgtuvi(opivi(word)),
+            (OPIVI, 0b011111) => Vmsgtvi(opivi(word)),
+            (OPIVI, 0b100000) => Vsadduvi(opivi(word)),
+            (OPIVI, 0b100001) => Vsaddvi(opivi(word)),
+            (OPIVI, 0b100101) => Vsllvi(opivi_unsigned(word)),
+            (OPIVI, 0b101000) => Vsrlvi(opivi_unsigned(word)),
+            (OPIVI, 0b101001) => Vsravi(opivi_unsigned(word)),
+            (OPIVI, 0b101010) => Vssrlvi(opivi_unsigned(word)),
+            (OPIVI, 0b101011) => Vssravi(opivi_unsigned(word)),
+            (OPIVI, 0b101100) => Vnsrlwi(opivi_unsigned(word)),
+            (OPIVI, 0b101101) => Vnsrawi(opivi_unsigned(word)),
+            (OPIVI, 0b101110) => Vnclipuwi(opivi_unsigned(word)),
+            (OPIVI, 0b101111) => Vnclipwi(opivi_unsigned(word)),
+
+            (OPMVV, 0b000000) => Vredsumvs(opmvv(word)),
+            (OPMVV, 0b000001) => Vredandvs(opmvv(word)),
+            (OPMVV, 0b000010) => Vredorvs(opmvv(word)),
+            (OPMVV, 0b000011) => Vredxorvs(opmvv(word)),
+            (OPMVV, 0b000100) => Vredminuvs(opmvv(word)),
+            (OPMVV, 0b000101) => Vredminvs(opmvv(word)),
+            (OPMVV, 0b000110) => Vredmaxuvs(opmvv(word)),
+            (OPMVV, 0b000111) => Vredmaxvs(opmvv(word)),
+            (OPMVV, 0b001000) => Vaadduvv(opmvv(word)),
+            (OPMVV, 0b001001) => Vaaddvv(opmvv(word)),
+            (OPMVV, 0b001010) => Vasubuvv(opmvv(word)),
+            (OPMVV, 0b001011) => Vasubvv(opmvv(word)),
+            (OPMVV, 0b100000) => Vdivuvv(opmvv(word)),
+            (OPMVV, 0b100001) => Vdivvv(opmvv(word)),
+            (OPMVV, 0b100010) => Vremuvv(opmvv(word)),
+            (OPMVV, 0b100011) => Vremvv(opmvv(word)),
+            (OPMVV, 0b100100) => Vmulhuvv(opmvv(word)),
+            (OPMVV, 0b100101) => Vmulvv(opmvv(word)),
+            (OPMVV, 0b100110) => Vmulhsuvv(opmvv(word)),
+            (OPMVV, 0b100111) => Vmulhvv(opmvv(word)),
+            (OPMVV, 0b101001) => Vmaddvv(opmvv(word)),
+            (OPMVV, 0b101011) => Vnmsubvv(opmvv(word)),
+            (OPMVV, 0b101101) => Vmaccvv(opmvv(word)),
+            (OPMVV, 0b101111) => Vnmsacvv(opmvv(word)),
+            (OPMVV, 0b110000) => Vwadduvv(opmvv(word)),
+            (OPMVV, 0b110001) => Vwaddvv(opmvv(word)),
+            (OPMVV, 0b110010) => Vwsubuvv(opmvv(word)),
+            (OPMVV, 0b110011) => Vwsubvv(opmvv(word)),
+            (OPMVV, 0b110100) => Vwadduwv(opmvv(word)),
+            (OPMVV, 0b110101) => Vwaddwv(opmvv(word)),
+            (OPMVV, 0b110110) => Vwsubuwv(opmvv(word)),
+            (OPMVV, 0b110111) => Vwsubwv(opmvv(word)),
+            (OPMVV, 0b111000) => Vwmuluvv(opmvv(word)),
+            (OPMVV, 0b111010) => Vwmulsuvv(opmvv(word)),
+            (OPMVV, 0b111011) => Vwmulvv(opmvv(word)),
+            (OPMVV, 0b111100) => Vwmaccuvv(opmvv(word)),
+            (OPMVV, 0b111101) => Vwmaccvv(opmvv(word)),
+            (OPMVV, 0b111111) => Vwmaccsuvv(opmvv(word)),
+
+            (OPMVX, 0b001000) => Vaadduvx(opmvx(word)),
+            (OPMVX, 0b001001) => Vaaddvx(opmvx(word)),
+            (OPMVX, 0b001010) => Vasubuvx(opmvx(word)),
+            (OPMVX, 0b001011) => Vasubvx(opmvx(word)),
+            (OPMVX, 0b001110) => Vslide1upvx(opmvx(word)),
+            (OPMVX, 0b001111) => Vslide1downvx(opmvx(word)),
+            (OPMVX, 0b100000) => Vdivuvx(opmvx(word)),
+            (OPMVX, 0b100001) => Vdivvx(opmvx(word)),
+            (OPMVX, 0b100010) => Vremuvx(opmvx(word)),
+            (OPMVX, 0b100011) => Vremvx(opmvx(word)),
+            (OPMVX, 0b100100) => Vmulhuvx(opmvx(word)),
+            (OPMVX, 0b100101) => Vmulvx(opmvx(word)),
+            (OPMVX, 0b100110) => Vmulhsuvx(opmvx(word)),
+            (OPMVX, 0b100111) => Vmulhvx(opmvx(word)),
+            (OPMVX, 0b101001) => Vmaddvx(opmvx(word)),
+            (OPMVX, 0b101011) => Vnmsubvx(opmvx(word)),
+            (OPMVX, 0b101101) => Vmaccvx(opmvx(word)),
+            (OPMVX, 0b101111) => Vnmsacvx(opmvx(word)),
+            (OPMVX, 0b110000) => Vwadduvx(opmvx(word)),
+            (OPMVX, 0b110001) => Vwaddvx(opmvx(word)),
+            (OPMVX, 0b110010) => Vwsubuvx(opmvx(word)),
+            (OPMVX, 0b110011) => Vwsubvx(opmvx(word)),
+            (OPMVX, 0b110100) => Vwadduwx(opmvx(word)),
+            (OPMVX, 0b110101) => Vwaddwx(opmvx(word)),
+            (OPMVX, 0b110110) => Vwsubuwx(opmvx(word)),
+            (OPMVX, 0b110111) => Vwsubwx(opmvx(word)),
+            (OPMVX, 0b111000) => Vwmuluvx(opmvx(word)),
+            (OPMVX, 0b111010) => Vwmulsuvx(opmvx(word)),
+            (OPMVX, 0b111011) => Vwmulvx(opmvx(word)),
+            (OPMVX, 0b111100) => Vwmaccuvx(opmvx(word)),
+            (OPMVX, 0b111101) => Vwmaccvx(opmvx(word)),
+            (OPMVX, 0b111110) => Vwmaccusvx(opmvx(word)),
+            (OPMVX, 0b111111) => Vwmaccsuvx(opmvx(word)),
+
+            (OPFVV, 0b000000) => Vfaddvv(opfvv(word)),
+            (OPFVV, 0b000001) => Vfredusumvs(opfvv(word)),
+            (OPFVV, 0b000010) => Vfsubvv(opfvv(word)),
+            (OPFVV, 0b000011) => Vfredosumvs(opfvv(word)),
+            (OPFVV, 0b000100) => Vfminvv(opfvv(word)),
+            (OPFVV, 0b000101) => Vfredminvs(opfvv(word)),
+            (OPFVV, 0b000110) => Vfmaxvv(opfvv(word)),
+            (OPFVV, 0b000111) => Vfredmaxvs(opfvv(word)),
+            (OPFVV, 0b001000) => Vfsgnjvv(opfvv(word)),
+            (OPFVV, 0b001001) => Vfsgnjnvv(opfvv(word)),
+            (OPFVV, 0b001010) => Vfsgnjxvv(opfvv(word)),
+            (OPFVV, 0b011000) => Vmfeqvv(opfvv(word)),
+            (OPFVV, 0b011001) => Vmflevv(opfvv(word)),
+            (OPFVV, 0b011011) => Vmfltvv(opfvv(word)),
+            (OPFVV, 0b011100) => Vmfnevv(opfvv(word)),
+            (OPFVV, 0b100000) => Vfdivvv(opfvv(word)),
+            (OPFVV, 0b100100) => Vfmulvv(opfvv(word)),
+            (OPFVV, 0b101000) => Vfmaddvv(opfvv(word)),
+            (OPFVV, 0b101001) => Vfnmaddvv(opfvv(word)),
+            (OPFVV, 0b101010) => Vfmsubvv(opfvv(word)),
+            (OPFVV, 0b101011) => Vfnmsubvv(opfvv(word)),
+            (OPFVV, 0b101100) => Vfmaccvv(opfvv(word)),
+            (OPFVV, 0b101101) => Vfnmaccvv(opfvv(word)),
+            (OPFVV, 0b101110) => Vfmsacvv(opfvv(word)),
+            (OPFVV, 0b101111) => Vfnmsacvv(opfvv(word)),
+            (OPFVV, 0b110000) => Vfwaddvv(opfvv(word)),
+            (OPFVV, 0b110001) => Vfwredusumvs(opfvv(word)),
+            (OPFVV, 0b110010) => Vfwsubvv(opfvv(word)),
+            (OPFVV, 0b110011) => Vfwredosumvs(opfvv(word)),
+            (OPFVV, 0b110100) => Vfwaddwv(opfvv(word)),
+            (OPFVV, 0b110110) => Vfwsubwv(opfvv(word)),
+            (OPFVV, 0b111000) => Vfwmulvv(opfvv(word)),
+            (OPFVV, 0b111100) => Vfwmaccvv(opfvv(word)),
+            (OPFVV, 0b111101) => Vfwnmaccvv(opfvv(word)),
+            (OPFVV, 0b111110) => Vfwmsacvv(opfvv(word)),
+            (OPFVV, 0b111111) => Vfwnmsacvv(opfvv(word)),
+
+            (OPFVF, 0b000000) => Vfaddvf(opfvf(word)),
+            (OPFVF, 0b000010) => Vfsubvf(opfvf(word)),
+            (OPFVF, 0b000100) => Vfminvf(opfvf(word)),
+            (OPFVF, 0b000110) => Vfmaxvf(opfvf(word)),
+            (OPFVF, 0b001000) => Vfsgnjvf(opfvf(word)),
+            (OPFVF, 0b001001) => Vfsgnjnvf(opfvf(word)),
+            (OPFVF, 0b001010) => Vfsgnjxvf(opfvf(word)),
+            (OPFVF, 0b001110) => Vfslide1upvf(opfvf(word)),
+            (OPFVF, 0b001111) => Vfslide1downvf(opfvf(word)),
+            (OPFVF, 0b011000) => Vmfeqvf(opfvf(word)),
+            (OPFVF, 0b011001) => Vmflevf(opfvf(word)),
+            (OPFVF, 0b011011) => Vmfltvf(opfvf(word)),
+            (OPFVF, 0b011100) => Vmfnevf(opfvf(word)),
+            (OPFVF, 0b011101) => Vmfgtvf(opfvf(word)),
+            (OPFVF, 0b011111) => Vmfgevf(opfvf(word)),
+            (OPFVF, 0b100000) => Vfdivvf(opfvf(word)),
+            (OPFVF, 0b100001) => Vfrdivvf(opfvf(word)),
+            (OPFVF, 0b100100) => Vfmulvf(opfvf(word)),
+            (OPFVF, 0b100111) => Vfrsubvf(opfvf(word)),
+            (OPFVF, 0b101000) => Vfmaddvf(opfvf(word)),
+            (OPFVF, 0b101001) => Vfnmaddvf(opfvf(word)),
+            (OPFVF, 0b101010) => Vfmsubvf(opfvf(word)),
+            (OPFVF, 0b101011) => Vfnmsubvf(opfvf(word)),
+            (OPFVF, 0b101100) => Vfmaccvf(opfvf(word)),
+            (OPFVF, 0b101101) => Vfnmaccvf(opfvf(word)),
+            (OPFVF, 0b101110) => Vfmsacvf(opfvf(word)),
+            (OPFVF, 0b101111) => Vfnmsacvf(opfvf(word)),
+            (OPFVF, 0b110000) => Vfwaddvf(opfvf(word)),
+            (OPFVF, 0b110010) => Vfwsubvf(opfvf(word)),
+            (OPFVF, 0b110100) => Vfwaddwf(opfvf(word)),
+            (OPFVF, 0b110110) => Vfwsubwf(opfvf(word)),
+            (OPFVF, 0b111000) => Vfwmulvf(opfvf(word)),
+            (OPFVF, 0b111100) => Vfwmaccvf(opfvf(word)),
+            (OPFVF, 0b111101) => Vfwnmaccvf(opfvf(word)),
+            (OPFVF, 0b111110) => Vfwmsacvf(opfvf(word)),
+            (OPFVF, 0b111111) => Vfwnmsacvf(opfvf(word)),
+
+            // Carry-in and merge variants always read v0, i.e. are encoded as masked
+            (OPIVV, 0b010000) if masked => Vadcvvm(opivv(word)),
+            (OPIVX, 0b010000) if masked => Vadcvxm(opivx(word)),
+            (OPIVI, 0b010000) if masked => Vadcvim(opivi(word)),
+            (OPIVV, 0b010001) if masked => Vmadcvvm(opivv(word)),
+            (OPIVX, 0b010001) if masked => Vmadcvxm(opivx(word)),
+            (OPIVI, 0b010001) if masked => Vmadcvim(opivi(word)),
+            (OPIVV, 0b010001) => Vmadcvv(opivv(word)),
+            (OPIVX, 0b010001) => Vmadcvx(opivx(word)),
+            (OPIVI, 0b010001) => Vmadcvi(opivi(word)),
+            (OPIVV, 0b010010) if masked => Vsbcvvm(opivv(word)),
+            (OPIVX, 0b010010) if masked => Vsbcvxm(opivx(word)),
+            (OPIVV, 0b010011) if masked => Vmsbcvvm(opivv(word)),
+            (OPIVX, 0b010011) if masked => Vmsbcvxm(opivx(word)),
+            (OPIVV, 0b010011) => Vmsbcvv(opivv(word)),
+            (OPIVX, 0b010011) => Vmsbcvx(opivx(word)),
+            (OPIVV, 0b010111) if masked => Vmergevvm(opivv(word)),
+            (OPIVX, 0b010111) if masked => Vmergevxm(opivx(word)),
+            (OPIVI, 0b010111) if masked => Vmergevim(opivi(word)),
+            (OPIVV, 0b010111) if vs2(word) == 0 => Vmvvv(opivv(word)),
+            (OPIVX, 0b010111) if vs2(word) == 0 => Vmvvx(opivx(word)),
+            (OPIVI, 0b010111) if vs2(word) == 0 => Vmvvi(opivi(word)),
+            (OPIVI, 0b100111) => match rs1(word) {
+                0 => Vmv1rv(opivi(word)),
+                1 => Vmv2rv(opivi(word)),
+                3 => Vmv4rv(opivi(word)),
+                7 => Vmv8rv(opivi(word)),
+                _ => return None,
+            },
+
+            // Unary instructions select the operation with their vs1 or vs2 field
+            (OPMVV, 0b010000) => match vs1(word) {
+                0b00000 => Vmvxs(opmvv_unary(word)),
+                0b10000 => Vcpopm(opmvv_unary(word)),
+                0b10001 => Vfirstm(opmvv_unary(word)),
+                _ => return None,
+            },
+            (OPMVX, 0b010000) if vs2(word) == 0 => Vmvsx(opmvx(word)),
+            (OPMVV, 0b010010) => match vs1(word) {
+                0b00010 => Vzextvf8(opmvv_unary(word)),
+                0b00011 => Vsextvf8(opmvv_unary(word)),
+                0b00100 => Vzextvf4(opmvv_unary(word)),
+                0b00101 => Vsextvf4(opmvv_unary(word)),
+                0b00110 => Vzextvf2(opmvv_unary(word)),
+                0b00111 => Vsextvf2(opmvv_unary(word)),
+                _ => return None,
+            },
+            (OPMVV, 0b010100) => match vs1(word) {
+                0b00001 => Vmsbfm(opmvv_unary(word)),
+                0b00010 => Vmsofm(opmvv_unary(word)),
+                0b00011 => Vmsifm(opmvv_unary(word)),
+                0b10000 => Viotam(opmvv_unary(word)),
+                0b10001 => Vidv(opmvv_unary(word)),
+                _ => return None,
+            },
+            (OPMVV, 0b010111) => Vcompressvm(opmvv(word)),
+            (OPMVV, 0b011000) => Vmandnmm(opmvv(word)),
+            (OPMVV, 0b011001) => Vmandmm(opmvv(word)),
+            (OPMVV, 0b011010) => Vmormm(opmvv(word)),
+            (OPMVV, 0b011011) => Vmxormm(opmvv(word)),
+            (OPMVV, 0b011100) => Vmornmm(opmvv(word)),
+            (OPMVV, 0b011101) => Vmnandmm(opmvv(word)),
+            (OPMVV, 0b011110) => Vmnormm(opmvv(word)),
+            (OPMVV, 0b011111) => Vmxnormm(opmvv(word)),
+            (OPFVV, 0b010000) if vs1(word) == 0 => Vfmvfs(opfvv_unary(word)),
+            (OPFVF, 0b010000) if vs2(word) == 0 => Vfmvsf(opfvf(word)),
+            (OPFVV, 0b010010) => match vs1(word) {
+                0b00000 => Vfcvtxufv(opfvv_unary(word)),
+                0b00001 => Vfcvtxfv(opfvv_unary(word)),
+                0b00010 => Vfcvtfxuv(opfvv_unary(word)),
+                0b00011 => Vfcvtfxv(opfvv_unary(word)),
+                0b00110 => VfcvtRtzxufv(opfvv_unary(word)),
+                0b00111 => VfcvtRtzxfv(opfvv_unary(word)),
+                0b01000 => Vfwcvtxufv(opfvv_unary(word)),
+                0b01001 => Vfwcvtxfv(opfvv_unary(word)),
+                0b01010 => Vfwcvtfxuv(opfvv_unary(word)),
+                0b01011 => Vfwcvtfxv(opfvv_unary(word)),
+                0b01100 => Vfwcvtffv(opfvv_unary(word)),
+                0b01110 => VfwcvtRtzxufv(opfvv_unary(word)),
+                0b01111 => VfwcvtRtzxfv(opfvv_unary(word)),
+                0b10000 => Vfncvtxufw(opfvv_unary(word)),
+                0b10001 => Vfncvtxfw(opfvv_unary(word)),
+                0b10010 => Vfncvtfxuw(opfvv_unary(word)),
+                0b10011 => Vfncvtfxw(opfvv_unary(word)),
+                0b10100 => Vfncvtffw(opfvv_unary(word)),
+                0b10101 => VfncvtRodffw(opfvv_unary(word)),
+                0b10110 => VfncvtRtzxufw(opfvv_unary(word)),
+                0b10111 => VfncvtRtzxfw(opfvv_unary(word)),
+                _ => return None,
+            },
+            (OPFVV, 0b010011) => match vs1(word) {
+                0b00000 => Vfsqrtv(opfvv_unary(word)),
+                0b00100 => Vfrsqrt7v(opfvv_unary(word)),
+                0b00101 => Vfrec7v(opfvv_unary(word)),
+                0b10000 => Vfclassv(opfvv_unary(word)),
+                _ => return None,
+            },
+            (OPFVF, 0b010111) if masked => Vfmergevfm(opfvf(word)),
+            (OPFVF, 0b010111) if vs2(word) == 0 => Vfmvvf(opfvf(word)),
+            _ => return None,
+        })
+    }
+
+    fn decode_vector_load(word: u32) -> Option<Instruction> {
+        use Instruction::*;
+
+        let (eew, nf) = vector_memory(word)?;
+
+        Some(match ((word >> 26) & 0b11, rs2(word) as u32, nf) {
+            (UNIT_STRIDE, 0, 1) => Vlv {
+                data: vl(word),
+                eew,
+            },
+            (UNIT_STRIDE, 0, _) => Vlsegv {
+                data: vl(word),
+                eew,
+                nf,
+            },
+            (UNIT_STRIDE, WHOLE_REGISTER, 1 | 2 | 4 | 8) => Vlrv {
+                data: format::Vlr {
+                    vd: rd(word),
+                    rs1: rs1(word),
+                },
+                eew,
+                nf,
+            },
+            (UNIT_STRIDE, MASK, 1) if eew == BaseSew::E8 => Vlmv(vl(word)),
+            (UNIT_STRIDE, FAULT_ONLY_FIRST, 1) => Vlffv {
+                data: vl(word),
+                eew,
+            },
+            (STRIDED, _, 1) => Vlsv {
+                data: vls(word),
+                eew,
+            },
+            (STRIDED, _, _) => Vlssegv {
+                data: vls(word),
+                eew,
+                nf,
+            },
+            (INDEXED_UNORDERED, _, 1) => Vluxv {
+                data: vlx(word),
+                eew,
+            },
+            (INDEXED_UNORDERED, _, _) => Vluxsegv {
+                data: vlx(word),
+                eew,
+                nf,
+            },
+            (INDEXED_ORDERED, _, 1) => Vloxv {
+                data: vlx(word),
+                eew,
+            },
+            (INDEXED_ORDERED, _, _) => Vloxsegv {
+                data: vlx(word),
+                eew,
+                nf,
+            },
+            _ => return None,
+        })
+    }
+
+    fn decode_vector_store(word: u32) -> Option<Instruction> {
+        use Instruction::*;
+
+        let (eew, nf) = vector_memory(word)?;
+
+        Some(match ((word >> 26) & 0b11, rs2(word) as u32, nf) {
+            (UNIT_STRIDE, 0, 1) => Vsv {
+                data: vs(word),
+                eew,
+            },
+            (UNIT_STRIDE, 0, _) => Vssegv {
+                data: vs(word),
+                eew,
+                nf,
+            },
+            (UNIT_STRIDE, WHOLE_REGISTER, 1 | 2 | 4 | 8) if eew == BaseSew::E8 => Vsrv {
+                data: format::Vsr {
+                    vs3: rd(word),
+                    rs1: rs1(word),
+                },
+                nf,
+            },
+            (UNIT_STRIDE, MASK, 1) if eew == BaseSew::E8 => Vsmv(vs(word)),
+            (STRIDED, _, 1) => Vssv {
+                data: vss(word),
+                eew,
+            },
+            (STRIDED, _, _) => Vsssegv {
+                data: vss(word),
+                eew,
+                nf,
+            },
+            (INDEXED_UNORDERED, _, 1) => Vsuxv {
+                data: vsx(word),
+                eew,
+            },
+            (INDEXED_UNORDERED, _, _) => Vsuxsegv {
+                data: vsx(word),
+                eew,
+                nf,
+            },
+            (INDEXED_ORDERED, _, 1) => Vsoxv {
+                data: vsx(word),
+                eew,
+            },
+            (INDEXED_ORDERED, _, _) => Vsoxsegv {
+                data: vsx(word),
+                eew,
+                nf,
+            },
+            _ => return None,
+        })
+    }
+}
+
+fn rd(word: u32) -> usize {
+    ((word >> 7) & 0b11111) as usize
+}
+
+fn rs1(word: u32) -> usize {
+    ((word >> 15) & 0b11111) as usize
+}
+
+fn rs2(word: u32) -> usize {
+    ((word >> 20) & 0b11111) as usize
+}
+
+fn rs3(word: u32) -> usize {
+    (word >> 27) as usize
+}
+
+fn vs1(word: u32) -> usize {
+    rs1(word)
+}
+
+fn vs2(word: u32) -> usize {
+    rs2(word)
+}
+
+/// In eeric `vm` is set for instructions masked by v0, unlike the encoded bit
+fn vm(word: u32) -> bool {
+    (word >> 25) & 1 == 0
+}
+
+fn r(word: u32) -> format::R {
+    format::R {
+        rd: rd(word),
+        rs1: rs1(word),
+        rs2: rs2(word),
+    }
+}
+
+fn fp_unary(word: u32) -> format::R {
+    format::R { rs2: 0, ..r(word) }
+}
+
+fn r4(word: u32) -> format::R4 {
+    format::R4 {
+        rd: rd(word),
+        rs1: rs1(word),
+        rs2: rs2(word),
+        rs3: rs3(word),
+    }
+}
+
+fn i(word: u32) -> format::I {
+    format::I {
+        rd: rd(word),
+        rs1: rs1(word),
+        imm12: word as i32 >> 20,
+    }
+}
+
+fn shift(word: u32, shamt_bits: u32) -> format::I {
+    format::I {
+        imm12: ((word >> 20) & ((1 << shamt_bits) - 1)) as i32,
+        ..i(word)
+    }
+}
+
+fn s(word: u32) -> format::S {
+    format::S {
+        rs1: rs1(word),
+        rs2: rs2(word),
+        imm12: ((word as i32 >> 25) << 5) | ((word >> 7) & 0b11111) as i32,
+    }
+}
+
+fn b(word: u32) -> format::S {
+    let imm = ((word as i32 >> 31) << 12)
+        | (((word >> 7) & 1) << 11) as i32
+        | (((word >> 25) & 0b111111) << 5) as i32
+        | (((word >> 8) & 0b1111) << 1) as i32;
+
+    format::S {
+        rs1: rs1(word),
+        rs2: rs2(word),
+        imm12: imm,
+    }
+}
+
+/// Upper immediates are kept as written in assembly, i.e. as the raw 20 bits
+fn u(word: u32) -> format::U {
+    format::U {
+        rd: rd(word),
+        imm20: (word >> 12) as i32,
+    }
+}
+
+fn j(word: u32) -> format::U {
+    let imm = ((word as i32 >> 31) << 20)
+        | (((word >> 12) & 0b11111111) << 12) as i32
+        | (((word >> 20) & 1) << 11) as i32
+        | (((word >> 21) & 0b1111111111) << 1) as i32;
+
+    format::U {
+        rd: rd(word),
+        imm20: imm,
+    }
+}
+
+fn csrr(word: u32) -> format::Csrr {
+    format::Csrr {
+        rd: rd(word),
+        rs1: rs1(word),
+        csr: (word >> 20) as usize,
+    }
+}
+
+fn csri(word: u32) -> format::Csri {
+    format::Csri {
+        rd: rd(word),
+        uimm: rs1(word),
+        csr: (word >> 20) as usize,
+    }
+}
+
+fn opivv(word: u32) -> format::Opivv {
+    format::Opivv {
+        vd: rd(word),
+        vs1: vs1(word),
+        vs2: vs2(word),
+        vm: vm(word),
+    }
+}
+
+fn opivx(word: u32) -> format::Opivx {
+    format::Opivx {
+        vd: rd(word),
+        rs1: rs1(word),
+        vs2: vs2(word),
+        vm: vm(word),
+    }
+}
+
+fn opivi(word: u32) -> format::Opivi {
+    format::Opivi {
+        vd: rd(word),
+        imm5: (word as i32) << 12 >> 27,
+        vs2: vs2(word),
+        vm: vm(word),
+    }
+}
+
+/// Shifts, slides and gathers take an unsigned immediate
+fn opivi_unsigned(word: u32) -> format::Opivi {
+    format::Opivi {
+        imm5: rs1(word) as i32,
+        ..opivi(word)
+    }
+}
+
+fn opmvv(word: u32) -> format::Opmvv {
+    format::Opmvv {
+        dest: rd(word),
+        vs1: vs1(word),
+        vs2: vs2(word),
+        vm: vm(word),
+    }
+}
+
+fn opmvv_unary(word: u32) -> format::Opmvv {
+    format::Opmvv {
+        vs1: 0,
+        ..opmvv(word)
+    }
+}
+
+fn opmvx(word: u32) -> format::Opmvx {
+    format::Opmvx {
+        dest: rd(word),
+        rs1: rs1(word),
+        vs2: vs2(word),
+        vm: vm(word),
+    }
+}
+
+fn opfvv(word: u32) -> format::Opfvv {
+    format::Opfvv {
+        dest: rd(word),
+        vs1: vs1(word),
+        vs2: vs2(word),
+        vm: vm(word),
+    }
+}
+
+fn opfvv_unary(word: u32) -> format::Opfvv {
+    format::Opfvv {
+        vs1: 0,
+        ..opfvv(word)
+    }
+}
+
+fn opfvf(word: u32) -> format::Opfvf {
+    format::Opfvf {
+        vd: rd(word),
+        rs1: rs1(word),
+        vs2: vs2(word),
+        vm: vm(word),
+    }
+}
+
+/// Element width and number of fields of vector loads and stores
+fn vector_memory(word: u32) -> Option<(BaseSew, usize)> {
+    let eew = match (word >> 12) & 0b111 {
+        0b000 => BaseSew::E8,
+        0b101 => BaseSew::E16,
+        0b110 => BaseSew::E32,
+        0b111 => BaseSew::E64,
+        _ => return None,
+    };
+
+    // Extended element widths are reserved
+    if (word >> 28) & 1 != 0 {
+        return None;
+    }
+
+    Some((eew, (word >> 29) as usize + 1))
+}
+
+fn vl(word: u32) -> format::Vl {
+    format::Vl {
+        vd: rd(word),
+        rs1: rs1(word),
+        vm: vm(word),
+    }
+}
+
+fn vls(word: u32) -> format::Vls {
+    format::Vls {
+        vd: rd(word),
+        rs1: rs1(word),
+        rs2: rs2(word),
+        vm: vm(word),
+    }
+}
+
+fn vlx(word: u32) -> format::Vlx {
+    format::Vlx {
+        vd: rd(word),
+        rs1: rs1(word),
+        vs2: vs2(word),
+        vm: vm(word),
+    }
+}
+
+fn vs(word: u32) -> format::Vs {
+    format::Vs {
+        vs3: rd(word),
+        rs1: rs1(word),
+        vm: vm(word),
+    }
+}
+
+fn vss(word: u32) -> format::Vss {
+    format::Vss {
+        vs3: rd(word),
+        rs1: rs1(word),
+        rs2: rs2(word),
+        vm: vm(word),
+    }
+}
+
+fn vsx(word: u32) -> format::Vsx {
+    format::Vsx {
+        vs3: rd(word),
+        rs1: rs1(word),
+        vs2: vs2(word),
+        vm: vm(word),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::interpreter::decoder::{expression::Context, Decoder};
+
+    fn decode(line: &str) -> Instruction {
+        let labels = HashMap::from([("target".to_owned(), 16)]);
+
+        Decoder::decode_text_section(line, &Context::new(&labels, &labels, 8)).unwrap()
+    }
+
+    #[test]
+    fn decodes_encoded_instructions() {
+        let lines = [
+            "addi a0, zero, -1",
+            "sltiu a0, a1, 2047",
+            "slli a0, a0, 63",
+            "srai a0, a0, 1",
+            "sraiw a0, a0, 31",
+            "addiw a0, a0, -2048",
+            "sub a0, a1, a2",
+            "remuw a0, a1, a2",
+            "mulhsu a0, a1, a2",
+            "lbu a0, -1(sp)",
+            "lwu a0, 4(a0)",
+            "sd ra, -8(sp)",
+            "sb a0, 2047(sp)",
+            "beq a0, a1, target",
+            "bgeu a0, a1, -4088",
+            "jal target",
+            "jal zero, -8",
+            "jalr ra, -4(a0)",
+            "lui a0, 0x12345",
+            "lui a0, 0xfffff",
+            "auipc a0, 0x80000",
+            "csrrw a0, fcsr, a1",
+            "csrrci a0, vstart, 31",
+            "rdcycle a0",
+            "flw fa0, 4(a0)",
+            "fsd fs0, -16(sp)",
+            "fmadd.d fa0, fa1, fa2, fa3",
+            "fnmsub.s fa0, fa1, fa2, fa3",
+            "fdiv.s fa0, fa1, fa2",
+            "fsgnjx.d fa0, fa1, fa2",
+            "fsqrt.d fa0, fa1",
+            "fcvt.s.d fa0, fa1",
+            "fcvt.wu.s a0, fa0",
+            "fcvt.d.lu fa0, a0",
+            "fmv.x.w a0, fa0",
+            "fmv.d.x fa0, a0",
+            "fclass.s a0, fa0",
+            "fle.d a0, fa0, fa1",
+            "vsetvli t0, a0, e32, m2, ta, mu",
+            "vsetivli zero, 4, e8, mf2, tu, ma",
+            "vsetvl t0, a0, a1",
+            "vle32.v v1, (a0), v0.t",
+            "vle8ff.v v1, (a0)",
+            "vlm.v v1, (a0)",
+            "vlse64.v v1, (a0), a1",
+            "vluxei16.v v1, (a0), v2",
+            "vlseg3e32.v v4, (a0)",
+            "vloxseg2ei8.v v4, (a0), v2",
+            "vl4re16.v v4, (a0)",
+            "vse8.v v1, (a0), v0.t",
+            "vsm.v v1, (a0)",
+            "vssseg2e16.v v4, (a0), a1",
+            "vsoxei32.v v1, (a0), v2",
+            "vs2r.v v2, (a0)",
+            "vadd.vv v1, v2, v3",
+            "vadd.vi v1, v2, -3, v0.t",
+            "vsll.vi v1, v2, 31",
+            "vslidedown.vi v1, v2, 17",
+            "vnclipu.wi v1, v2, 20",
+            "vadc.vvm v1, v2, v3, v0",
+            "vmadc.vim v1, v2, 5, v0",
+            "vmadc.vx v1, v2, a0",
+            "vsbc.vxm v1, v2, a0, v0",
+            "vmsbc.vv v1, v2, v3",
+            "vmerge.vim v1, v2, -16, v0",
+            "vmv.v.v v1, v2",
+            "vmv.v.i v1, 7",
+            "vmv4r.v v4, v8",
+            "vmsgtu.vx v1, v2, a0",
+            "vwmacc.vx v2, a0, v4",
+            "vredsum.vs v1, v2, v3",
+            "vmv.x.s a0, v1",
+            "vmv.s.x v1, a0",
+            "vcpop.m a0, v2",
+            "vfirst.m a0, v2, v0.t",
+            "vzext.vf4 v1, v2",
+            "vmsbf.m v1, v2",
+            "viota.m v1, v2",
+            "vid.v v3, v0.t",
+            "vcompress.vm v1, v2, v3",
+            "vmxnor.mm v1, v2, v3",
+            "vfadd.vv v1, v2, v3",
+            "vfmul.vf v1, v2, fa0",
+            "vfmacc.vf v1, fa0, v2",
+            "vfsqrt.v v1, v2",
+            "vfcvt.xu.f.v v1, v2",
+            "vfwcvt.f.x.v v2, v1",
+            "vfncvt.rtz.x.f.w v1, v2",
+            "vfmv.f.s fa0, v1",
+            "vfmv.s.f v1, fa0",
+            "vfmv.v.f v1, fa0",
+            "vfmerge.vfm v1, v2, fa0, v0",
+            "vfslide1down.vf v1, v2, fa0",
+        ];
+
+        for line in lines {
+            let instruction = decode(line);
+            let words = Encoder::encode(&instruction).unwrap();
+
+            assert_eq!(
+                BinaryDecoder::decode_words(&words).unwrap(),
+                vec![instruction],
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_hex_dumps_and_flat_binaries() {
+        let dump = "
+            00100513  # addi a0, zero, 1
+            0x00c58533 00813503
+        ";
+        let expected = vec![
+            decode("addi a0, zero, 1"),
+            decode("add a0, a1, a2"),
+            decode("ld a0, 8(sp)"),
+        ];
+
+        assert_eq!(BinaryDecoder::decode_hex(dump).unwrap(), expected);
+        assert_eq!(
+            BinaryDecoder::decode_bytes(&[
+                0x13, 0x05, 0x10, 0x00, 0x33, 0x85, 0xc5, 0x00, 0x03, 0x35, 0x81, 0x00
+            ])
+            .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_instructions() {
+        let Err(errors) = BinaryDecoder::decode_hex("00100513\nffffffff 4505") else {
+            panic!("expected illegal instructions");
+        };
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].code, Code::IllegalInstruction);
+        assert_eq!(errors[0].line, 1);
+        assert_eq!(
+            errors[1].help.as_deref(),
+            Some("compressed instructions are not supported")
+        );
+
+        let Err(errors) = BinaryDecoder::decode_bytes(&[0x05, 0x45]) else {
+            panic!("expected a truncated binary");
+        };
+
+        assert_eq!(errors[0].code, Code::IllegalInstruction);
+    }
+}
@@ -0,0 +1,80 @@
+use super::diagnostic::{Code, Diagnostic};
+
+struct Block {
+    line_address: usize,
+    /// Whether the enclosing blocks are assembled at all
+    reachable: bool,
+    assembling: bool,
+    taken: bool,
+    seen_else: bool,
+}
+
+#[derive(Default)]
+pub struct Conditionals {
+    blocks: Vec<Block>,
+}
+
+impl Conditionals {
+    pub fn is_assembling(&self) -> bool {
+        self.blocks.last().is_none_or(|block| block.assembling)
+    }
+
+    /// Conditions of `.else`-like branches only need evaluating when no previous branch was taken
+    pub fn is_pending(&self) -> bool {
+        self.blocks
+            .last()
+            .is_some_and(|block| block.reachable && !block.taken)
+    }
+
+    pub fn open(&mut self, condition: bool, line_address: usize) {
+        let reachable = self.is_assembling();
+
+        self.blocks.push(Block {
+            line_address,
+            reachable,
+            assembling: reachable && condition,
+            taken: condition,
+            seen_else: false,
+        });
+    }
+
+    pub fn alternative(&mut self, condition: bool, directive: &str) -> Result<(), Diagnostic> {
+        let Some(block) = self.blocks.last_mut() else {
+            return Err(unmatched(directive));
+        };
+
+        if block.seen_else {
+            return Err(Diagnostic::error(
+                Code::InvalidConditional,
+                format!("{} after .else", directive),
+            )
+            .with_token(directive));
+        }
+
+        block.seen_else = directive == ".else";
+        block.assembling = block.reachable && !block.taken && condition;
+        block.taken |= condition;
+
+        Ok(())
+    }
+
+    pub fn close(&mut self) -> Result<(), Diagnostic> {
+        self.blocks
+            .pop()
+            .map(|_| ())
+            .ok_or_else(|| unmatched(".endif"))
+    }
+
+    /// Lines of the conditionals whose `.endif` is missing
+    pub fn unterminated(&self) -> impl Iterator<Item = usize> + '_ {
+        self.blocks.iter().map(|block| block.line_address)
+    }
+}
+
+fn unmatched(directive: &str) -> Diagnostic {
+    Diagnostic::error(
+        Code::InvalidConditional,
+        format!("{} without matching .if", directive),
+    )
+    .with_token(directive)
+}
@@ -1,21 +1,69 @@
 mod data;
+pub mod expression;
 mod operand;
 
-use std::{ascii, cmp::Ordering, collections::HashMap};
-
 use eeric_core::{
     fuse,
     prelude::{format::*, *},
 };
+use expression::Context;
 use operand::{csr, float, integer, vector};
+
+use super::{
+    diagnostic::{Code, Diagnostic},
+    source::strip_comment,
+};
 use Instruction::*;
 
 pub struct Decoder;
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Section {
     Data,
     Text,
+    /// `.rodata`, placed before `.data`
+    ReadOnlyData,
+    /// `.sdata`, placed after `.data` and named sections
+    SmallData,
+    /// `.bss`, placed last and holding only zeros
+    Bss,
+    /// Any other section, placed after `.data` in order of appearance
+    Named(String),
+}
+
+impl Section {
+    /// Subsections like `.rodata.str1.8` are merged into their parent, as in the default linker script
+    pub fn from_name(name: &str) -> Self {
+        let is_in = |parent: &str| {
+            name.strip_prefix(parent)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        };
+
+        if is_in(".text") {
+            Self::Text
+        } else if is_in(".data") {
+            Self::Data
+        } else if is_in(".rodata") || is_in(".srodata") {
+            Self::ReadOnlyData
+        } else if is_in(".sdata") {
+            Self::SmallData
+        } else if is_in(".bss") || is_in(".sbss") {
+            Self::Bss
+        } else {
+            Self::Named(name.to_owned())
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Data => ".data",
+            Self::Text => ".text",
+            Self::ReadOnlyData => ".rodata",
+            Self::SmallData => ".sdata",
+            Self::Bss => ".bss",
+            Self::Named(name) => name,
+        }
+    }
 }
 
 pub enum Data {
@@ -25,8 +73,30 @@ pub enum Data {
     Quad(Vec<u64>),
     Float(Vec<f32>),
     Double(Vec<f64>),
-    Ascii(Vec<ascii::Char>),
+    /// Bit patterns of IEEE 754 binary16 values, which have no stable Rust type
+    Float16(Vec<u16>),
+    /// Bit patterns of bfloat16 values, i.e. `f32` with a 7-bit mantissa
+    BFloat16(Vec<u16>),
+    String(Vec<u8>),
     Zero(usize),
+    /// Copies of a pattern, only expanded once the size is known to fit in memory
+    Fill(usize, Vec<u8>),
+}
+
+impl Data {
+    /// Size in bytes, without expanding `Zero` and `Fill`
+    pub fn len(&self) -> usize {
+        match self {
+            Data::Byte(bytes) | Data::String(bytes) => bytes.len(),
+            Data::Half(halves) | Data::Float16(halves) | Data::BFloat16(halves) => halves.len() * 2,
+            Data::Word(words) => words.len() * 4,
+            Data::Quad(quads) => quads.len() * 8,
+            Data::Float(floats) => floats.len() * 4,
+            Data::Double(doubles) => doubles.len() * 8,
+            Data::Zero(length) => *length,
+            Data::Fill(repeat, pattern) => repeat.saturating_mul(pattern.len()),
+        }
+    }
 }
 
 impl From<Data> for Vec<u8> {
@@ -38,15 +108,57 @@ impl From<Data> for Vec<u8> {
             Data::Quad(quads) => quads.into_iter().flat_map(u64::to_le_bytes).collect(),
             Data::Float(floats) => floats.into_iter().flat_map(f32::to_le_bytes).collect(),
             Data::Double(doubles) => doubles.into_iter().flat_map(f64::to_le_bytes).collect(),
-            Data::Ascii(string) => string.into_iter().map(|char| char as u8).collect(),
-            Data::Zero(length) => std::iter::repeat(0).take(length).collect(),
+            Data::Float16(halves) | Data::BFloat16(halves) => {
+                halves.into_iter().flat_map(u16::to_le_bytes).collect()
+            }
+            Data::String(bytes) => bytes,
+            Data::Zero(length) => std::iter::repeat_n(0, length).collect(),
+            Data::Fill(repeat, pattern) => pattern.repeat(repeat),
+        }
+    }
+}
+
+/// Padding requested by `.align`, `.p2align` or `.balign`
+#[derive(Clone, PartialEq, Debug)]
+pub struct Alignment {
+    pub bytes: usize,
+    /// Byte to pad data with, `.text` is always padded with `nop`s
+    pub fill: u8,
+    /// Padding is skipped entirely if it would exceed this many bytes
+    pub max_skip: Option<usize>,
+}
+
+impl Alignment {
+    pub fn padding(&self, address: usize) -> usize {
+        let padding = address.next_multiple_of(self.bytes) - address;
+
+        match self.max_skip {
+            Some(max_skip) if padding > max_skip => 0,
+            _ => padding,
         }
     }
 }
 
 pub enum AssemblerDirective {
     Section(Section),
-    Data(Data),
+    /// Decoded with `Decoder::decode_data_section` once the labels it refers to are known
+    Data(String),
+    Macro(String, Vec<(String, Option<String>)>),
+    EndMacro,
+    Equ(String, String),
+    /// Decoded with `Decoder::decode_alignment` once the symbols it refers to are known
+    Align(String, String),
+    Include(String),
+    If(Condition),
+    ElseIf(String),
+    Else,
+    EndIf,
+}
+
+pub enum Condition {
+    Expression(String),
+    Defined(String),
+    Undefined(String),
 }
 pub enum PreprocDirective {
     Define(String, String),
@@ -61,50 +173,339 @@ pub enum LineClassification {
 }
 
 impl Decoder {
-    pub fn classify(line: &str) -> Result<LineClassification, String> {
-        let trimmed_line = line.split('#').next().unwrap_or("").trim();
+    pub fn classify(line: &str) -> Result<LineClassification, Diagnostic> {
+        if let Some(directive) = Self::preproc_directive(line) {
+            return Self::decode_preproc_directive(directive)
+                .map(LineClassification::PreprocDirective);
+        }
+
+        let trimmed_line = strip_comment(line).trim();
 
         Ok(if trimmed_line.is_empty() {
             LineClassification::Empty
-        } else if trimmed_line.starts_with('#') {
-            match trimmed_line {
-                "#define" => return Err("#define directive not supported yet".to_owned()),
-                _ => {
-                    return Err(format!(
-                        "Unrecognized preprocessor directive: {}",
-                        trimmed_line
-                    ))
-                }
-            }
         } else if let Some(label) = trimmed_line.strip_suffix(':') {
             if !label.contains(' ') {
                 LineClassification::Label(label.to_string())
             } else {
-                return Err(format!("Label {} cannot have whitespaces", label));
+                return Err(Diagnostic::error(
+                    Code::InvalidLabel,
+                    format!("Label {} cannot have whitespaces", label),
+                ));
             }
         } else if trimmed_line.starts_with('.') {
-            match trimmed_line {
-                ".section .data" | ".data" => LineClassification::AssemblerDirective(
-                    AssemblerDirective::Section(Section::Data),
-                ),
-                ".section .text" | ".text" => LineClassification::AssemblerDirective(
-                    AssemblerDirective::Section(Section::Text),
-                ),
-                _ => LineClassification::AssemblerDirective(AssemblerDirective::Data(
-                    Self::decode_data_section(trimmed_line)?,
-                )),
-            }
+            let (directive, arguments) = Self::split_instruction(trimmed_line);
+
+            LineClassification::AssemblerDirective(match trimmed_line {
+                ".data" => AssemblerDirective::Section(Section::Data),
+                ".text" => AssemblerDirective::Section(Section::Text),
+                ".rodata" => AssemblerDirective::Section(Section::ReadOnlyData),
+                ".sdata" => AssemblerDirective::Section(Section::SmallData),
+                ".bss" => AssemblerDirective::Section(Section::Bss),
+                _ => match directive {
+                    ".section" => Self::decode_section_directive(arguments)?,
+                    ".macro" => Self::decode_macro_directive(arguments)?,
+                    ".endm" => AssemblerDirective::EndMacro,
+                    ".equ" | ".set" => Self::decode_equ_directive(directive, arguments)?,
+                    ".align" | ".p2align" | ".balign" => {
+                        AssemblerDirective::Align(directive.to_owned(), arguments.to_owned())
+                    }
+                    ".include" => Self::decode_include_directive(arguments)?,
+                    ".if" => AssemblerDirective::If(Condition::Expression(arguments.to_owned())),
+                    ".ifdef" => AssemblerDirective::If(Condition::Defined(
+                        Self::decode_conditional_symbol(directive, arguments)?,
+                    )),
+                    ".ifndef" => AssemblerDirective::If(Condition::Undefined(
+                        Self::decode_conditional_symbol(directive, arguments)?,
+                    )),
+                    ".elseif" => AssemblerDirective::ElseIf(arguments.to_owned()),
+                    ".else" => AssemblerDirective::Else,
+                    ".endif" => AssemblerDirective::EndIf,
+                    _ => AssemblerDirective::Data(trimmed_line.to_string()),
+                },
+            })
         } else {
             LineClassification::Instruction(trimmed_line.to_string())
         })
     }
 
+    pub fn is_conditional_directive(line: &str) -> bool {
+        let trimmed_line = strip_comment(line).trim();
+
+        matches!(
+            Self::split_instruction(trimmed_line).0,
+            ".if" | ".ifdef" | ".ifndef" | ".elseif" | ".else" | ".endif"
+        )
+    }
+
+    // Flags and types of sections, like `.section .rodata,"a",@progbits`, only matter for executable ones
+    fn decode_section_directive(arguments: &str) -> Result<AssemblerDirective, Diagnostic> {
+        let mut tokens = arguments.split(',').map(str::trim);
+        let name = tokens.next().unwrap_or_default();
+
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: '.section name[, \"flags\"]', got {} instead",
+                    arguments
+                ),
+            )
+            .with_token(".section"));
+        }
+
+        let is_executable = tokens
+            .next()
+            .and_then(|flags| flags.strip_prefix('"'))
+            .is_some_and(|flags| flags.contains('x'));
+
+        Ok(AssemblerDirective::Section(match is_executable {
+            true => Section::Text,
+            false => Section::from_name(name),
+        }))
+    }
+
+    /// On RISC-V `.align` takes a power of two like `.p2align`, `.balign` takes bytes
+    pub fn decode_alignment(
+        directive: &str,
+        arguments: &str,
+        ctx: &Context,
+    ) -> Result<Alignment, Diagnostic> {
+        let mut tokens = arguments.split(',').map(str::trim);
+        let alignment = tokens.next().unwrap_or_default();
+
+        if alignment.is_empty() {
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: '{} alignment[, fill[, max]]', got {} instead",
+                    directive, arguments
+                ),
+            )
+            .with_token(directive));
+        }
+
+        let value = ctx.evaluate(alignment)?;
+        let bytes = match directive {
+            ".balign" => u32::try_from(value)
+                .ok()
+                .filter(|bytes| bytes.is_power_of_two()),
+            _ => u32::try_from(value)
+                .ok()
+                .and_then(|power| 1u32.checked_shl(power)),
+        }
+        .ok_or_else(|| {
+            Diagnostic::error(
+                Code::InvalidData,
+                format!("Invalid alignment {} for {}", value, directive),
+            )
+            .with_token(alignment)
+        })?;
+
+        let fill = match tokens.next().filter(|fill| !fill.is_empty()) {
+            Some(fill) => {
+                let value = ctx.evaluate(fill)?;
+
+                u8::try_from(value).map_err(|_| {
+                    Diagnostic::error(
+                        Code::InvalidData,
+                        format!("Fill value {} does not fit in 8 bits", value),
+                    )
+                    .with_token(fill)
+                })?
+            }
+            None => 0,
+        };
+
+        let max_skip = match tokens.next() {
+            Some(max_skip) => Some(ctx.evaluate(max_skip)?.max(0) as usize),
+            None => None,
+        };
+
+        Ok(Alignment {
+            bytes: bytes as usize,
+            fill,
+            max_skip,
+        })
+    }
+
+    /// Alignment of the values of a data directive, used when automatic alignment is enabled
+    ///
+    /// Like in GAS, the `.2byte`, `.4byte` and `.8byte` variants stay unaligned.
+    pub fn natural_alignment(data_line: &str) -> usize {
+        match Self::split_instruction(data_line).0 {
+            ".half" | ".short" | ".half_float" | ".float16" | ".bfloat16" => 2,
+            ".word" | ".long" | ".float" => 4,
+            ".dword" | ".quad" | ".double" => 8,
+            _ => 1,
+        }
+    }
+
+    fn decode_include_directive(arguments: &str) -> Result<AssemblerDirective, Diagnostic> {
+        match arguments
+            .strip_prefix('"')
+            .and_then(|path| path.strip_suffix('"'))
+        {
+            Some(path) if !path.is_empty() && !path.contains('"') => {
+                Ok(AssemblerDirective::Include(path.to_owned()))
+            }
+            _ => Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: '.include \"file\"', got {} instead",
+                    arguments
+                ),
+            )
+            .with_token(".include")),
+        }
+    }
+
+    fn decode_equ_directive(
+        directive: &str,
+        arguments: &str,
+    ) -> Result<AssemblerDirective, Diagnostic> {
+        let (name, expr) = arguments.split_once(',').unwrap_or((arguments, ""));
+        let (name, expr) = (name.trim(), expr.trim());
+
+        let is_symbol = name
+            .chars()
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == '_' || first == '.')
+            && name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.');
+
+        if !is_symbol || expr.is_empty() {
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: '{} symbol, expression', got {} instead",
+                    directive, arguments
+                ),
+            )
+            .with_token(directive));
+        }
+
+        Ok(AssemblerDirective::Equ(name.to_owned(), expr.to_owned()))
+    }
+
+    fn decode_conditional_symbol(directive: &str, symbol: &str) -> Result<String, Diagnostic> {
+        if symbol.is_empty() || symbol.contains(char::is_whitespace) {
+            return Err(Diagnostic::error(
+                Code::InvalidConditional,
+                format!("Expected format: '{} symbol', got '{}'", directive, symbol),
+            )
+            .with_token(directive));
+        }
+
+        Ok(symbol.to_owned())
+    }
+
+    pub fn is_preproc_directive(line: &str) -> bool {
+        Self::preproc_directive(line).is_some()
+    }
+
+    // Note: '#' also starts a comment, so only lines starting with a known keyword are directives
+    fn preproc_directive(line: &str) -> Option<&str> {
+        let directive = line.trim_start().strip_prefix('#')?;
+        let (keyword, _) = Self::split_instruction(directive);
+
+        matches!(
+            keyword,
+            "define"
+                | "undef"
+                | "include"
+                | "if"
+                | "ifdef"
+                | "ifndef"
+                | "elif"
+                | "else"
+                | "endif"
+                | "error"
+                | "pragma"
+        )
+        .then_some(directive)
+    }
+
+    fn decode_preproc_directive(directive: &str) -> Result<PreprocDirective, Diagnostic> {
+        let (keyword, body) = Self::split_instruction(directive);
+
+        match keyword {
+            "define" => {
+                let (name, value) = Self::split_instruction(body.split('#').next().unwrap_or(""));
+
+                if !Self::is_identifier(name) {
+                    return Err(Diagnostic::error(
+                        Code::Preprocessor,
+                        format!("Invalid #define name: '{}'", name),
+                    )
+                    .with_token(name));
+                }
+
+                Ok(PreprocDirective::Define(name.to_owned(), value.to_owned()))
+            }
+            _ => Err(Diagnostic::error(
+                Code::Preprocessor,
+                format!("Unsupported preprocessor directive: #{}", keyword),
+            )
+            .with_token(keyword)),
+        }
+    }
+
+    // Parameters are separated by commas, each one optionally with '=default', e.g. `.macro scale reg, by = 2 * 4`
+    fn decode_macro_directive(signature: &str) -> Result<AssemblerDirective, Diagnostic> {
+        let signature = signature.trim();
+        let (name, parameters) = signature
+            .split_once(|ch: char| ch == ',' || ch.is_whitespace())
+            .unwrap_or((signature, ""));
+
+        if !Self::is_identifier(name) {
+            return Err(Diagnostic::error(
+                Code::InvalidMacro,
+                format!("Invalid macro name: '{}'", name),
+            )
+            .with_token(name));
+        }
+
+        let parameters = parameters
+            .split(',')
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(|token| {
+                let (parameter, default) = match token.split_once('=') {
+                    Some((parameter, default)) => {
+                        (parameter.trim_end(), Some(default.trim_start().to_owned()))
+                    }
+                    None => (token, None),
+                };
+
+                if Self::is_identifier(parameter) {
+                    Ok((parameter.to_owned(), default))
+                } else {
+                    Err(Diagnostic::error(
+                        Code::InvalidMacro,
+                        format!("Invalid macro parameter: '{}'", parameter),
+                    )
+                    .with_token(parameter))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(AssemblerDirective::Macro(name.to_owned(), parameters))
+    }
+
+    fn is_identifier(name: &str) -> bool {
+        let mut chars = name.chars();
+
+        chars
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+            && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+    }
+
     pub fn decode_text_section(
         instruction_line: &str,
-        instruction_labels: &HashMap<String, usize>,
-        memory_labels: &HashMap<String, usize>,
-        current_address: usize,
-    ) -> Result<Instruction, String> {
+        ctx: &Context,
+    ) -> Result<Instruction, Diagnostic> {
         let (mnemonic, op) = Self::split_instruction(instruction_line);
 
         use integer::{
@@ -123,19 +524,20 @@ impl Decoder {
 
         use vector::{
             parse_opfvf_fma_format as opfvf_fma, parse_opfvf_format as opfvf,
-            parse_opfvv_fma_format as opfvv_fma, parse_opfvv_format as opfvv,
-            parse_opivi_format as opivi, parse_opivi_maskless_format as opivi_maskless,
-            parse_opivi_v0_format as opivi_v0, parse_opivi_vmv_format as opivi_vmv,
-            parse_opivv_format as opivv, parse_opivv_maskless_format as opivv_maskless,
-            parse_opivv_v0_format as opivv_v0, parse_opivv_vmv_format as opivv_vmv,
-            parse_opivx_format as opivx, parse_opivx_maskless_format as opivx_maskless,
-            parse_opivx_v0_format as opivx_v0, parse_opivx_vmv_format as opivx_vmv,
-            parse_opmvv_fma_format as opmvv_fma, parse_opmvv_format as opmvv,
-            parse_opmvv_maskless_format as opmvv_maskless, parse_opmvx_fma_format as opmvx_fma,
-            parse_opmvx_format as opmvx, parse_vfunary0_format as vfunary0,
-            parse_vfunary1_format as vfunary1, parse_vl_format as vl, parse_vlm_format as vlm,
-            parse_vlr_format as vlr, parse_vls_format as vls, parse_vlx_format as vlx,
-            parse_vmunary0_format as vmunary0, parse_vmunary0_vidv_format as vidv,
+            parse_opfvf_v0_format as opfvf_v0, parse_opfvv_fma_format as opfvv_fma,
+            parse_opfvv_format as opfvv, parse_opivi_format as opivi,
+            parse_opivi_maskless_format as opivi_maskless, parse_opivi_v0_format as opivi_v0,
+            parse_opivi_vmv_format as opivi_vmv, parse_opivv_format as opivv,
+            parse_opivv_maskless_format as opivv_maskless, parse_opivv_v0_format as opivv_v0,
+            parse_opivv_vmv_format as opivv_vmv, parse_opivx_format as opivx,
+            parse_opivx_maskless_format as opivx_maskless, parse_opivx_v0_format as opivx_v0,
+            parse_opivx_vmv_format as opivx_vmv, parse_opmvv_fma_format as opmvv_fma,
+            parse_opmvv_format as opmvv, parse_opmvv_maskless_format as opmvv_maskless,
+            parse_opmvx_fma_format as opmvx_fma, parse_opmvx_format as opmvx,
+            parse_vfunary0_format as vfunary0, parse_vfunary1_format as vfunary1,
+            parse_vl_format as vl, parse_vlm_format as vlm, parse_vlr_format as vlr,
+            parse_vls_format as vls, parse_vlx_format as vlx, parse_vmunary0_format as vmunary0,
+            parse_vmunary0_vidv_format as vidv, parse_vmvnr_format as vmvnr,
             parse_vrfunary0_format as vrfunary0, parse_vrxunary0_format as vrxunary0,
             parse_vs_format as vs, parse_vsetivli_format as vsetivli,
             parse_vsetvl_format as vsetvl, parse_vsetvli_format as vsetvli,
@@ -150,66 +552,60 @@ impl Decoder {
             "addw" => Addw(r(op)?),
             "sub" => Sub(r(op)?),
             "subw" => Subw(r(op)?),
-            "addi" => Addi(i(op)?),
-            "addiw" => Addiw(i(op)?),
+            "addi" => Addi(i(op, ctx)?),
+            "addiw" => Addiw(i(op, ctx)?),
             "slt" => Slt(r(op)?),
-            "slti" => Slti(i(op)?),
+            "slti" => Slti(i(op, ctx)?),
             "sltu" => Sltu(r(op)?),
-            "sltiu" => Sltiu(i(op)?),
-            "lui" => Lui(u(op)?),
-            "auipc" => Auipc(u(op)?),
+            "sltiu" => Sltiu(i(op, ctx)?),
+            "lui" => Lui(u(op, ctx)?),
+            "auipc" => Auipc(u(op, ctx)?),
 
             "and" => And(r(op)?),
             "or" => Or(r(op)?),
             "xor" => Xor(r(op)?),
-            "andi" => Andi(i(op)?),
-            "ori" => Ori(i(op)?),
-            "xori" => Xori(i(op)?),
+            "andi" => Andi(i(op, ctx)?),
+            "ori" => Ori(i(op, ctx)?),
+            "xori" => Xori(i(op, ctx)?),
             "sll" => Sll(r(op)?),
             "sllw" => Sllw(r(op)?),
             "srl" => Srl(r(op)?),
             "srlw" => Srlw(r(op)?),
             "sra" => Sra(r(op)?),
             "sraw" => Sraw(r(op)?),
-            "slli" => Slli(i(op)?),
-            "slliw" => Slliw(i(op)?),
-            "srli" => Srli(i(op)?),
-            "srliw" => Srliw(i(op)?),
-            "srai" => Srai(i(op)?),
-            "sraiw" => Sraiw(i(op)?),
-
-            "ld" => Ld(l(op, memory_labels)?),
-            "lw" => Lw(l(op, memory_labels)?),
-            "lh" => Lh(l(op, memory_labels)?),
-            "lb" => Lb(l(op, memory_labels)?),
-            "lwu" => Lwu(l(op, memory_labels)?),
-            "lhu" => Lhu(l(op, memory_labels)?),
-            "lbu" => Lbu(l(op, memory_labels)?),
-            "sd" => Sd(s(op, memory_labels)?),
-            "sw" => Sw(s(op, memory_labels)?),
-            "sh" => Sh(s(op, memory_labels)?),
-            "sb" => Sb(s(op, memory_labels)?),
-
-            "beq" => Beq(b(op, instruction_labels, current_address)?),
-            "bne" => Bne(b(op, instruction_labels, current_address)?),
-            "bge" => Bge(b(op, instruction_labels, current_address)?),
-            "bgeu" => Bgeu(b(op, instruction_labels, current_address)?),
-            "blt" => Blt(b(op, instruction_labels, current_address)?),
-            "bltu" => Bltu(b(op, instruction_labels, current_address)?),
-            "jal" => match u(op) {
-                Ok(instruction) => Jal(instruction),
-                Err(fst_err) => {
-                    match integer::pseudo::parse_label_format(
-                        op,
-                        instruction_labels,
-                        current_address,
-                    ) {
-                        Ok(diff) => Jal(U { rd: 1, imm20: diff }),
-                        Err(snd_err) => return Err(format!("{} or {}", fst_err, snd_err)),
-                    }
-                }
+            "slli" => Slli(i(op, ctx)?),
+            "slliw" => Slliw(i(op, ctx)?),
+            "srli" => Srli(i(op, ctx)?),
+            "srliw" => Srliw(i(op, ctx)?),
+            "srai" => Srai(i(op, ctx)?),
+            "sraiw" => Sraiw(i(op, ctx)?),
+
+            "ld" => Ld(l(op, ctx)?),
+            "lw" => Lw(l(op, ctx)?),
+            "lh" => Lh(l(op, ctx)?),
+            "lb" => Lb(l(op, ctx)?),
+            "lwu" => Lwu(l(op, ctx)?),
+            "lhu" => Lhu(l(op, ctx)?),
+            "lbu" => Lbu(l(op, ctx)?),
+            "sd" => Sd(s(op, ctx)?),
+            "sw" => Sw(s(op, ctx)?),
+            "sh" => Sh(s(op, ctx)?),
+            "sb" => Sb(s(op, ctx)?),
+
+            "beq" => Beq(b(op, ctx)?),
+            "bne" => Bne(b(op, ctx)?),
+            "bge" => Bge(b(op, ctx)?),
+            "bgeu" => Bgeu(b(op, ctx)?),
+            "blt" => Blt(b(op, ctx)?),
+            "bltu" => Bltu(b(op, ctx)?),
+            "jal" => match integer::pseudo::parse_op_label_format(op, ctx) {
+                Ok((rd, diff)) => Jal(U { rd, imm20: diff }),
+                Err(fst_err) => match integer::pseudo::parse_label_format(op, ctx) {
+                    Ok(diff) => Jal(U { rd: 1, imm20: diff }),
+                    Err(snd_err) => return Err(fst_err.or(snd_err)),
+                },
             },
-            "jalr" => match l(op, memory_labels) {
+            "jalr" => match l(op, ctx) {
                 Ok(instruction) => Jalr(instruction),
                 Err(fst_err) => match integer::pseudo::parse_op_format(op) {
                     Ok(rs1) => Jalr(I {
@@ -217,16 +613,16 @@ impl Decoder {
                         rs1,
                         imm12: 0,
                     }),
-                    Err(snd_err) => return Err(format!("{} or {}", fst_err, snd_err)),
+                    Err(snd_err) => return Err(fst_err.or(snd_err)),
                 },
             },
 
             "csrrw" => Csrrw(csrr(op)?),
             "csrrs" => Csrrs(csrr(op)?),
             "csrrc" => Csrrc(csrr(op)?),
-            "csrrwi" => Csrrwi(csri(op)?),
-            "csrrsi" => Csrrsi(csri(op)?),
-            "csrrci" => Csrrci(csri(op)?),
+            "csrrwi" => Csrrwi(csri(op, ctx)?),
+            "csrrsi" => Csrrsi(csri(op, ctx)?),
+            "csrrci" => Csrrci(csri(op, ctx)?),
 
             "mul" => Mul(r(op)?),
             "mulh" => Mulh(r(op)?),
@@ -242,8 +638,8 @@ impl Decoder {
             "remw" => Remw(r(op)?),
             "remuw" => Remuw(r(op)?),
 
-            "flw" => Flw(fl(op, memory_labels)?),
-            "fsw" => Fsw(fs(op, memory_labels)?),
+            "flw" => Flw(fl(op, ctx)?),
+            "fsw" => Fsw(fs(op, ctx)?),
             "fmadd.s" => Fmadds(r4(op)?),
             "fmsub.s" => Fmsubs(r4(op)?),
             "fnmsub.s" => Fnmsubs(r4(op)?),
@@ -251,8 +647,8 @@ impl Decoder {
             "fadd.s" => Fadds(fr(op)?),
             "fsub.s" => Fsubs(fr(op)?),
             "fmul.s" => Fmuls(fr(op)?),
-            "fdiv.s" => Fdivs(frs(op)?),
-            "fsqrt.s" => Fsqrts(fr(op)?),
+            "fdiv.s" => Fdivs(fr(op)?),
+            "fsqrt.s" => Fsqrts(frs(op)?),
             "fsgnj.s" => Fsgnjs(fr(op)?),
             "fsgnjn.s" => Fsgnjns(fr(op)?),
             "fsgnjx.s" => Fsgnjxs(fr(op)?),
@@ -273,8 +669,8 @@ impl Decoder {
             "fcvt.s.l" => Fcvtsl(frs_to_f(op)?),
             "fcvt.s.lu" => Fcvtslu(frs_to_f(op)?),
 
-            "fld" => Fld(fl(op, memory_labels)?),
-            "fsd" => Fsd(fs(op, memory_labels)?),
+            "fld" => Fld(fl(op, ctx)?),
+            "fsd" => Fsd(fs(op, ctx)?),
             "fmadd.d" => Fmaddd(r4(op)?),
             "fmsub.d" => Fmsubd(r4(op)?),
             "fnmsub.d" => Fnmsubd(r4(op)?),
@@ -282,15 +678,15 @@ impl Decoder {
             "fadd.d" => Faddd(fr(op)?),
             "fsub.d" => Fsubd(fr(op)?),
             "fmul.d" => Fmuld(fr(op)?),
-            "fdiv.d" => Fdivd(frs(op)?),
-            "fsqrt.d" => Fsqrtd(fr(op)?),
+            "fdiv.d" => Fdivd(fr(op)?),
+            "fsqrt.d" => Fsqrtd(frs(op)?),
             "fsgnj.d" => Fsgnjd(fr(op)?),
             "fsgnjn.d" => Fsgnjnd(fr(op)?),
             "fsgnjx.d" => Fsgnjxd(fr(op)?),
             "fmin.d" => Fmind(fr(op)?),
             "fmax.d" => Fmaxd(fr(op)?),
-            "fcvt.s.d" => Fcvtsd(fr(op)?),
-            "fcvt.d.s" => Fcvtds(fr(op)?),
+            "fcvt.s.d" => Fcvtsd(frs(op)?),
+            "fcvt.d.s" => Fcvtds(frs(op)?),
             "feq.d" => Feqd(frx(op)?),
             "flt.d" => Fltd(frx(op)?),
             "fle.d" => Fled(frx(op)?),
@@ -307,7 +703,7 @@ impl Decoder {
             "fmv.d.x" => Fmvdx(frs_to_f(op)?),
 
             "vsetvli" => Vsetvli(vsetvli(op)?),
-            "vsetivli" => Vsetivli(vsetivli(op)?),
+            "vsetivli" => Vsetivli(vsetivli(op, ctx)?),
             "vsetvl" => Vsetvl(vsetvl(op)?),
 
             "vle8.v" => Vlv {
@@ -432,19 +828,19 @@ impl Decoder {
                 data: vsx(op)?,
             },
 
-            "vsuxeix8.v" => Vsuxv {
+            "vsoxei8.v" => Vsoxv {
                 eew: BaseSew::E8,
                 data: vsx(op)?,
             },
-            "vsuxeix16.v" => Vsuxv {
+            "vsoxei16.v" => Vsoxv {
                 eew: BaseSew::E16,
                 data: vsx(op)?,
             },
-            "vsuxeix32.v" => Vsuxv {
+            "vsoxei32.v" => Vsoxv {
                 eew: BaseSew::E32,
                 data: vsx(op)?,
             },
-            "vsuxeix64.v" => Vsuxv {
+            "vsoxei64.v" => Vsoxv {
                 eew: BaseSew::E64,
                 data: vsx(op)?,
             },
@@ -1855,13 +2251,13 @@ impl Decoder {
 
             "vadd.vv" => Vaddvv(opivv(op)?),
             "vadd.vx" => Vaddvx(opivx(op)?),
-            "vadd.vi" => Vaddvi(opivi(op)?),
+            "vadd.vi" => Vaddvi(opivi(op, ctx)?),
 
             "vsub.vv" => Vsubvv(opivv(op)?),
             "vsub.vx" => Vsubvx(opivx(op)?),
 
             "vrsub.vx" => Vrsubvx(opivx(op)?),
-            "vrsub.vi" => Vrsubvi(opivi(op)?),
+            "vrsub.vi" => Vrsubvi(opivi(op, ctx)?),
 
             "vminu.vv" => Vminuvv(opivv(op)?),
             "vminu.vx" => Vminuvx(opivx(op)?),
@@ -1877,60 +2273,62 @@ impl Decoder {
 
             "vand.vv" => Vandvv(opivv(op)?),
             "vand.vx" => Vandvx(opivx(op)?),
-            "vand.vi" => Vandvi(opivi(op)?),
+            "vand.vi" => Vandvi(opivi(op, ctx)?),
 
             "vor.vv" => Vorvv(opivv(op)?),
             "vor.vx" => Vorvx(opivx(op)?),
-            "vor.vi" => Vorvi(opivi(op)?),
+            "vor.vi" => Vorvi(opivi(op, ctx)?),
 
             "vxor.vv" => Vxorvv(opivv(op)?),
             "vxor.vx" => Vxorvx(opivx(op)?),
-            "vxor.vi" => Vxorvi(opivi(op)?),
+            "vxor.vi" => Vxorvi(opivi(op, ctx)?),
 
             "vrgather.vv" => Vrgathervv(opivv(op)?),
             "vrgather.vx" => Vrgathervx(opivx(op)?),
-            "vrgather.vi" => Vrgathervi(opivi(op)?),
+            "vrgather.vi" => Vrgathervi(opivi(op, ctx)?),
 
             "vrgatherei16.v" => Vrgatherei16vv(opivv(op)?),
 
             "vslideup.vx" => Vslideupvx(opivx(op)?),
-            "vslideup.vi" => Vslideupvi(opivi(op)?),
+            "vslideup.vi" => Vslideupvi(opivi(op, ctx)?),
 
             "vslidedown.vx" => Vslidedownvx(opivx(op)?),
-            "vslidedown.vi" => Vslidedownvi(opivi(op)?),
+            "vslidedown.vi" => Vslidedownvi(opivi(op, ctx)?),
 
             "vadc.vvm" => Vadcvvm(opivv_v0(op)?),
             "vadc.vxm" => Vadcvxm(opivx_v0(op)?),
-            "vadc.vim" => Vadcvim(opivi_v0(op)?),
+            "vadc.vim" => Vadcvim(opivi_v0(op, ctx)?),
 
             "vmadc.vvm" => Vmadcvvm(opivv_v0(op)?),
             "vmadc.vxm" => Vmadcvxm(opivx_v0(op)?),
-            "vmadc.vim" => Vmadcvim(opivi_v0(op)?),
+            "vmadc.vim" => Vmadcvim(opivi_v0(op, ctx)?),
             "vmadc.vv" => Vmadcvv(opivv_maskless(op)?),
             "vmadc.vx" => Vmadcvx(opivx_maskless(op)?),
-            "vmadc.vi" => Vmadcvi(opivi_maskless(op)?),
+            "vmadc.vi" => Vmadcvi(opivi_maskless(op, ctx)?),
 
-            "vsbc.vvm" => Vsbcvvm(opivv(op)?),
-            "vsbc.vxm" => Vsbcvxm(opivx(op)?),
+            "vsbc.vvm" => Vsbcvvm(opivv_v0(op)?),
+            "vsbc.vxm" => Vsbcvxm(opivx_v0(op)?),
 
-            "vmsbc.vv" => Vmsbcvv(opivv(op)?),
-            "vmsbc.vx" => Vmsbcvx(opivx(op)?),
+            "vmsbc.vvm" => Vmsbcvvm(opivv_v0(op)?),
+            "vmsbc.vxm" => Vmsbcvxm(opivx_v0(op)?),
+            "vmsbc.vv" => Vmsbcvv(opivv_maskless(op)?),
+            "vmsbc.vx" => Vmsbcvx(opivx_maskless(op)?),
 
-            "vmerge.vvm" => Vmergevvm(opivv(op)?),
-            "vmerge.vxm" => Vmergevxm(opivx(op)?),
-            "vmerge.vim" => Vmergevim(opivi(op)?),
+            "vmerge.vvm" => Vmergevvm(opivv_v0(op)?),
+            "vmerge.vxm" => Vmergevxm(opivx_v0(op)?),
+            "vmerge.vim" => Vmergevim(opivi_v0(op, ctx)?),
 
             "vmv.v.v" => Vmvvv(opivv_vmv(op)?),
             "vmv.v.x" => Vmvvx(opivx_vmv(op)?),
-            "vmv.v.i" => Vmvvi(opivi_vmv(op)?),
+            "vmv.v.i" => Vmvvi(opivi_vmv(op, ctx)?),
 
             "vmseq.vv" => Vmseqvv(opivv(op)?),
             "vmseq.vx" => Vmseqvx(opivx(op)?),
-            "vmseq.vi" => Vmseqvi(opivi(op)?),
+            "vmseq.vi" => Vmseqvi(opivi(op, ctx)?),
 
             "vmsne.vv" => Vmsnevv(opivv(op)?),
             "vmsne.vx" => Vmsnevx(opivx(op)?),
-            "vmsne.vi" => Vmsnevi(opivi(op)?),
+            "vmsne.vi" => Vmsnevi(opivi(op, ctx)?),
 
             "vmsltu.vv" => Vmsltuvv(opivv(op)?),
             "vmsltu.vx" => Vmsltuvx(opivx(op)?),
@@ -1940,25 +2338,25 @@ impl Decoder {
 
             "vmsleu.vv" => Vmsleuvv(opivv(op)?),
             "vmsleu.vx" => Vmsleuvx(opivx(op)?),
-            "vmsleu.vi" => Vmsleuvi(opivi(op)?),
+            "vmsleu.vi" => Vmsleuvi(opivi(op, ctx)?),
 
             "vmsle.vv" => Vmslevv(opivv(op)?),
             "vmsle.vx" => Vmslevx(opivx(op)?),
-            "vmsle.vi" => Vmslevi(opivi(op)?),
+            "vmsle.vi" => Vmslevi(opivi(op, ctx)?),
 
             "vmsgtu.vx" => Vmsgtuvx(opivx(op)?),
-            "vmsgtu.vi" => Vmsgtuvi(opivi(op)?),
+            "vmsgtu.vi" => Vmsgtuvi(opivi(op, ctx)?),
 
             "vmsgt.vx" => Vmsgtvx(opivx(op)?),
-            "vmsgt.vi" => Vmsgtvi(opivi(op)?),
+            "vmsgt.vi" => Vmsgtvi(opivi(op, ctx)?),
 
             "vsaddu.vv" => Vsadduvv(opivv(op)?),
             "vsaddu.vx" => Vsadduvx(opivx(op)?),
-            "vsaddu.vi" => Vsadduvi(opivi(op)?),
+            "vsaddu.vi" => Vsadduvi(opivi(op, ctx)?),
 
             "vsadd.vv" => Vsaddvv(opivv(op)?),
             "vsadd.vx" => Vsaddvx(opivx(op)?),
-            "vsadd.vi" => Vsaddvi(opivi(op)?),
+            "vsadd.vi" => Vsaddvi(opivi(op, ctx)?),
 
             "vssubu.vv" => Vssubuvv(opivv(op)?),
             "vssubu.vx" => Vssubuvx(opivx(op)?),
@@ -1968,47 +2366,47 @@ impl Decoder {
 
             "vsll.vv" => Vsllvv(opivv(op)?),
             "vsll.vx" => Vsllvx(opivx(op)?),
-            "vsll.vi" => Vsllvi(opivi(op)?),
+            "vsll.vi" => Vsllvi(opivi(op, ctx)?),
 
             "vsmul.vv" => Vsmulvv(opivv(op)?),
             "vsmul.vx" => Vsmulvx(opivx(op)?),
 
-            "vmv1r.v" => Vmv1rv(opivi(op)?),
-            "vmv2r.v" => Vmv2rv(opivi(op)?),
-            "vmv4r.v" => Vmv4rv(opivi(op)?),
-            "vmv8r.v" => Vmv8rv(opivi(op)?),
+            "vmv1r.v" => Vmv1rv(vmvnr(op, 1)?),
+            "vmv2r.v" => Vmv2rv(vmvnr(op, 2)?),
+            "vmv4r.v" => Vmv4rv(vmvnr(op, 4)?),
+            "vmv8r.v" => Vmv8rv(vmvnr(op, 8)?),
 
             "vsrl.vv" => Vsrlvv(opivv(op)?),
             "vsrl.vx" => Vsrlvx(opivx(op)?),
-            "vsrl.vi" => Vsrlvi(opivi(op)?),
+            "vsrl.vi" => Vsrlvi(opivi(op, ctx)?),
 
             "vsra.vv" => Vsravv(opivv(op)?),
             "vsra.vx" => Vsravx(opivx(op)?),
-            "vsra.vi" => Vsravi(opivi(op)?),
+            "vsra.vi" => Vsravi(opivi(op, ctx)?),
 
             "vssrl.vv" => Vssrlvv(opivv(op)?),
             "vssrl.vx" => Vssrlvx(opivx(op)?),
-            "vssrl.vi" => Vssrlvi(opivi(op)?),
+            "vssrl.vi" => Vssrlvi(opivi(op, ctx)?),
 
             "vssra.vv" => Vssravv(opivv(op)?),
             "vssra.vx" => Vssravx(opivx(op)?),
-            "vssra.vi" => Vssravi(opivi(op)?),
+            "vssra.vi" => Vssravi(opivi(op, ctx)?),
 
             "vnsrl.wv" => Vnsrlwv(opivv(op)?),
             "vnsrl.wx" => Vnsrlwx(opivx(op)?),
-            "vnsrl.wi" => Vnsrlwi(opivi(op)?),
+            "vnsrl.wi" => Vnsrlwi(opivi(op, ctx)?),
 
             "vnsra.wv" => Vnsrawv(opivv(op)?),
             "vnsra.wx" => Vnsrawx(opivx(op)?),
-            "vnsra.wi" => Vnsrawi(opivi(op)?),
+            "vnsra.wi" => Vnsrawi(opivi(op, ctx)?),
 
             "vnclipu.wv" => Vnclipuwv(opivv(op)?),
             "vnclipu.wx" => Vnclipuwx(opivx(op)?),
-            "vnclipu.wi" => Vnclipuwi(opivi(op)?),
+            "vnclipu.wi" => Vnclipuwi(opivi(op, ctx)?),
 
             "vnclip.wv" => Vnclipwv(opivv(op)?),
             "vnclip.wx" => Vnclipwx(opivx(op)?),
-            "vnclip.wi" => Vnclipwi(opivi(op)?),
+            "vnclip.wi" => Vnclipwi(opivi(op, ctx)?),
 
             "vwredsumu.vs" => Vwredsumuvs(opivv(op)?),
             "vwredsum.vs" => Vwredsumvs(opivv(op)?),
@@ -2215,8 +2613,8 @@ impl Decoder {
             "vfrec7.v" => Vfrec7v(vfunary1(op)?),
             "vfclass.v" => Vfclassv(vfunary1(op)?),
 
-            "vfmerge.vfm" => Vfmergevfm(opfvf(op)?),
-            "vfmv.v.f" => Vfmvvf(opfvf(op)?),
+            "vfmerge.vfm" => Vfmergevfm(opfvf_v0(op)?),
+            "vfmv.v.f" => Vfmvvf(vrfunary0(op)?),
 
             "vmfeq.vv" => Vmfeqvv(opfvv(op)?),
             "vmfeq.vf" => Vmfeqvf(opfvf(op)?),
@@ -2301,20 +2699,11 @@ impl Decoder {
 
             // Pseudoinstructions
             "la" => {
-                let (rd, mem_addr) =
-                    integer::pseudo::parse_op_memory_label_format(op, memory_labels)?;
+                let (rd, mem_addr) = integer::pseudo::parse_op_memory_label_format(op, ctx)?;
+                let (imm20, imm12) =
+                    Self::split_pcrel(mem_addr.wrapping_sub(ctx.current_address as i32));
 
-                fuse![
-                    Auipc(U {
-                        rd,
-                        imm20: mem_addr >> 12,
-                    }),
-                    Addi(I {
-                        rd,
-                        rs1: rd,
-                        imm12: mem_addr & 0xfff,
-                    })
-                ]
+                fuse![Auipc(U { rd, imm20 }), Addi(I { rd, rs1: rd, imm12 })]
             }
             "nop" => Addi(I {
                 rd: 0,
@@ -2322,29 +2711,14 @@ impl Decoder {
                 imm12: 0,
             }),
             "li" => {
-                let (reg, imm) = integer::pseudo::parse_op_imm_format(op)?;
+                let (reg, imm) = integer::pseudo::parse_op_imm64_format(op, ctx)?;
+                let mut sequence = Vec::new();
 
-                match imm.cmp(&4096) {
-                    Ordering::Less => Addi(I {
-                        rd: reg,
-                        rs1: 0,
-                        imm12: imm,
-                    }),
-                    Ordering::Equal => Lui(U {
-                        rd: reg,
-                        imm20: imm,
-                    }),
-                    Ordering::Greater => fuse![
-                        Lui(U {
-                            rd: reg,
-                            imm20: imm >> 12,
-                        }),
-                        Addi(I {
-                            rd: reg,
-                            rs1: reg,
-                            imm12: imm & 0xfff,
-                        }),
-                    ],
+                Self::materialize(reg, imm, &mut sequence);
+
+                match sequence.len() {
+                    1 => sequence.remove(0),
+                    _ => Fusion(sequence.into_boxed_slice()),
                 }
             }
             "mv" => {
@@ -2483,11 +2857,7 @@ impl Decoder {
                 Fsgnjnd(R { rd, rs1, rs2: rs1 })
             }
             "beqz" => {
-                let (rs1, diff) = integer::pseudo::parse_op_label_format(
-                    op,
-                    instruction_labels,
-                    current_address,
-                )?;
+                let (rs1, diff) = integer::pseudo::parse_op_label_format(op, ctx)?;
                 Beq(S {
                     rs1,
                     rs2: 0,
@@ -2495,11 +2865,7 @@ impl Decoder {
                 })
             }
             "bnez" => {
-                let (rs1, diff) = integer::pseudo::parse_op_label_format(
-                    op,
-                    instruction_labels,
-                    current_address,
-                )?;
+                let (rs1, diff) = integer::pseudo::parse_op_label_format(op, ctx)?;
                 Bne(S {
                     rs1,
                     rs2: 0,
@@ -2507,11 +2873,7 @@ impl Decoder {
                 })
             }
             "blez" => {
-                let (rs1, diff) = integer::pseudo::parse_op_label_format(
-                    op,
-                    instruction_labels,
-                    current_address,
-                )?;
+                let (rs1, diff) = integer::pseudo::parse_op_label_format(op, ctx)?;
                 Bge(S {
                     rs1,
                     rs2: 0,
@@ -2519,11 +2881,7 @@ impl Decoder {
                 })
             }
             "bgez" => {
-                let (rs1, diff) = integer::pseudo::parse_op_label_format(
-                    op,
-                    instruction_labels,
-                    current_address,
-                )?;
+                let (rs1, diff) = integer::pseudo::parse_op_label_format(op, ctx)?;
                 Bge(S {
                     rs1,
                     rs2: 0,
@@ -2531,11 +2889,7 @@ impl Decoder {
                 })
             }
             "bltz" => {
-                let (rs1, diff) = integer::pseudo::parse_op_label_format(
-                    op,
-                    instruction_labels,
-                    current_address,
-                )?;
+                let (rs1, diff) = integer::pseudo::parse_op_label_format(op, ctx)?;
                 Blt(S {
                     rs1,
                     rs2: 0,
@@ -2543,11 +2897,7 @@ impl Decoder {
                 })
             }
             "bgtz" => {
-                let (rs1, diff) = integer::pseudo::parse_op_label_format(
-                    op,
-                    instruction_labels,
-                    current_address,
-                )?;
+                let (rs1, diff) = integer::pseudo::parse_op_label_format(op, ctx)?;
                 Blt(S {
                     rs1,
                     rs2: 0,
@@ -2555,11 +2905,7 @@ impl Decoder {
                 })
             }
             "bgt" => {
-                let (rs1, rs2, diff) = integer::pseudo::parse_op_op_label_format(
-                    op,
-                    instruction_labels,
-                    current_address,
-                )?;
+                let (rs1, rs2, diff) = integer::pseudo::parse_op_op_label_format(op, ctx)?;
                 Blt(S {
                     rs1: rs2,
                     rs2: rs1,
@@ -2567,11 +2913,7 @@ impl Decoder {
                 })
             }
             "ble" => {
-                let (rs1, rs2, diff) = integer::pseudo::parse_op_op_label_format(
-                    op,
-                    instruction_labels,
-                    current_address,
-                )?;
+                let (rs1, rs2, diff) = integer::pseudo::parse_op_op_label_format(op, ctx)?;
                 Bge(S {
                     rs1: rs2,
                     rs2: rs1,
@@ -2579,11 +2921,7 @@ impl Decoder {
                 })
             }
             "bgtu" => {
-                let (rs1, rs2, diff) = integer::pseudo::parse_op_op_label_format(
-                    op,
-                    instruction_labels,
-                    current_address,
-                )?;
+                let (rs1, rs2, diff) = integer::pseudo::parse_op_op_label_format(op, ctx)?;
                 Bltu(S {
                     rs1: rs2,
                     rs2: rs1,
@@ -2591,11 +2929,7 @@ impl Decoder {
                 })
             }
             "bleu" => {
-                let (rs1, rs2, diff) = integer::pseudo::parse_op_op_label_format(
-                    op,
-                    instruction_labels,
-                    current_address,
-                )?;
+                let (rs1, rs2, diff) = integer::pseudo::parse_op_op_label_format(op, ctx)?;
                 Bgeu(S {
                     rs1: rs2,
                     rs2: rs1,
@@ -2603,8 +2937,7 @@ impl Decoder {
                 })
             }
             "j" => {
-                let diff =
-                    integer::pseudo::parse_label_format(op, instruction_labels, current_address)?;
+                let diff = integer::pseudo::parse_label_format(op, ctx)?;
                 Jal(U { rd: 0, imm20: diff })
             }
             "jr" => {
@@ -2621,34 +2954,28 @@ impl Decoder {
                 imm12: 0,
             }),
             "call" => {
-                let diff =
-                    integer::pseudo::parse_label_format(op, instruction_labels, current_address)?;
+                let diff = integer::pseudo::parse_label_format(op, ctx)?;
+                let (imm20, imm12) = Self::split_pcrel(diff);
 
                 fuse![
-                    Auipc(U {
-                        rd: 1,
-                        imm20: diff >> 12,
-                    }),
+                    Auipc(U { rd: 1, imm20 }),
                     Jalr(I {
                         rd: 1,
                         rs1: 1,
-                        imm12: diff & 0xfff,
+                        imm12,
                     }),
                 ]
             }
             "tail" => {
-                let diff =
-                    integer::pseudo::parse_label_format(op, instruction_labels, current_address)?;
+                let diff = integer::pseudo::parse_label_format(op, ctx)?;
+                let (imm20, imm12) = Self::split_pcrel(diff);
 
                 fuse![
-                    Auipc(U {
-                        rd: 6,
-                        imm20: diff >> 12,
-                    }),
+                    Auipc(U { rd: 6, imm20 }),
                     Jalr(I {
                         rd: 0,
                         rs1: 6,
-                        imm12: diff & 0xfff,
+                        imm12,
                     }),
                 ]
             }
@@ -2712,7 +3039,7 @@ impl Decoder {
                         csr: alias::FCSR,
                         rs1: rs,
                     }),
-                    Err(snd_err) => return Err(format!("{} or {}", fst_err, snd_err)),
+                    Err(snd_err) => return Err(fst_err.or(snd_err)),
                 },
             },
             "frrm" => {
@@ -2735,22 +3062,22 @@ impl Decoder {
                         csr: alias::FRM,
                         rs1: rs,
                     }),
-                    Err(snd_err) => return Err(format!("{} or {}", fst_err, snd_err)),
+                    Err(snd_err) => return Err(fst_err.or(snd_err)),
                 },
             },
-            "fsrmi" => match integer::pseudo::parse_op_imm_format(op) {
+            "fsrmi" => match integer::pseudo::parse_op_imm_format(op, ctx) {
                 Ok((rd, imm)) => Csrrwi(Csri {
                     rd,
                     csr: alias::FRM,
                     uimm: imm as u32 as usize,
                 }),
-                Err(fst_err) => match integer::pseudo::parse_imm_format(op) {
+                Err(fst_err) => match integer::pseudo::parse_imm_format(op, ctx) {
                     Ok(imm) => Csrrwi(Csri {
                         rd: 0,
                         csr: alias::FRM,
                         uimm: imm as u32 as usize,
                     }),
-                    Err(snd_err) => return Err(format!("{} or {}", fst_err, snd_err)),
+                    Err(snd_err) => return Err(fst_err.or(snd_err)),
                 },
             },
             "frflags" => {
@@ -2773,22 +3100,22 @@ impl Decoder {
                         csr: alias::FFLAGS,
                         rs1,
                     }),
-                    Err(snd_err) => return Err(format!("{} or {}", fst_err, snd_err)),
+                    Err(snd_err) => return Err(fst_err.or(snd_err)),
                 },
             },
-            "fsflagsi" => match integer::pseudo::parse_op_imm_format(op) {
+            "fsflagsi" => match integer::pseudo::parse_op_imm_format(op, ctx) {
                 Ok((rd, imm)) => Csrrwi(Csri {
                     rd,
                     csr: alias::FFLAGS,
                     uimm: imm as u32 as usize,
                 }),
-                Err(fst_err) => match integer::pseudo::parse_imm_format(op) {
+                Err(fst_err) => match integer::pseudo::parse_imm_format(op, ctx) {
                     Ok(imm) => Csrrwi(Csri {
                         rd: 0,
                         csr: alias::FFLAGS,
                         uimm: imm as u32 as usize,
                     }),
-                    Err(snd_err) => return Err(format!("{} or {}", fst_err, snd_err)),
+                    Err(snd_err) => return Err(fst_err.or(snd_err)),
                 },
             },
 
@@ -2874,7 +3201,7 @@ impl Decoder {
                 })
             }
             "vmslt.vi" => {
-                let (vd, vs2, imm, vm) = vector::pseudo::parse_op_op_imm_mask_format(op)?;
+                let (vd, vs2, imm, vm) = vector::pseudo::parse_op_op_imm_mask_format(op, ctx)?;
                 Vmslevi(Opivi {
                     vd,
                     imm5: imm - 1,
@@ -2883,7 +3210,7 @@ impl Decoder {
                 })
             }
             "vmsltu.vi" => {
-                let (vd, vs2, imm, vm) = vector::pseudo::parse_op_op_imm_mask_format(op)?;
+                let (vd, vs2, imm, vm) = vector::pseudo::parse_op_op_imm_mask_format(op, ctx)?;
                 Vmsleuvi(Opivi {
                     vd,
                     imm5: imm - 1,
@@ -2892,7 +3219,7 @@ impl Decoder {
                 })
             }
             "vmsge.vi" => {
-                let (vd, vs2, imm, vm) = vector::pseudo::parse_op_op_imm_mask_format(op)?;
+                let (vd, vs2, imm, vm) = vector::pseudo::parse_op_op_imm_mask_format(op, ctx)?;
                 Vmsgtvi(Opivi {
                     vd,
                     imm5: imm - 1,
@@ -2901,7 +3228,7 @@ impl Decoder {
                 })
             }
             "vmsgeu.vi" => {
-                let (vd, vs2, imm, vm) = vector::pseudo::parse_op_op_imm_mask_format(op)?;
+                let (vd, vs2, imm, vm) = vector::pseudo::parse_op_op_imm_mask_format(op, ctx)?;
                 Vmsgtuvi(Opivi {
                     vd,
                     imm5: imm - 1,
@@ -2985,9 +3312,7 @@ impl Decoder {
                                 ]
                             }
                         }
-                        Err(trd_err) => {
-                            return Err(format!("{}, {} or {}", fst_err, snd_err, trd_err))
-                        }
+                        Err(trd_err) => return Err(fst_err.or(snd_err).or(trd_err)),
                     },
                 },
             },
@@ -3067,9 +3392,7 @@ impl Decoder {
                                 ]
                             }
                         }
-                        Err(trd_err) => {
-                            return Err(format!("{}, {} or {}", fst_err, snd_err, trd_err))
-                        }
+                        Err(trd_err) => return Err(fst_err.or(snd_err).or(trd_err)),
                     },
                 },
             },
@@ -3145,26 +3468,207 @@ impl Decoder {
                     vm: false,
                 })
             }
-            _ => return Err(format!("Unknown mnemonic: {}", mnemonic)),
+            _ => {
+                return Err(Diagnostic::error(
+                    Code::UnknownMnemonic,
+                    format!("Unknown mnemonic: {}", mnemonic),
+                )
+                .with_token(mnemonic))
+            }
         };
 
         Ok(instruction)
     }
 
-    pub fn decode_data_section(data_line: &str) -> Result<Data, String> {
+    /// Splits an offset for an `auipc` pair, so that the low part fits a sign-extended 12-bit immediate
+    fn split_pcrel(offset: i32) -> (i32, i32) {
+        let offset = i64::from(offset);
+
+        (expression::hi(offset) as i32, expression::lo(offset) as i32)
+    }
+
+    // Follows the sequences emitted by GNU as: LUI+ADDIW for 32-bit values,
+    // larger ones are built from their upper bits shifted left and a final ADDI
+    fn materialize(rd: usize, value: i64, sequence: &mut Vec<Instruction>) {
+        let lo12 = (value << 52) >> 52;
+
+        if i32::try_from(value).is_ok() {
+            let hi20 = ((value - lo12) >> 12) as i32;
+
+            if hi20 != 0 {
+                sequence.push(Lui(U { rd, imm20: hi20 }));
+            }
+
+            if lo12 != 0 || hi20 == 0 {
+                let rs1 = if hi20 != 0 { rd } else { 0 };
+                let imm12 = lo12 as i32;
+
+                sequence.push(match hi20 {
+                    0 => Addi(I { rd, rs1, imm12 }),
+                    _ => Addiw(I { rd, rs1, imm12 }),
+                });
+            }
+
+            return;
+        }
+
+        let upper = value.wrapping_sub(lo12) >> 12;
+        let shift = upper.trailing_zeros() + 12;
+
+        Self::materialize(rd, upper >> (shift - 12), sequence);
+
+        sequence.push(Slli(I {
+            rd,
+            rs1: rd,
+            imm12: shift as i32,
+        }));
+
+        if lo12 != 0 {
+            sequence.push(Addi(I {
+                rd,
+                rs1: rd,
+                imm12: lo12 as i32,
+            }));
+        }
+    }
+
+    /// Target of `auipc rd, %pcrel_hi(symbol)`, which `%pcrel_lo` operands of other instructions refer to
+    pub fn pcrel_hi_target(instruction_line: &str, ctx: &Context) -> Option<i64> {
+        let (mnemonic, op) = Self::split_instruction(instruction_line);
+        let (_, imm) = op.split_once(',')?;
+        let symbol = imm.trim().strip_prefix("%pcrel_hi(")?.strip_suffix(')')?;
+
+        (mnemonic == "auipc")
+            .then(|| ctx.evaluate(symbol).ok())
+            .flatten()
+    }
+
+    /// Warnings about the line, which decoded to `instruction`
+    pub fn lint_text_section(instruction_line: &str, instruction: &Instruction) -> Vec<Diagnostic> {
+        let (mnemonic, op) = Self::split_instruction(instruction_line);
+        let mut warnings = Vec::new();
+
+        let rd = op.split(',').next().unwrap_or_default().trim();
+        // The canonical nop, however its operands are spelled, e.g. `addi zero, x0, 0`
+        let is_nop = mnemonic == "addi"
+            && *instruction
+                == Instruction::Addi(format::I {
+                    rd: 0,
+                    rs1: 0,
+                    imm12: 0,
+                });
+
+        if Self::writes_integer_rd(mnemonic)
+            && !is_nop
+            && matches!(integer::parse_operand(rd), Ok(0))
+        {
+            warnings.push(
+                Diagnostic::warning(
+                    Code::WriteToZero,
+                    format!(
+                        "Result of {} is discarded, x0 is hardwired to zero",
+                        mnemonic
+                    ),
+                )
+                .with_token(rd),
+            );
+        }
+
+        warnings
+    }
+
+    fn writes_integer_rd(mnemonic: &str) -> bool {
+        matches!(
+            mnemonic,
+            "add"
+                | "addw"
+                | "sub"
+                | "subw"
+                | "xor"
+                | "or"
+                | "and"
+                | "sll"
+                | "sllw"
+                | "srl"
+                | "srlw"
+                | "sra"
+                | "sraw"
+                | "slt"
+                | "sltu"
+                | "addi"
+                | "addiw"
+                | "xori"
+                | "ori"
+                | "andi"
+                | "slli"
+                | "slliw"
+                | "srli"
+                | "srliw"
+                | "srai"
+                | "sraiw"
+                | "slti"
+                | "sltiu"
+                | "lui"
+                | "auipc"
+                | "mul"
+                | "mulh"
+                | "mulhsu"
+                | "mulhu"
+                | "mulw"
+                | "div"
+                | "divu"
+                | "divw"
+                | "divuw"
+                | "rem"
+                | "remu"
+                | "remw"
+                | "remuw"
+                | "ld"
+                | "lw"
+                | "lh"
+                | "lb"
+                | "lwu"
+                | "lhu"
+                | "lbu"
+                | "li"
+                | "la"
+                | "mv"
+                | "not"
+                | "neg"
+                | "negw"
+                | "sext.w"
+                | "seqz"
+                | "snez"
+                | "sltz"
+                | "sgtz"
+        )
+    }
+
+    pub fn decode_data_section(data_line: &str, ctx: &Context) -> Result<Data, Diagnostic> {
         let (data_type, values) = Self::split_instruction(data_line);
 
         match data_type {
-            ".byte" => data::parse_bytes(values),
-            ".2byte" | ".half" | ".short" => data::parse_halves(values),
-            ".4byte" | ".word" | ".long" => data::parse_words(values),
-            ".8byte" | ".dword" | ".quad" => data::parse_quads(values),
+            ".byte" => data::parse_bytes(values, ctx),
+            ".2byte" | ".half" | ".short" => data::parse_halves(values, ctx),
+            ".4byte" | ".word" | ".long" => data::parse_words(values, ctx),
+            ".8byte" | ".dword" | ".quad" => data::parse_quads(values, ctx),
             ".float" => data::parse_floats(values),
             ".double" => data::parse_doubles(values),
-            ".string" => data::parse_string(values),
-            ".asciz" => data::parse_asciz(values),
-            ".zero" => data::parse_zero(values),
-            _ => Err(format!("Unknown data type: {}", data_type)),
+            ".half_float" | ".float16" => data::parse_float16s(values),
+            ".bfloat16" => data::parse_bfloat16s(values),
+            ".ascii" => data::parse_string(values, ctx),
+            ".asciz" | ".string" => data::parse_asciz(values, ctx),
+            ".zero" => data::parse_zero(values, ctx),
+            ".space" | ".skip" => data::parse_space(values, ctx),
+            ".fill" => data::parse_fill(values, ctx),
+            ".uleb128" => data::parse_uleb128(values, ctx),
+            ".sleb128" => data::parse_sleb128(values, ctx),
+            ".incbin" => data::parse_incbin(values, ctx),
+            _ => Err(Diagnostic::error(
+                Code::UnknownDirective,
+                format!("Unknown data type: {}", data_type),
+            )
+            .with_token(data_type)),
         }
     }
 
@@ -3201,6 +3705,8 @@ impl Decoder {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use super::*;
 
     #[test]
@@ -3230,8 +3736,10 @@ mod tests {
 
         memory_labels.insert("to_copy".to_owned(), 12);
 
-        let instruction =
-            Decoder::decode_text_section("la x1, to_copy", &HashMap::new(), &memory_labels, 0);
+        let instruction = Decoder::decode_text_section(
+            "la x1, to_copy",
+            &Context::new(&HashMap::new(), &memory_labels, 0),
+        );
 
         assert_eq!(
             instruction,
@@ -3252,8 +3760,10 @@ mod tests {
 
         memory_labels.insert("to_copy".to_owned(), 5000);
 
-        let instruction =
-            Decoder::decode_text_section("la x1, to_copy", &HashMap::new(), &memory_labels, 0);
+        let instruction = Decoder::decode_text_section(
+            "la x1, to_copy",
+            &Context::new(&HashMap::new(), &memory_labels, 0),
+        );
 
         assert_eq!(
             instruction,
@@ -3267,4 +3777,198 @@ mod tests {
             ])
         );
     }
+
+    fn load_immediate(value: &str) -> (u64, usize) {
+        let labels = HashMap::new();
+        let instruction = Decoder::decode_text_section(
+            &format!("li a0, {}", value),
+            &Context::new(&labels, &labels, 0),
+        )
+        .unwrap();
+
+        let sequence = match instruction {
+            Fusion(sequence) => sequence.into_vec(),
+            single => vec![single],
+        };
+
+        let mut a0 = 0xbad_u64;
+
+        for instruction in &sequence {
+            a0 = match *instruction {
+                Lui(U { rd: 10, imm20 }) => ((imm20 as i64) << 12) as i32 as u64,
+                Addi(I {
+                    rd: 10,
+                    rs1: 0,
+                    imm12,
+                }) => imm12 as u64,
+                Addi(I {
+                    rd: 10,
+                    rs1: 10,
+                    imm12,
+                }) => a0.wrapping_add(imm12 as u64),
+                Addiw(I {
+                    rd: 10,
+                    rs1: 10,
+                    imm12,
+                }) => (a0 as i32).wrapping_add(imm12) as u64,
+                Slli(I {
+                    rd: 10,
+                    rs1: 10,
+                    imm12,
+                }) => a0 << imm12,
+                ref other => panic!("Unexpected instruction {:?}", other),
+            };
+        }
+
+        (a0, sequence.len())
+    }
+
+    #[test]
+    fn li_materializes_constants() {
+        assert_eq!(load_immediate("0"), (0, 1));
+        assert_eq!(load_immediate("-1"), (u64::MAX, 1));
+        assert_eq!(load_immediate("2047"), (2047, 1));
+        assert_eq!(load_immediate("-2048"), (-2048_i64 as u64, 1));
+        assert_eq!(load_immediate("0x800"), (0x800, 2));
+        assert_eq!(load_immediate("4096"), (4096, 1));
+        assert_eq!(load_immediate("0x7fffffff"), (0x7fffffff, 2));
+        assert_eq!(load_immediate("-0x80000000"), (-0x80000000_i64 as u64, 1));
+        assert_eq!(load_immediate("0x80000000").0, 0x80000000);
+        assert_eq!(load_immediate("0xffffffff").0, 0xffffffff);
+        assert_eq!(load_immediate("1 << 40"), (1 << 40, 2));
+        assert_eq!(load_immediate("0x7fffffffffffffff").0, i64::MAX as u64);
+        assert_eq!(load_immediate("0x8000000000000000").0, i64::MIN as u64);
+        assert_eq!(load_immediate("0xdeadbeefcafebabe").0, 0xdeadbeefcafebabe);
+    }
+
+    fn decode(line: &str) -> Result<Instruction, Diagnostic> {
+        let labels = HashMap::new();
+
+        Decoder::decode_text_section(line, &Context::new(&labels, &labels, 0))
+    }
+
+    #[test]
+    fn float_operand_counts() {
+        assert_eq!(
+            decode("fdiv.s fa0, fa1, fa2"),
+            Ok(Fdivs(R {
+                rd: 10,
+                rs1: 11,
+                rs2: 12
+            }))
+        );
+        assert_eq!(
+            decode("fdiv.d fa0, fa1, fa2"),
+            Ok(Fdivd(R {
+                rd: 10,
+                rs1: 11,
+                rs2: 12
+            }))
+        );
+
+        for (line, instruction) in [
+            ("fsqrt.s fa0, fa1", Fsqrts as fn(R) -> Instruction),
+            ("fsqrt.d fa0, fa1", Fsqrtd),
+            ("fcvt.s.d fa0, fa1", Fcvtsd),
+            ("fcvt.d.s fa0, fa1", Fcvtds),
+        ] {
+            assert_eq!(
+                decode(line),
+                Ok(instruction(R {
+                    rd: 10,
+                    rs1: 11,
+                    rs2: 0
+                })),
+                "{}",
+                line
+            );
+        }
+
+        assert!(decode("fdiv.s fa0, fa1").is_err());
+        assert!(decode("fsqrt.d fa0, fa1, fa2").is_err());
+    }
+
+    #[test]
+    fn indexed_stores_keep_their_ordering() {
+        let Ok(Vsuxv { eew, data }) = decode("vsuxei32.v v1, (a0), v2") else {
+            panic!("Expected an unordered indexed store");
+        };
+
+        assert_eq!(
+            decode("vsoxei32.v v1, (a0), v2"),
+            Ok(Vsoxv {
+                eew: BaseSew::E32,
+                data
+            })
+        );
+        assert_eq!(eew, BaseSew::E32);
+        assert!(decode("vsuxeix32.v v1, (a0), v2").is_err());
+    }
+
+    #[test]
+    fn carry_and_merge_take_v0() {
+        let opivv = Opivv {
+            vd: 1,
+            vs2: 2,
+            vs1: 3,
+            vm: true,
+        };
+
+        assert_eq!(
+            decode("vmerge.vvm v1, v2, v3, v0"),
+            Ok(Vmergevvm(opivv.clone()))
+        );
+        assert_eq!(
+            decode("vsbc.vvm v1, v2, v3, v0"),
+            Ok(Vsbcvvm(opivv.clone()))
+        );
+        assert_eq!(decode("vmsbc.vvm v1, v2, v3, v0"), Ok(Vmsbcvvm(opivv)));
+
+        assert!(matches!(
+            decode("vmerge.vim v1, v2, -16, v0"),
+            Ok(Vmergevim(Opivi {
+                vd: 1,
+                vs2: 2,
+                imm5: -16,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            decode("vsbc.vxm v1, v2, a0, v0"),
+            Ok(Vsbcvxm(Opivx {
+                vd: 1,
+                vs2: 2,
+                rs1: 10,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            decode("vmsbc.vx v1, v2, a0"),
+            Ok(Vmsbcvx(Opivx {
+                vd: 1,
+                vs2: 2,
+                rs1: 10,
+                ..
+            }))
+        ));
+
+        assert!(decode("vmerge.vvm v1, v2, v3, v0.t").is_err());
+        assert!(decode("vmerge.vvm v1, v2, v3, v1").is_err());
+        assert!(decode("vsbc.vxm v1, v2, a0").is_err());
+        assert!(decode("vmsbc.vv v1, v2, v3, v0.t").is_err());
+    }
+
+    #[test]
+    fn vfmv_splats_a_float_register() {
+        assert_eq!(
+            decode("vfmv.v.f v1, fa0"),
+            Ok(Vfmvvf(Vrfunary0 {
+                vd: 1,
+                vs2: 0,
+                rs1: 10,
+                vm: false
+            }))
+        );
+        assert!(decode("vfmv.v.f v1, v2, fa0").is_err());
+    }
 }
@@ -1,117 +1,495 @@
-use std::ascii;
+use std::cmp::Ordering;
 
-use super::Data;
+use super::{
+    expression::{parse_escape, Context},
+    Data,
+};
+use crate::interpreter::diagnostic::{Code, Diagnostic};
 
-fn parse_single_byte(byte: &str) -> Result<u8, String> {
-    if byte.len() == 3 && byte.starts_with('\'') && byte.ends_with('\'') {
-        let ch = byte.as_bytes()[1];
-        if ch.is_ascii() {
-            return Ok(ch);
-        }
-    }
-    byte.parse::<u8>().map_err(|err| err.to_string())
+fn parse_single_integer<T: TryFrom<i64>>(value: &str, ctx: &Context) -> Result<T, Diagnostic> {
+    let integer = ctx.evaluate(value)?;
+
+    // Counts depending on later labels are checked once they are known
+    T::try_from(integer)
+        .or_else(|err| match ctx.lenient {
+            true => T::try_from(0),
+            false => Err(err),
+        })
+        .map_err(|_| {
+            Diagnostic::error(
+                Code::InvalidData,
+                format!(
+                    "Value {} does not fit in {} bits",
+                    integer,
+                    std::mem::size_of::<T>() * 8
+                ),
+            )
+            .with_token(value)
+        })
 }
 
-pub fn parse_bytes(bytes: &str) -> Result<Data, String> {
-    let bytes = bytes
-        .split(',')
-        .map(str::trim)
-        .map(parse_single_byte)
-        .collect::<Result<_, _>>()?;
-    Ok(Data::Byte(bytes))
+// Values may be written signed or unsigned, e.g. both -1 and 0xff fill a byte
+fn parse_sized_integer<T: TryFrom<u64>>(value: &str, ctx: &Context) -> Result<T, Diagnostic> {
+    let integer = ctx.evaluate(value)?;
+    let bits = std::mem::size_of::<T>() * 8;
+    let fits = bits == 64 || (-(1i64 << (bits - 1))..1i64 << bits).contains(&integer);
+
+    match T::try_from(integer as u64 & (u64::MAX >> (64 - bits))) {
+        Ok(integer) if fits || ctx.lenient => Ok(integer),
+        _ => Err(Diagnostic::error(
+            Code::InvalidData,
+            format!("Value {} does not fit in {} bits", integer, bits),
+        )
+        .with_token(value)),
+    }
 }
 
-fn parse_single_half(half: &str) -> Result<u16, String> {
-    half.parse::<u16>().map_err(|err| err.to_string())
+fn parse_integers<T: TryFrom<u64>>(values: &str, ctx: &Context) -> Result<Vec<T>, Diagnostic> {
+    split_values(values)
+        .into_iter()
+        .map(|value| parse_sized_integer(value, ctx))
+        .collect()
 }
 
-pub fn parse_halves(halves: &str) -> Result<Data, String> {
-    let halves = halves
-        .split(',')
-        .map(str::trim)
-        .map(parse_single_half)
-        .collect::<Result<_, _>>()?;
-    Ok(Data::Half(halves))
+/// Splits comma-separated values, except at commas inside character and string literals
+fn split_values(values: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (index, ch) in values.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if ch == '\\' => escaped = true,
+            Some(open) if ch == open => quote = None,
+            Some(_) => {}
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None if ch == ',' => {
+                parts.push(values[start..index].trim());
+                start = index + 1;
+            }
+            None => {}
+        }
+    }
+
+    parts.push(values[start..].trim());
+    parts
 }
 
-fn parse_single_word(word: &str) -> Result<u32, String> {
-    word.parse::<u32>().map_err(|err| err.to_string())
+pub fn parse_bytes(bytes: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    parse_integers(bytes, ctx).map(Data::Byte)
 }
 
-pub fn parse_words(words: &str) -> Result<Data, String> {
-    let words = words
-        .split(',')
-        .map(str::trim)
-        .map(parse_single_word)
-        .collect::<Result<_, _>>()?;
-    Ok(Data::Word(words))
+pub fn parse_halves(halves: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    parse_integers(halves, ctx).map(Data::Half)
 }
 
-fn parse_single_quad(quad: &str) -> Result<u64, String> {
-    quad.parse::<u64>().map_err(|err| err.to_string())
+pub fn parse_words(words: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    parse_integers(words, ctx).map(Data::Word)
 }
 
-pub fn parse_quads(quads: &str) -> Result<Data, String> {
-    let quads = quads
-        .split(',')
-        .map(str::trim)
-        .map(parse_single_quad)
-        .collect::<Result<_, _>>()?;
-    Ok(Data::Quad(quads))
+pub fn parse_quads(quads: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    parse_integers(quads, ctx).map(Data::Quad)
 }
 
-fn parse_single_float(float: &str) -> Result<f32, String> {
-    float.parse::<f32>().map_err(|err| err.to_string())
+fn parse_single_float(float: &str) -> Result<f32, Diagnostic> {
+    float
+        .parse::<f32>()
+        .map_err(|err| invalid_value(float, err))
 }
 
-pub fn parse_floats(floats: &str) -> Result<Data, String> {
-    let floats = floats
-        .split(',')
-        .map(str::trim)
+pub fn parse_floats(floats: &str) -> Result<Data, Diagnostic> {
+    let floats = split_values(floats)
+        .into_iter()
         .map(parse_single_float)
         .collect::<Result<_, _>>()?;
     Ok(Data::Float(floats))
 }
 
-fn parse_single_double(double: &str) -> Result<f64, String> {
-    double.parse::<f64>().map_err(|err| err.to_string())
+fn parse_single_double(double: &str) -> Result<f64, Diagnostic> {
+    double
+        .parse::<f64>()
+        .map_err(|err| invalid_value(double, err))
 }
 
-pub fn parse_doubles(doubles: &str) -> Result<Data, String> {
-    let doubles = doubles
-        .split(',')
-        .map(str::trim)
+pub fn parse_doubles(doubles: &str) -> Result<Data, Diagnostic> {
+    let doubles = split_values(doubles)
+        .into_iter()
         .map(parse_single_double)
         .collect::<Result<_, _>>()?;
     Ok(Data::Double(doubles))
 }
 
-fn parse_ascii(ascii: &str) -> Result<Vec<ascii::Char>, String> {
-    if !ascii.starts_with('"') || !ascii.ends_with('"') {
-        return Err("Ascii string needs to be wrapped in quotes".to_owned());
+pub fn parse_float16s(halves: &str) -> Result<Data, Diagnostic> {
+    let halves = split_values(halves)
+        .into_iter()
+        .map(|half| parse_single_narrow_float(half, 5, 10))
+        .collect::<Result<_, _>>()?;
+    Ok(Data::Float16(halves))
+}
+
+pub fn parse_bfloat16s(halves: &str) -> Result<Data, Diagnostic> {
+    let halves = split_values(halves)
+        .into_iter()
+        .map(|half| parse_single_narrow_float(half, 8, 7))
+        .collect::<Result<_, _>>()?;
+    Ok(Data::BFloat16(halves))
+}
+
+// Parsing into f64 first rounds twice, which is only wrong if that lands exactly between
+// two 16-bit values, so such ties are broken by comparing against the literal itself
+fn parse_single_narrow_float(
+    float: &str,
+    exponent_bits: u32,
+    mantissa_bits: u32,
+) -> Result<u16, Diagnostic> {
+    let value = float
+        .parse::<f64>()
+        .map_err(|err| invalid_value(float, err))?;
+
+    Ok(narrow_float(
+        value,
+        exponent_bits,
+        mantissa_bits,
+        |midpoint| compare_decimal(float, midpoint),
+    ))
+}
+
+/// Rounds to nearest, ties to even, into a 16-bit format with the given field widths
+///
+/// `tie` tells whether the exact value is below, at or above a midpoint equal to `value`.
+fn narrow_float(
+    value: f64,
+    exponent_bits: u32,
+    mantissa_bits: u32,
+    tie: impl FnOnce(f64) -> Ordering,
+) -> u16 {
+    let sign = (value.is_sign_negative() as u16) << 15;
+    let infinity = sign | (((1 << exponent_bits) - 1) << mantissa_bits);
+
+    if value.is_nan() {
+        return infinity | (1 << (mantissa_bits - 1));
+    }
+
+    if value.is_infinite() {
+        return infinity;
     }
 
-    let unwrapped = &ascii[1..ascii.len() - 1];
-    let ascii = unwrapped
-        .as_ascii()
-        .ok_or_else(|| format!("Unable to parse {} as an ascii string", ascii))?;
-    Ok(ascii.to_vec())
+    let bits = value.abs().to_bits();
+    let biased_exponent = (bits >> 52) as i32;
+
+    // Subnormal doubles are far below the smallest 16-bit subnormal
+    if biased_exponent == 0 {
+        return sign;
+    }
+
+    let significand = (bits & ((1 << 52) - 1)) | (1 << 52);
+    let exponent = biased_exponent - 1023;
+    let bias = (1 << (exponent_bits - 1)) - 1;
+
+    if exponent + bias >= (1 << exponent_bits) - 1 {
+        return infinity;
+    }
+
+    // Subnormal results keep fewer bits of the significand
+    let shift = 52 - mantissa_bits as i32 + (1 - bias - exponent).max(0);
+
+    if shift > 53 {
+        return sign;
+    }
+
+    let truncated = significand >> shift;
+    let remainder = significand & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+
+    let round_up = match remainder.cmp(&half) {
+        Ordering::Less => false,
+        Ordering::Greater => true,
+        Ordering::Equal => match tie(value.abs()) {
+            Ordering::Equal => truncated & 1 == 1,
+            ordering => ordering == Ordering::Greater,
+        },
+    };
+    let rounded = (truncated + round_up as u64) as u16;
+
+    // A carry out of the mantissa increments the exponent, possibly up to infinity
+    match exponent + bias > 0 {
+        true => {
+            sign | ((((exponent + bias) as u16) << mantissa_bits) + rounded - (1 << mantissa_bits))
+        }
+        false => sign | rounded,
+    }
 }
 
-pub fn parse_string(string: &str) -> Result<Data, String> {
-    let string = parse_ascii(string)?;
-    Ok(Data::Ascii(string))
+/// Compares the magnitude of a decimal literal with the exact value of `value`
+fn compare_decimal(decimal: &str, value: f64) -> Ordering {
+    // Binary fractions have finite decimal expansions, which are printed exactly
+    let exact = format!("{:.1100e}", value);
+
+    match (normalize_decimal(decimal), normalize_decimal(&exact)) {
+        (Some(decimal), Some(exact)) => decimal.cmp(&exact),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Exponent and significant digits of a decimal, which equals `0.<digits> * 10^exponent`
+fn normalize_decimal(decimal: &str) -> Option<(i64, Vec<u8>)> {
+    let decimal = decimal.trim_start_matches(['+', '-']);
+    let (mantissa, exponent) = match decimal.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+        None => (decimal, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    let digits = integer
+        .bytes()
+        .chain(fraction.bytes())
+        .map(|digit| (digit as char).to_digit(10).map(|digit| digit as u8))
+        .collect::<Option<Vec<_>>>()?;
+    let leading_zeros = digits.iter().take_while(|&&digit| digit == 0).count();
+    let trailing_zeros = digits.iter().rev().take_while(|&&digit| digit == 0).count();
+
+    if leading_zeros == digits.len() {
+        return Some((i64::MIN, Vec::new()));
+    }
+
+    Some((
+        exponent + integer.len() as i64 - leading_zeros as i64,
+        digits[leading_zeros..digits.len() - trailing_zeros].to_vec(),
+    ))
 }
 
-pub fn parse_asciz(asciz: &str) -> Result<Data, String> {
-    let mut asciz = parse_ascii(asciz)?;
-    asciz.push(ascii::Char::Null);
-    Ok(Data::Ascii(asciz))
+// Escapes may produce any byte, other characters have to be ASCII unless UTF-8 is enabled
+fn parse_string_literal(literal: &str, ctx: &Context) -> Result<Vec<u8>, Diagnostic> {
+    let Some(mut rest) = literal
+        .strip_prefix('"')
+        .and_then(|literal| literal.strip_suffix('"'))
+    else {
+        return Err(
+            Diagnostic::error(Code::InvalidData, "String needs to be wrapped in quotes")
+                .with_token(literal),
+        );
+    };
+
+    let mut bytes = Vec::new();
+
+    while let Some(ch) = rest.chars().next() {
+        let length = match ch {
+            '\\' => {
+                let (byte, length) = parse_escape(&rest[1..]).ok_or_else(|| {
+                    Diagnostic::error(
+                        Code::InvalidData,
+                        format!("Invalid escape sequence in {}", literal),
+                    )
+                    .with_token(literal)
+                })?;
+                bytes.push(byte);
+                length + 1
+            }
+            '"' => {
+                return Err(Diagnostic::error(
+                    Code::InvalidData,
+                    format!("Unescaped quote in {}", literal),
+                )
+                .with_token(literal)
+                .with_help("write it as \\\""));
+            }
+            _ if ch.is_ascii() || ctx.utf8 => {
+                bytes.extend(ch.encode_utf8(&mut [0; 4]).bytes());
+                ch.len_utf8()
+            }
+            _ => {
+                return Err(Diagnostic::error(
+                    Code::InvalidData,
+                    format!("Non-ASCII character {} in {}", ch, literal),
+                )
+                .with_token(literal)
+                .with_help("enable CompileOptions::utf8 to encode strings as UTF-8"));
+            }
+        };
+
+        rest = &rest[length..];
+    }
+
+    Ok(bytes)
+}
+
+pub fn parse_string(strings: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    let mut bytes = Vec::new();
+
+    for string in split_values(strings) {
+        bytes.extend(parse_string_literal(string, ctx)?);
+    }
+
+    Ok(Data::String(bytes))
+}
+
+// Every string is terminated, e.g. `.asciz "a", "b"` takes 4 bytes
+pub fn parse_asciz(strings: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    let mut bytes = Vec::new();
+
+    for string in split_values(strings) {
+        bytes.extend(parse_string_literal(string, ctx)?);
+        bytes.push(0);
+    }
+
+    Ok(Data::String(bytes))
+}
+
+pub fn parse_zero(length: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    parse_single_integer(length, ctx).map(Data::Zero)
+}
+
+pub fn parse_space(arguments: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    let (length, fill) = arguments.split_once(',').unwrap_or((arguments, "0"));
+    let length = parse_single_integer(length.trim(), ctx)?;
+    let fill = parse_sized_integer(fill.trim(), ctx)?;
+
+    Ok(Data::Fill(length, vec![fill]))
+}
+
+// Like in GAS, the value is truncated to the size, which defaults to 1 with a value of 0
+pub fn parse_fill(arguments: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    let mut tokens = split_values(arguments).into_iter();
+    let repeat: usize = parse_single_integer(tokens.next().unwrap_or_default(), ctx)?;
+    let size = match tokens.next() {
+        Some(size) => parse_single_integer::<usize>(size, ctx)?,
+        None => 1,
+    };
+    let value = match tokens.next() {
+        Some(value) => ctx.evaluate(value)?,
+        None => 0,
+    };
+
+    if size > 8 && !ctx.lenient {
+        return Err(Diagnostic::error(
+            Code::InvalidData,
+            format!("Fill size {} is larger than 8 bytes", size),
+        )
+        .with_token(arguments));
+    }
+
+    let pattern = &value.to_le_bytes()[..size.min(8)];
+
+    Ok(Data::Fill(repeat, pattern.to_vec()))
+}
+
+pub fn parse_uleb128(values: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    let mut bytes = Vec::new();
+
+    for value in split_values(values) {
+        let mut value = ctx.evaluate(value)? as u64;
+
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                bytes.push(byte);
+                break;
+            }
+
+            bytes.push(byte | 0x80);
+        }
+    }
+
+    Ok(Data::Byte(bytes))
+}
+
+pub fn parse_sleb128(values: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    let mut bytes = Vec::new();
+
+    for value in split_values(values) {
+        let mut value = ctx.evaluate(value)?;
+
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+
+            // Done once the remaining bits are a sign extension of the last byte
+            if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+                bytes.push(byte);
+                break;
+            }
+
+            bytes.push(byte | 0x80);
+        }
+    }
+
+    Ok(Data::Byte(bytes))
+}
+
+pub fn parse_incbin(arguments: &str, ctx: &Context) -> Result<Data, Diagnostic> {
+    let mut tokens = split_values(arguments).into_iter();
+    let quoted = tokens.next().unwrap_or_default();
+
+    let path = match quoted
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+    {
+        Some(path) if !path.is_empty() && !path.contains('"') => path,
+        _ => {
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: '.incbin \"file\"[, skip[, count]]', got {} instead",
+                    arguments
+                ),
+            )
+            .with_token(".incbin"))
+        }
+    };
+
+    let Some(provider) = ctx.source_provider else {
+        return Err(Diagnostic::error(
+            Code::InvalidInclude,
+            format!("Cannot include {}: no source provider is configured", path),
+        )
+        .with_token(path)
+        .with_help("set CompileOptions::source_provider"));
+    };
+
+    let contents = provider.read_bytes(path).map_err(|reason| {
+        Diagnostic::error(
+            Code::InvalidInclude,
+            format!("Cannot include {}: {}", path, reason),
+        )
+        .with_token(path)
+    })?;
+
+    let skip: usize = match tokens.next() {
+        Some(skip) => parse_single_integer(skip, ctx)?,
+        None => 0,
+    };
+    let count = match tokens.next() {
+        Some(count) => parse_single_integer(count, ctx)?,
+        None => contents.len().saturating_sub(skip),
+    };
+
+    contents
+        .get(skip..skip.saturating_add(count))
+        .or(ctx.lenient.then_some(&[][..]))
+        .map(|bytes| Data::Byte(bytes.to_vec()))
+        .ok_or_else(|| {
+            Diagnostic::error(
+                Code::InvalidData,
+                format!(
+                    "Cannot read {} bytes at offset {} of {}, which has {} bytes",
+                    count,
+                    skip,
+                    path,
+                    contents.len()
+                ),
+            )
+            .with_token(arguments)
+        })
 }
 
-pub fn parse_zero(length: &str) -> Result<Data, String> {
-    length
-        .parse::<usize>()
-        .map(Data::Zero)
-        .map_err(|err| err.to_string())
+fn invalid_value(value: &str, err: impl std::fmt::Display) -> Diagnostic {
+    Diagnostic::error(
+        Code::InvalidData,
+        format!("Invalid value {}: {}", value, err),
+    )
+    .with_token(value)
 }
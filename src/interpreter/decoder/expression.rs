@@ -0,0 +1,521 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::interpreter::{
+    diagnostic::{Code, Diagnostic},
+    SourceProvider,
+};
+
+/// Symbols visible to the expressions of a single line
+pub struct Context<'a> {
+    pub instruction_labels: &'a HashMap<String, usize>,
+    pub memory_labels: &'a HashMap<String, usize>,
+    pub current_address: usize,
+    /// Values of `.equ` and `.set` symbols, which take precedence over labels
+    pub symbols: Option<&'a HashMap<String, i64>>,
+    /// Targets of `%pcrel_hi` operands, keyed by the address of their instruction
+    pub pcrel_targets: Option<&'a HashMap<usize, i64>>,
+    /// Resolves unknown symbols to 0 and ignores errors of values, used to size data before all labels are known
+    pub lenient: bool,
+    /// Reads the files of `.incbin` directives
+    pub source_provider: Option<&'a dyn SourceProvider>,
+    /// Encodes non-ASCII characters of string literals as UTF-8 instead of rejecting them
+    pub utf8: bool,
+    /// Collects the labels which the expressions resolve
+    pub resolved: Option<&'a RefCell<Vec<String>>>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(
+        instruction_labels: &'a HashMap<String, usize>,
+        memory_labels: &'a HashMap<String, usize>,
+        current_address: usize,
+    ) -> Self {
+        Self {
+            instruction_labels,
+            memory_labels,
+            current_address,
+            symbols: None,
+            pcrel_targets: None,
+            lenient: false,
+            source_provider: None,
+            utf8: false,
+            resolved: None,
+        }
+    }
+
+    /// Evaluates an immediate or an address, memory labels take precedence over instruction labels
+    pub fn evaluate(&self, expr: &str) -> Result<i64, Diagnostic> {
+        self.evaluate_with(expr, false)
+    }
+
+    /// Evaluates a jump or branch target, instruction labels take precedence over memory labels
+    pub fn evaluate_target(&self, expr: &str) -> Result<i64, Diagnostic> {
+        self.evaluate_with(expr, true)
+    }
+
+    fn evaluate_with(&self, expr: &str, target: bool) -> Result<i64, Diagnostic> {
+        let tokens = tokenize(expr)?;
+
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+            ctx: self,
+            target,
+        };
+
+        let value = parser.parse_logical_or()?;
+
+        match parser.peek() {
+            None => Ok(value),
+            Some(token) => Err(invalid(
+                format!("Unexpected {} in expression {}", token, expr),
+                expr,
+            )),
+        }
+    }
+
+    fn resolve(&self, symbol: &str, target: bool) -> Result<i64, Diagnostic> {
+        let (preferred, fallback, kind) = if target {
+            (
+                self.instruction_labels,
+                self.memory_labels,
+                "instruction label",
+            )
+        } else {
+            (self.memory_labels, self.instruction_labels, "memory label")
+        };
+
+        if let Some(&value) = self.symbols.and_then(|symbols| symbols.get(symbol)) {
+            return Ok(value);
+        }
+
+        match preferred.get(symbol).or_else(|| fallback.get(symbol)) {
+            Some(&address) => {
+                if let Some(resolved) = self.resolved {
+                    resolved.borrow_mut().push(symbol.to_owned());
+                }
+
+                Ok(address as i64)
+            }
+            None if self.lenient => Ok(0),
+            None => Err(Diagnostic::error(
+                Code::UnknownLabel,
+                format!("Did not find {} {}", kind, symbol),
+            )
+            .with_token(symbol)),
+        }
+    }
+
+    fn pcrel_target(&self, auipc_address: i64, label: &str) -> Result<i64, Diagnostic> {
+        let target = usize::try_from(auipc_address)
+            .ok()
+            .and_then(|address| self.pcrel_targets?.get(&address));
+
+        match target {
+            Some(&target) => Ok(target.wrapping_sub(auipc_address)),
+            None if self.lenient => Ok(0),
+            None => Err(Diagnostic::error(
+                Code::InvalidImmediate,
+                format!(
+                    "%pcrel_lo({}) does not refer to an instruction using %pcrel_hi",
+                    label
+                ),
+            )
+            .with_token(label)
+            .with_help("label the auipc instruction and pass that label to %pcrel_lo")),
+        }
+    }
+}
+
+/// Upper 20 bits of the value, rounded so that adding the sign-extended lower 12 bits restores it
+pub fn hi(value: i64) -> i64 {
+    value.wrapping_add(0x800) >> 12
+}
+
+pub fn lo(value: i64) -> i64 {
+    value.wrapping_sub(hi(value).wrapping_shl(12))
+}
+
+#[derive(PartialEq, Debug)]
+enum Token<'a> {
+    Number(i64),
+    Symbol(&'a str),
+    Operator(&'static str),
+}
+
+impl std::fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(number) => write!(f, "'{}'", number),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+            Token::Operator(operator) => write!(f, "'{}'", operator),
+        }
+    }
+}
+
+// Longer operators come first, so that e.g. "<<" is not read as "<"
+const OPERATORS: [&str; 22] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "&", "|",
+    "^", "~", "(", ")", "!",
+];
+
+fn tokenize(expr: &str) -> Result<Vec<Token<'_>>, Diagnostic> {
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+
+    if rest.is_empty() {
+        return Err(invalid("Expected an expression", expr));
+    }
+
+    while let Some(ch) = rest.chars().next() {
+        let length = if ch.is_ascii_digit() {
+            let length = rest.find(|ch| !is_symbol_char(ch)).unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..length])?));
+            length
+        } else if ch == '\'' {
+            let (value, length) = parse_char(rest)?;
+            tokens.push(Token::Number(value));
+            length
+        } else if is_symbol_start(ch) {
+            let length = rest.find(|ch| !is_symbol_char(ch)).unwrap_or(rest.len());
+            tokens.push(Token::Symbol(&rest[..length]));
+            length
+        } else if let Some(operator) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Operator(operator));
+            operator.len()
+        } else {
+            return Err(invalid(
+                format!("Unexpected character '{}' in expression {}", ch, expr),
+                expr,
+            ));
+        };
+
+        rest = rest[length..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+// Like in GNU as, a leading 0 makes a number octal, e.g. `010` is 8
+fn parse_number(number: &str) -> Result<i64, Diagnostic> {
+    let (digits, radix) = match number.get(..2) {
+        Some("0x" | "0X") => (&number[2..], 16),
+        Some("0b" | "0B") => (&number[2..], 2),
+        Some(_) if number.starts_with('0') => (&number[1..], 8),
+        _ => (number, 10),
+    };
+
+    // Literals up to u64::MAX are accepted and reinterpreted as two's complement
+    u64::from_str_radix(&digits.replace('_', ""), radix)
+        .map(|value| value as i64)
+        .map_err(|err| {
+            let diagnostic = invalid(format!("Error parsing number {}: {}", number, err), number);
+
+            match radix {
+                8 => {
+                    diagnostic.with_help("numbers with a leading 0 are octal, drop it for decimal")
+                }
+                _ => diagnostic,
+            }
+        })
+}
+
+fn parse_char(literal: &str) -> Result<(i64, usize), Diagnostic> {
+    let body = &literal[1..];
+
+    match body.chars().next() {
+        Some('\\') => parse_escape(&body[1..]).map(|(byte, length)| (byte as i64, length + 1)),
+        Some(ch) if ch != '\'' => Some((ch as i64, ch.len_utf8())),
+        _ => None,
+    }
+    .filter(|(_, length)| body[*length..].starts_with('\''))
+    .map(|(value, length)| (value, length + 2))
+    .ok_or_else(|| invalid(format!("Invalid character literal {}", literal), literal))
+}
+
+/// Decodes the C-style escape sequence following a backslash into a byte and its length
+pub fn parse_escape(escaped: &str) -> Option<(u8, usize)> {
+    match escaped.chars().next()? {
+        'n' => Some((b'\n', 1)),
+        't' => Some((b'\t', 1)),
+        'r' => Some((b'\r', 1)),
+        'a' => Some((0x07, 1)),
+        'b' => Some((0x08, 1)),
+        'f' => Some((0x0c, 1)),
+        'v' => Some((0x0b, 1)),
+        'e' => Some((0x1b, 1)),
+        ch @ ('\\' | '\'' | '"') => Some((ch as u8, 1)),
+        '0'..='7' => {
+            let length = escaped
+                .chars()
+                .take(3)
+                .take_while(|ch| ch.is_digit(8))
+                .count();
+            u8::from_str_radix(&escaped[..length], 8)
+                .ok()
+                .map(|byte| (byte, length))
+        }
+        'x' => {
+            let length = escaped[1..]
+                .chars()
+                .take(2)
+                .take_while(char::is_ascii_hexdigit)
+                .count();
+            u8::from_str_radix(&escaped[1..1 + length], 16)
+                .ok()
+                .map(|byte| (byte, length + 1))
+        }
+        _ => None,
+    }
+}
+
+fn is_symbol_start(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == '_' || ch == '.' || ch == '$'
+}
+
+fn is_symbol_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_' || ch == '.' || ch == '$'
+}
+
+fn invalid(message: impl Into<String>, token: &str) -> Diagnostic {
+    Diagnostic::error(Code::InvalidImmediate, message).with_token(token.trim())
+}
+
+// Operator precedence follows C: unary, multiplicative, additive, shifts, relational,
+// equality, &, ^, |, &&, ||. Comparisons evaluate to 1 or 0
+struct Parser<'t, 'c> {
+    tokens: &'t [Token<'t>],
+    position: usize,
+    ctx: &'c Context<'c>,
+    target: bool,
+}
+
+impl Parser<'_, '_> {
+    fn peek(&self) -> Option<&Token<'_>> {
+        self.tokens.get(self.position)
+    }
+
+    fn eat(&mut self, operators: &[&str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Operator(operator)) if operators.contains(operator) => {
+                let operator = *operator;
+                self.position += 1;
+                Some(operator)
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_logical_or(&mut self) -> Result<i64, Diagnostic> {
+        let mut lhs = self.parse_logical_and()?;
+        while self.eat(&["||"]).is_some() {
+            let rhs = self.parse_logical_and()?;
+            lhs = (lhs != 0 || rhs != 0) as i64;
+        }
+        Ok(lhs)
+    }
+
+    fn parse_logical_and(&mut self) -> Result<i64, Diagnostic> {
+        let mut lhs = self.parse_or()?;
+        while self.eat(&["&&"]).is_some() {
+            let rhs = self.parse_or()?;
+            lhs = (lhs != 0 && rhs != 0) as i64;
+        }
+        Ok(lhs)
+    }
+
+    fn parse_or(&mut self) -> Result<i64, Diagnostic> {
+        let mut lhs = self.parse_xor()?;
+        while self.eat(&["|"]).is_some() {
+            lhs |= self.parse_xor()?;
+        }
+        Ok(lhs)
+    }
+
+    fn parse_xor(&mut self) -> Result<i64, Diagnostic> {
+        let mut lhs = self.parse_and()?;
+        while self.eat(&["^"]).is_some() {
+            lhs ^= self.parse_and()?;
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<i64, Diagnostic> {
+        let mut lhs = self.parse_equality()?;
+        while self.eat(&["&"]).is_some() {
+            lhs &= self.parse_equality()?;
+        }
+        Ok(lhs)
+    }
+
+    fn parse_equality(&mut self) -> Result<i64, Diagnostic> {
+        let mut lhs = self.parse_relational()?;
+        while let Some(operator) = self.eat(&["==", "!="]) {
+            let rhs = self.parse_relational()?;
+            lhs = match operator {
+                "==" => (lhs == rhs) as i64,
+                _ => (lhs != rhs) as i64,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn parse_relational(&mut self) -> Result<i64, Diagnostic> {
+        let mut lhs = self.parse_shift()?;
+        while let Some(operator) = self.eat(&["<", ">", "<=", ">="]) {
+            let rhs = self.parse_shift()?;
+            lhs = match operator {
+                "<" => (lhs < rhs) as i64,
+                ">" => (lhs > rhs) as i64,
+                "<=" => (lhs <= rhs) as i64,
+                _ => (lhs >= rhs) as i64,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn parse_shift(&mut self) -> Result<i64, Diagnostic> {
+        let mut lhs = self.parse_additive()?;
+        while let Some(operator) = self.eat(&["<<", ">>"]) {
+            let rhs = self.parse_additive()?;
+            let shift = u32::try_from(rhs)
+                .ok()
+                .filter(|shift| *shift < i64::BITS)
+                .or(self.ctx.lenient.then_some(0))
+                .ok_or_else(|| {
+                    Diagnostic::error(
+                        Code::InvalidImmediate,
+                        format!("Shift amount {} is out of range", rhs),
+                    )
+                })?;
+
+            lhs = match operator {
+                "<<" => lhs << shift,
+                _ => lhs >> shift,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn parse_additive(&mut self) -> Result<i64, Diagnostic> {
+        let mut lhs = self.parse_multiplicative()?;
+        while let Some(operator) = self.eat(&["+", "-"]) {
+            let rhs = self.parse_multiplicative()?;
+            lhs = match operator {
+                "+" => lhs.wrapping_add(rhs),
+                _ => lhs.wrapping_sub(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<i64, Diagnostic> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(operator) = self.eat(&["*", "/", "%"]) {
+            let rhs = self.parse_unary()?;
+
+            if operator != "*" && rhs == 0 && !self.ctx.lenient {
+                return Err(Diagnostic::error(
+                    Code::InvalidImmediate,
+                    "Division by zero in expression",
+                ));
+            }
+
+            lhs = match operator {
+                "*" => lhs.wrapping_mul(rhs),
+                _ if rhs == 0 => 0,
+                "/" => lhs.wrapping_div(rhs),
+                _ => lhs.wrapping_rem(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<i64, Diagnostic> {
+        match self.eat(&["-", "+", "~", "!"]) {
+            Some("-") => Ok(self.parse_unary()?.wrapping_neg()),
+            Some("~") => Ok(!self.parse_unary()?),
+            Some("!") => Ok((self.parse_unary()? == 0) as i64),
+            Some(_) => self.parse_unary(),
+            None => self.parse_primary(),
+        }
+    }
+
+    fn parse_parenthesized(&mut self) -> Result<i64, Diagnostic> {
+        let value = self.parse_logical_or()?;
+        self.eat(&[")"]).ok_or_else(|| {
+            Diagnostic::error(Code::InvalidImmediate, "Expected ')' in expression")
+        })?;
+        Ok(value)
+    }
+
+    fn parse_relocation(&mut self) -> Result<i64, Diagnostic> {
+        let Some(Token::Symbol(operator)) = self.tokens.get(self.position) else {
+            return Err(Diagnostic::error(
+                Code::InvalidImmediate,
+                "Expected relocation operator after '%'",
+            ));
+        };
+        let operator = *operator;
+        self.position += 1;
+
+        if self.eat(&["("]).is_none() {
+            return Err(Diagnostic::error(
+                Code::InvalidImmediate,
+                format!("Expected '(' after %{}", operator),
+            )
+            .with_token(format!("%{}", operator)));
+        }
+
+        let start = self.position;
+
+        match operator {
+            "hi" => Ok(hi(self.parse_parenthesized()?)),
+            "lo" => Ok(lo(self.parse_parenthesized()?)),
+            "pcrel_hi" => {
+                let value = self.parse_parenthesized()?;
+                Ok(hi(value.wrapping_sub(self.ctx.current_address as i64)))
+            }
+            "pcrel_lo" => {
+                // The operand names the auipc whose %pcrel_hi holds the actual target
+                let target = std::mem::replace(&mut self.target, true);
+                let auipc_address = self.parse_parenthesized();
+                self.target = target;
+
+                let label = match self.tokens.get(start) {
+                    Some(Token::Symbol(label)) => label,
+                    _ => "",
+                };
+                let offset = self.ctx.pcrel_target(auipc_address?, label)?;
+
+                Ok(lo(offset))
+            }
+            _ => Err(Diagnostic::error(
+                Code::InvalidImmediate,
+                format!("Unknown relocation operator %{}", operator),
+            )
+            .with_token(format!("%{}", operator))
+            .with_help("supported operators are %hi, %lo, %pcrel_hi and %pcrel_lo")),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<i64, Diagnostic> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+
+        match token {
+            Some(Token::Number(number)) => Ok(*number),
+            Some(Token::Symbol(symbol)) => self.ctx.resolve(symbol, self.target),
+            Some(Token::Operator("(")) => self.parse_parenthesized(),
+            Some(Token::Operator("%")) => self.parse_relocation(),
+            Some(token) => Err(Diagnostic::error(
+                Code::InvalidImmediate,
+                format!("Unexpected {} in expression", token),
+            )),
+            None => Err(Diagnostic::error(
+                Code::InvalidImmediate,
+                "Unexpected end of expression",
+            )),
+        }
+    }
+}
@@ -1,15 +1,21 @@
 use eeric_core::prelude::*;
 
+use crate::interpreter::{
+    decoder::expression::Context,
+    diagnostic::{Code, Diagnostic},
+};
+
 use super::integer;
 
-pub fn parse_csrr_format(csrr: &str) -> Result<format::Csrr, String> {
+pub fn parse_csrr_format(csrr: &str) -> Result<format::Csrr, Diagnostic> {
     let tokens: Vec<&str> = csrr.split(',').map(str::trim).collect();
 
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'rd, csr, rs1', got {} instead",
-            csrr
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, csr, rs1', got {} instead", csrr),
+        )
+        .with_token(csrr));
     }
 
     let rd = integer::parse_operand(tokens[0])?;
@@ -19,19 +25,20 @@ pub fn parse_csrr_format(csrr: &str) -> Result<format::Csrr, String> {
     Ok(format::Csrr { rd, csr, rs1 })
 }
 
-pub fn parse_csri_format(csri: &str) -> Result<format::Csri, String> {
+pub fn parse_csri_format(csri: &str, ctx: &Context) -> Result<format::Csri, Diagnostic> {
     let tokens: Vec<&str> = csri.split(',').map(str::trim).collect();
 
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'rd, csr, imm', got {} instead",
-            csri
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, csr, imm', got {} instead", csri),
+        )
+        .with_token(csri));
     }
 
     let rd = integer::parse_operand(tokens[0])?;
     let csr = parse_operand(tokens[1])?;
-    let uimm = integer::parse_immediate(tokens[2])?;
+    let uimm = integer::parse_immediate(tokens[2], ctx)?;
 
     Ok(format::Csri {
         rd,
@@ -40,14 +47,14 @@ pub fn parse_csri_format(csri: &str) -> Result<format::Csri, String> {
     })
 }
 
-fn parse_operand(op: &str) -> Result<usize, String> {
+fn parse_operand(op: &str) -> Result<usize, Diagnostic> {
     let operand = match op {
         "instret" => alias::INSTRET,
-        "instreth" => return Err("instreth is RV32 only CSR".to_owned()),
+        "instreth" => return Err(rv32_only(op)),
         "cycle" => alias::CYCLE,
-        "cycleh" => return Err("cycleh is RV32 only CSR".to_owned()),
+        "cycleh" => return Err(rv32_only(op)),
         "time" => alias::TIME,
-        "timeh" => return Err("timeh is RV32 only CSR".to_owned()),
+        "timeh" => return Err(rv32_only(op)),
         "marchid" => alias::MARCHID,
         "fcsr" => alias::FCSR,
         "fflags" => alias::FFLAGS,
@@ -61,21 +68,39 @@ fn parse_operand(op: &str) -> Result<usize, String> {
         "vxrm" => alias::VXRM,
         "vxsat" => alias::VXSAT,
         "vcsr" => alias::VCSR,
-        _ => return Err(format!("Incorrect or unsupported CSR operand: {}", op)),
+        _ => {
+            return Err(Diagnostic::error(
+                Code::InvalidRegister,
+                format!("Incorrect or unsupported CSR operand: {}", op),
+            )
+            .with_token(op))
+        }
     };
 
     Ok(operand)
 }
 
+fn rv32_only(op: &str) -> Diagnostic {
+    Diagnostic::error(Code::InvalidRegister, format!("{} is RV32 only CSR", op))
+        .with_token(op)
+        .with_help(format!(
+            "on RV64 the full counter is available in {}",
+            op.trim_end_matches('h')
+        ))
+}
+
 pub mod pseudo {
-    pub fn parse_op_csr_format(op_csr: &str) -> Result<(usize, usize), String> {
+    use crate::interpreter::diagnostic::{Code, Diagnostic};
+
+    pub fn parse_op_csr_format(op_csr: &str) -> Result<(usize, usize), Diagnostic> {
         let tokens: Vec<&str> = op_csr.split(',').map(str::trim).collect();
 
         if tokens.len() != 2 {
-            return Err(format!(
-                "Expected format: 'xreg, csr', got {} instead",
-                op_csr
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'xreg, csr', got {} instead", op_csr),
+            )
+            .with_token(op_csr));
         }
 
         let reg = super::integer::parse_operand(tokens[0])?;
@@ -84,14 +109,15 @@ pub mod pseudo {
         Ok((reg, csr))
     }
 
-    pub fn parse_csr_op_format(csr_op: &str) -> Result<(usize, usize), String> {
+    pub fn parse_csr_op_format(csr_op: &str) -> Result<(usize, usize), Diagnostic> {
         let tokens: Vec<&str> = csr_op.split(',').map(str::trim).collect();
 
         if tokens.len() != 2 {
-            return Err(format!(
-                "Expected format: 'csr, xreg', got {} instead",
-                csr_op
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'csr, xreg', got {} instead", csr_op),
+            )
+            .with_token(csr_op));
         }
 
         let csr = super::parse_operand(tokens[0])?;
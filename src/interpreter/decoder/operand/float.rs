@@ -1,15 +1,19 @@
-use std::collections::HashMap;
-
 use eeric_core::prelude::*;
 
-pub fn parse_r4_format(r4: &str) -> Result<format::R4, String> {
+use crate::interpreter::{
+    decoder::expression::Context,
+    diagnostic::{Code, Diagnostic},
+};
+
+pub fn parse_r4_format(r4: &str) -> Result<format::R4, Diagnostic> {
     let tokens: Vec<&str> = r4.split(',').map(str::trim).collect();
 
     if tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'rd, rs1, rs2, rs3', got {} instead",
-            r4
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, rs1, rs2, rs3', got {} instead", r4),
+        )
+        .with_token(r4));
     }
 
     let rd = parse_operand(tokens[0])?;
@@ -20,21 +24,19 @@ pub fn parse_r4_format(r4: &str) -> Result<format::R4, String> {
     Ok(format::R4 { rd, rs1, rs2, rs3 })
 }
 
-pub fn parse_load_format(
-    i: &str,
-    memory_labels: &HashMap<String, usize>,
-) -> Result<format::I, String> {
+pub fn parse_load_format(i: &str, ctx: &Context) -> Result<format::I, Diagnostic> {
     let tokens: Vec<&str> = i.split(',').map(str::trim).collect();
 
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'fd, imm(rs1)', got {} instead",
-            i
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'fd, imm(rs1)', got {} instead", i),
+        )
+        .with_token(i));
     }
 
     let rd = parse_operand(tokens[0])?;
-    let (imm, rs1) = super::integer::parse_offset_addr_operand(tokens[1], memory_labels)?;
+    let (imm, rs1) = super::integer::parse_offset_addr_operand(tokens[1], ctx)?;
 
     Ok(format::I {
         rd,
@@ -43,21 +45,19 @@ pub fn parse_load_format(
     })
 }
 
-pub fn parse_store_format(
-    s: &str,
-    memory_labels: &HashMap<String, usize>,
-) -> Result<format::S, String> {
+pub fn parse_store_format(s: &str, ctx: &Context) -> Result<format::S, Diagnostic> {
     let tokens: Vec<&str> = s.split(',').map(str::trim).collect();
 
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'rs2, imm(rs1)', got {} instead",
-            s
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rs2, imm(rs1)', got {} instead", s),
+        )
+        .with_token(s));
     }
 
     let rs2 = parse_operand(tokens[0])?;
-    let (imm, rs1) = super::integer::parse_offset_addr_operand(tokens[1], memory_labels)?;
+    let (imm, rs1) = super::integer::parse_offset_addr_operand(tokens[1], ctx)?;
 
     Ok(format::S {
         rs1,
@@ -66,14 +66,15 @@ pub fn parse_store_format(
     })
 }
 
-pub fn parse_r_format(r: &str) -> Result<format::R, String> {
+pub fn parse_r_format(r: &str) -> Result<format::R, Diagnostic> {
     let tokens: Vec<&str> = r.split(',').map(str::trim).collect();
 
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'fd, fs1, fs2', got {} instead",
-            r
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'fd, fs1, fs2', got {} instead", r),
+        )
+        .with_token(r));
     }
 
     let rd = parse_operand(tokens[0])?;
@@ -83,11 +84,15 @@ pub fn parse_r_format(r: &str) -> Result<format::R, String> {
     Ok(format::R { rd, rs1, rs2 })
 }
 
-pub fn parse_r_single_reg_format(r: &str) -> Result<format::R, String> {
+pub fn parse_r_single_reg_format(r: &str) -> Result<format::R, Diagnostic> {
     let tokens: Vec<&str> = r.split(',').map(str::trim).collect();
 
     if tokens.len() != 2 {
-        return Err(format!("Expected format: 'fd, fs1', got {} instead", r));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'fd, fs1', got {} instead", r),
+        )
+        .with_token(r));
     }
 
     let rd = parse_operand(tokens[0])?;
@@ -96,14 +101,15 @@ pub fn parse_r_single_reg_format(r: &str) -> Result<format::R, String> {
     Ok(format::R { rd, rs1, rs2: 0 })
 }
 
-pub fn parse_r_to_x_format(r: &str) -> Result<format::R, String> {
+pub fn parse_r_to_x_format(r: &str) -> Result<format::R, Diagnostic> {
     let tokens: Vec<&str> = r.split(',').map(str::trim).collect();
 
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'rd, fs1, fs2', got {} instead",
-            r
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, fs1, fs2', got {} instead", r),
+        )
+        .with_token(r));
     }
 
     let rd = super::integer::parse_operand(tokens[0])?;
@@ -113,11 +119,15 @@ pub fn parse_r_to_x_format(r: &str) -> Result<format::R, String> {
     Ok(format::R { rd, rs1, rs2 })
 }
 
-pub fn parse_r_single_reg_to_x_format(r: &str) -> Result<format::R, String> {
+pub fn parse_r_single_reg_to_x_format(r: &str) -> Result<format::R, Diagnostic> {
     let tokens: Vec<&str> = r.split(',').map(str::trim).collect();
 
     if tokens.len() != 2 {
-        return Err(format!("Expected format: 'rd, fs1', got {} instead", r));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, fs1', got {} instead", r),
+        )
+        .with_token(r));
     }
 
     let rd = super::integer::parse_operand(tokens[0])?;
@@ -126,11 +136,15 @@ pub fn parse_r_single_reg_to_x_format(r: &str) -> Result<format::R, String> {
     Ok(format::R { rd, rs1, rs2: 0 })
 }
 
-pub fn parse_r_single_reg_to_f_format(r: &str) -> Result<format::R, String> {
+pub fn parse_r_single_reg_to_f_format(r: &str) -> Result<format::R, Diagnostic> {
     let tokens: Vec<&str> = r.split(',').map(str::trim).collect();
 
     if tokens.len() != 2 {
-        return Err(format!("Expected format: 'fd, rs1', got {} instead", r));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'fd, rs1', got {} instead", r),
+        )
+        .with_token(r));
     }
 
     let rd = parse_operand(tokens[0])?;
@@ -139,7 +153,7 @@ pub fn parse_r_single_reg_to_f_format(r: &str) -> Result<format::R, String> {
     Ok(format::R { rd, rs1, rs2: 0 })
 }
 
-pub fn parse_operand(op_str: &str) -> Result<usize, String> {
+pub fn parse_operand(op_str: &str) -> Result<usize, Diagnostic> {
     let op = match op_str {
         "f0" | "ft0" => 0,
         "f1" | "ft1" => 1,
@@ -173,21 +187,31 @@ pub fn parse_operand(op_str: &str) -> Result<usize, String> {
         "f29" | "ft9" => 29,
         "f30" | "ft10" => 30,
         "f31" | "ft11" => 31,
-        _ => return Err(format!("Incorrect float operand: {}", op_str)),
+        _ => {
+            return Err(Diagnostic::error(
+                Code::InvalidRegister,
+                format!("Incorrect float operand: {}", op_str),
+            )
+            .with_token(op_str)
+            .with_help("float registers are f0-f31 or their ABI names, e.g. ft0, fs0, fa0"))
+        }
     };
 
     Ok(op)
 }
 
 pub mod pseudo {
-    pub fn parse_op_op_format(op_op: &str) -> Result<(usize, usize), String> {
+    use crate::interpreter::diagnostic::{Code, Diagnostic};
+
+    pub fn parse_op_op_format(op_op: &str) -> Result<(usize, usize), Diagnostic> {
         let tokens: Vec<&str> = op_op.split(',').map(str::trim).collect();
 
         if tokens.len() != 2 {
-            return Err(format!(
-                "Expected format: 'freg1, freg2', got {} instead",
-                op_op
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'freg1, freg2', got {} instead", op_op),
+            )
+            .with_token(op_op));
         }
 
         let reg1 = super::parse_operand(tokens[0])?;
@@ -1,15 +1,19 @@
-use std::collections::HashMap;
-
 use eeric_core::prelude::*;
 
-pub fn parse_r_format(r: &str) -> Result<format::R, String> {
+use crate::interpreter::{
+    decoder::expression::Context,
+    diagnostic::{Code, Diagnostic},
+};
+
+pub fn parse_r_format(r: &str) -> Result<format::R, Diagnostic> {
     let tokens: Vec<&str> = r.split(',').map(str::trim).collect();
 
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'rd, rs1, rs2', got {} instead",
-            r
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, rs1, rs2', got {} instead", r),
+        )
+        .with_token(r));
     }
 
     let rd = parse_operand(tokens[0])?;
@@ -19,19 +23,20 @@ pub fn parse_r_format(r: &str) -> Result<format::R, String> {
     Ok(format::R { rd, rs1, rs2 })
 }
 
-pub fn parse_i_format(i: &str) -> Result<format::I, String> {
+pub fn parse_i_format(i: &str, ctx: &Context) -> Result<format::I, Diagnostic> {
     let tokens: Vec<&str> = i.split(',').map(str::trim).collect();
 
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'rd, rs1, imm', got {} instead",
-            i
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, rs1, imm', got {} instead", i),
+        )
+        .with_token(i));
     }
 
     let rd = parse_operand(tokens[0])?;
     let rs1 = parse_operand(tokens[1])?;
-    let imm = parse_immediate(tokens[2])?;
+    let imm = parse_immediate(tokens[2], ctx)?;
 
     Ok(format::I {
         rd,
@@ -40,21 +45,19 @@ pub fn parse_i_format(i: &str) -> Result<format::I, String> {
     })
 }
 
-pub fn parse_load_format(
-    i: &str,
-    memory_labels: &HashMap<String, usize>,
-) -> Result<format::I, String> {
+pub fn parse_load_format(i: &str, ctx: &Context) -> Result<format::I, Diagnostic> {
     let tokens: Vec<&str> = i.split(',').map(str::trim).collect();
 
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'rd, imm(rs1)', got {} instead",
-            i
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, imm(rs1)', got {} instead", i),
+        )
+        .with_token(i));
     }
 
     let rd = parse_operand(tokens[0])?;
-    let (imm, rs1) = parse_offset_addr_operand(tokens[1], memory_labels)?;
+    let (imm, rs1) = parse_offset_addr_operand(tokens[1], ctx)?;
 
     Ok(format::I {
         rd,
@@ -63,21 +66,19 @@ pub fn parse_load_format(
     })
 }
 
-pub fn parse_s_format(
-    s: &str,
-    memory_labels: &HashMap<String, usize>,
-) -> Result<format::S, String> {
+pub fn parse_s_format(s: &str, ctx: &Context) -> Result<format::S, Diagnostic> {
     let tokens: Vec<&str> = s.split(',').map(str::trim).collect();
 
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'rs2, imm(rs1)', got {} instead",
-            s
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rs2, imm(rs1)', got {} instead", s),
+        )
+        .with_token(s));
     }
 
     let rs2 = parse_operand(tokens[0])?;
-    let (imm, rs1) = parse_offset_addr_operand(tokens[1], memory_labels)?;
+    let (imm, rs1) = parse_offset_addr_operand(tokens[1], ctx)?;
 
     Ok(format::S {
         rs1,
@@ -86,23 +87,20 @@ pub fn parse_s_format(
     })
 }
 
-pub fn parse_branch_format(
-    s: &str,
-    labels: &HashMap<String, usize>,
-    current_line: usize,
-) -> Result<format::S, String> {
+pub fn parse_branch_format(s: &str, ctx: &Context) -> Result<format::S, Diagnostic> {
     let tokens: Vec<&str> = s.split(',').map(str::trim).collect();
 
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'rs1, rs2, label', got {} instead",
-            s
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rs1, rs2, label', got {} instead", s),
+        )
+        .with_token(s));
     }
 
     let rs1 = parse_operand(tokens[0])?;
     let rs2 = parse_operand(tokens[1])?;
-    let label_addr = parse_instruction_label(tokens[2], labels, current_line)?;
+    let label_addr = parse_instruction_label(tokens[2], ctx)?;
 
     Ok(format::S {
         rs1,
@@ -111,51 +109,62 @@ pub fn parse_branch_format(
     })
 }
 
-pub fn parse_u_format(u: &str) -> Result<format::U, String> {
+pub fn parse_u_format(u: &str, ctx: &Context) -> Result<format::U, Diagnostic> {
     let tokens: Vec<&str> = u.split(',').map(str::trim).collect();
 
     if tokens.len() != 2 {
-        return Err(format!("Expected format: 'rd, imm', got {} instead", u));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, imm', got {} instead", u),
+        )
+        .with_token(u));
     }
 
     let rd = parse_operand(tokens[0])?;
-    let imm = parse_immediate(tokens[1])?;
+    let imm = parse_immediate(tokens[1], ctx)?;
 
     Ok(format::U { rd, imm20: imm })
 }
 
-pub fn parse_offset_addr_operand(
-    op: &str,
-    memory_labels: &HashMap<String, usize>,
-) -> Result<(i32, usize), String> {
-    let Some(operand_addr) = op.find('(') else {
-        return Err(format!(
-            "Expected format: 'imm(rs1)' for the address with offset, got {} instead",
-            op
-        ));
+pub fn parse_offset_addr_operand(op: &str, ctx: &Context) -> Result<(i32, usize), Diagnostic> {
+    // The offset itself may be a parenthesized expression, so the register is the last group
+    let Some(operand_addr) = op.rfind('(') else {
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'imm(rs1)' for the address with offset, got {} instead",
+                op
+            ),
+        )
+        .with_token(op));
     };
 
     let (imm, reg) = op.split_at(operand_addr);
 
-    let imm = parse_immediate_or_memory_label(imm, memory_labels)?;
+    let imm = if imm.trim().is_empty() {
+        0
+    } else {
+        parse_immediate(imm, ctx)?
+    };
     let reg = parse_addr_operand(reg)?;
 
     Ok((imm, reg))
 }
 
-pub fn parse_addr_operand(op: &str) -> Result<usize, String> {
+pub fn parse_addr_operand(op: &str) -> Result<usize, Diagnostic> {
     if op.starts_with('(') && op.ends_with(')') {
         let inner_op = &op[1..op.len() - 1];
         parse_operand(inner_op)
     } else {
-        Err(format!(
-            "Address operand {} is not wrapped in parentheses",
-            op
-        ))
+        Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Address operand {} is not wrapped in parentheses", op),
+        )
+        .with_token(op))
     }
 }
 
-pub fn parse_operand(op: &str) -> Result<usize, String> {
+pub fn parse_operand(op: &str) -> Result<usize, Diagnostic> {
     let operand = match op {
         "x0" | "zero" => 0,
         "x1" | "ra" => 1,
@@ -189,97 +198,105 @@ pub fn parse_operand(op: &str) -> Result<usize, String> {
         "x29" | "t4" => 29,
         "x30" | "t5" => 30,
         "x31" | "t6" => 31,
-        _ => return Err(format!("Incorrect integer operand: {}", op)),
+        _ => {
+            return Err(Diagnostic::error(
+                Code::InvalidRegister,
+                format!("Incorrect integer operand: {}", op),
+            )
+            .with_token(op)
+            .with_help("integer registers are x0-x31 or their ABI names, e.g. zero, ra, sp, a0"))
+        }
     };
 
     Ok(operand)
 }
 
-pub fn parse_immediate(imm: &str) -> Result<i32, String> {
-    if imm.starts_with("0x") || imm.starts_with("0X") {
-        i32::from_str_radix(&imm[2..], 16)
-            .map_err(|e| format!("Error parsing hexadecimal immediate: {}", e))
-    } else if imm.starts_with("0o") || imm.starts_with("0O") {
-        i32::from_str_radix(&imm[2..], 8)
-            .map_err(|e| format!("Error parsing octal immediate: {}", e))
-    } else if imm.starts_with("0b") || imm.starts_with("0B") {
-        i32::from_str_radix(&imm[2..], 2)
-            .map_err(|e| format!("Error parsing binary immediate: {}", e))
-    } else {
-        imm.parse::<i32>()
-            .map_err(|e| format!("Error parsing immediate: {}", e))
-    }
-}
-
-pub fn parse_immediate_or_memory_label(
-    imm_or_mem: &str,
-    memory_labels: &HashMap<String, usize>,
-) -> Result<i32, String> {
-    if imm_or_mem.is_empty() {
-        return Ok(0);
-    }
+pub fn parse_immediate(imm: &str, ctx: &Context) -> Result<i32, Diagnostic> {
+    let value = ctx.evaluate(imm)?;
 
-    match parse_immediate(imm_or_mem) {
-        Ok(imm) => Ok(imm),
-        Err(imm_err) => parse_memory_label(imm_or_mem, memory_labels)
-            .map_err(|addr_err| format!("{} or {}", imm_err, addr_err)),
-    }
+    i32::try_from(value).map_err(|_| {
+        Diagnostic::error(
+            Code::InvalidImmediate,
+            format!("Immediate {} does not fit in 32 bits", value),
+        )
+        .with_token(imm.trim())
+    })
 }
 
-pub fn parse_memory_label(label: &str, map: &HashMap<String, usize>) -> Result<i32, String> {
-    map.get(label)
-        .cloned()
-        .map(|addr| addr as i32)
-        .ok_or(format!("Did not find memory label {}", label))
+pub fn parse_memory_label(label: &str, ctx: &Context) -> Result<i32, Diagnostic> {
+    parse_immediate(label, ctx)
 }
 
-pub fn parse_instruction_label(
-    label: &str,
-    map: &HashMap<String, usize>,
-    current_line: usize,
-) -> Result<i32, String> {
-    map.get(label)
-        .cloned()
-        .map(|addr| addr.wrapping_sub(current_line) as i32)
-        .ok_or(format!("Did not find instruction label {}", label))
+pub fn parse_instruction_label(label: &str, ctx: &Context) -> Result<i32, Diagnostic> {
+    ctx.evaluate_target(label)
+        .map(|addr| (addr as usize).wrapping_sub(ctx.current_address) as i32)
 }
 
 pub mod pseudo {
-    use std::collections::HashMap;
+    use crate::interpreter::{
+        decoder::expression::Context,
+        diagnostic::{Code, Diagnostic},
+    };
 
-    pub fn parse_imm_format(imm: &str) -> Result<i32, String> {
+    pub fn parse_imm_format(imm: &str, ctx: &Context) -> Result<i32, Diagnostic> {
         let tokens: Vec<&str> = imm.split(',').map(str::trim).collect();
 
         if tokens.len() != 1 {
-            return Err(format!("Expected format: 'imm', got {} instead", imm));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'imm', got {} instead", imm),
+            )
+            .with_token(imm));
         }
 
-        let imm = super::parse_immediate(tokens[0])?;
+        let imm = super::parse_immediate(tokens[0], ctx)?;
 
         Ok(imm)
     }
 
-    pub fn parse_op_imm_format(op_imm: &str) -> Result<(usize, i32), String> {
+    pub fn parse_op_imm_format(op_imm: &str, ctx: &Context) -> Result<(usize, i32), Diagnostic> {
+        let tokens: Vec<&str> = op_imm.split(',').map(str::trim).collect();
+
+        if tokens.len() != 2 {
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'xreg, imm', got {} instead", op_imm),
+            )
+            .with_token(op_imm));
+        }
+
+        let reg = super::parse_operand(tokens[0])?;
+        let imm = super::parse_immediate(tokens[1], ctx)?;
+
+        Ok((reg, imm))
+    }
+
+    pub fn parse_op_imm64_format(op_imm: &str, ctx: &Context) -> Result<(usize, i64), Diagnostic> {
         let tokens: Vec<&str> = op_imm.split(',').map(str::trim).collect();
 
         if tokens.len() != 2 {
-            return Err(format!(
-                "Expected format: 'xreg, imm', got {} instead",
-                op_imm
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'xreg, imm', got {} instead", op_imm),
+            )
+            .with_token(op_imm));
         }
 
         let reg = super::parse_operand(tokens[0])?;
-        let imm = super::parse_immediate(tokens[1])?;
+        let imm = ctx.evaluate(tokens[1])?;
 
         Ok((reg, imm))
     }
 
-    pub fn parse_op_format(op: &str) -> Result<usize, String> {
+    pub fn parse_op_format(op: &str) -> Result<usize, Diagnostic> {
         let tokens: Vec<&str> = op.split(',').map(str::trim).collect();
 
         if tokens.len() != 1 {
-            return Err(format!("Expected format: 'xreg', got {} instead", op));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'xreg', got {} instead", op),
+            )
+            .with_token(op));
         }
 
         let xreg = super::parse_operand(tokens[0])?;
@@ -287,14 +304,15 @@ pub mod pseudo {
         Ok(xreg)
     }
 
-    pub fn parse_op_op_format(op_op: &str) -> Result<(usize, usize), String> {
+    pub fn parse_op_op_format(op_op: &str) -> Result<(usize, usize), Diagnostic> {
         let tokens: Vec<&str> = op_op.split(',').map(str::trim).collect();
 
         if tokens.len() != 2 {
-            return Err(format!(
-                "Expected format: 'xreg1, xreg2', got {} instead",
-                op_op
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'xreg1, xreg2', got {} instead", op_op),
+            )
+            .with_token(op_op));
         }
 
         let reg1 = super::parse_operand(tokens[0])?;
@@ -303,78 +321,85 @@ pub mod pseudo {
         Ok((reg1, reg2))
     }
 
-    pub fn parse_label_format(
-        label: &str,
-        labels: &HashMap<String, usize>,
-        current_line: usize,
-    ) -> Result<i32, String> {
+    pub fn parse_label_format(label: &str, ctx: &Context) -> Result<i32, Diagnostic> {
         let tokens: Vec<&str> = label.split(',').map(str::trim).collect();
 
         if tokens.len() != 1 {
-            return Err(format!("Expected format: 'label', got {} instead", label));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'label', got {} instead", label),
+            )
+            .with_token(label));
         }
 
-        let diff = super::parse_instruction_label(tokens[0], labels, current_line)?;
+        let diff = super::parse_instruction_label(tokens[0], ctx)?;
 
         Ok(diff)
     }
 
     pub fn parse_op_label_format(
         op_label: &str,
-        labels: &HashMap<String, usize>,
-        current_line: usize,
-    ) -> Result<(usize, i32), String> {
+        ctx: &Context,
+    ) -> Result<(usize, i32), Diagnostic> {
         let tokens: Vec<&str> = op_label.split(',').map(str::trim).collect();
 
         if tokens.len() != 2 {
-            return Err(format!(
-                "Expected format: 'xreg, label', got {} instead",
-                op_label
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'xreg, label', got {} instead", op_label),
+            )
+            .with_token(op_label));
         }
 
         let reg = super::parse_operand(tokens[0])?;
-        let diff = super::parse_instruction_label(tokens[1], labels, current_line)?;
+        let diff = super::parse_instruction_label(tokens[1], ctx)?;
 
         Ok((reg, diff))
     }
 
     pub fn parse_op_memory_label_format(
         op_label: &str,
-        memory_labels: &HashMap<String, usize>,
-    ) -> Result<(usize, i32), String> {
+        ctx: &Context,
+    ) -> Result<(usize, i32), Diagnostic> {
         let tokens: Vec<&str> = op_label.split(',').map(str::trim).collect();
 
         if tokens.len() != 2 {
-            return Err(format!(
-                "Expected format: 'xreg, memory_label', got {} instead",
-                op_label
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: 'xreg, memory_label', got {} instead",
+                    op_label
+                ),
+            )
+            .with_token(op_label));
         }
 
         let reg = super::parse_operand(tokens[0])?;
-        let diff = super::parse_memory_label(tokens[1], memory_labels)?;
+        let diff = super::parse_memory_label(tokens[1], ctx)?;
 
         Ok((reg, diff))
     }
 
     pub fn parse_op_op_label_format(
         op_op_label: &str,
-        labels: &HashMap<String, usize>,
-        current_line: usize,
-    ) -> Result<(usize, usize, i32), String> {
+        ctx: &Context,
+    ) -> Result<(usize, usize, i32), Diagnostic> {
         let tokens: Vec<&str> = op_op_label.split(',').map(str::trim).collect();
 
         if tokens.len() != 3 {
-            return Err(format!(
-                "Expected format: 'xreg1, xreg2, label', got {} instead",
-                op_op_label
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: 'xreg1, xreg2, label', got {} instead",
+                    op_op_label
+                ),
+            )
+            .with_token(op_op_label));
         }
 
         let xreg1 = super::parse_operand(tokens[0])?;
         let xreg2 = super::parse_operand(tokens[1])?;
-        let diff = super::parse_instruction_label(tokens[2], labels, current_line)?;
+        let diff = super::parse_instruction_label(tokens[2], ctx)?;
 
         Ok((xreg1, xreg2, diff))
     }
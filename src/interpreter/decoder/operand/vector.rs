@@ -1,10 +1,15 @@
 use eeric_core::prelude::*;
 
+use crate::interpreter::{
+    decoder::expression::Context,
+    diagnostic::{Code, Diagnostic},
+};
+
 use super::{float, integer};
 
 fn construct_vtype(
     (sew, lmul, tail, mask): (BaseSew, Lmul, MaskBehavior, MaskBehavior),
-) -> Result<u32, String> {
+) -> Result<u32, Diagnostic> {
     let vsew = match sew {
         BaseSew::E8 => 0b000,
         BaseSew::E16 => 0b001,
@@ -36,13 +41,22 @@ fn construct_vtype(
     Ok(result)
 }
 
-fn parse_vtype(vtype: &[&str]) -> Result<(BaseSew, Lmul, MaskBehavior, MaskBehavior), String> {
+fn unknown_vtype_value(field: &str, value: &str) -> Diagnostic {
+    Diagnostic::error(
+        Code::InvalidVtype,
+        format!("Unknown {} value: {}", field, value),
+    )
+    .with_token(value)
+    .with_help("vtype is written as e.g. 'e32, m1, ta, ma'")
+}
+
+fn parse_vtype(vtype: &[&str]) -> Result<(BaseSew, Lmul, MaskBehavior, MaskBehavior), Diagnostic> {
     let sew = match vtype[0] {
         "e8" => BaseSew::E8,
         "e16" => BaseSew::E16,
         "e32" => BaseSew::E32,
         "e64" => BaseSew::E64,
-        other => return Err(format!("Unknown BaseSew value: {}", other)),
+        other => return Err(unknown_vtype_value("BaseSew", other)),
     };
 
     let lmul = match vtype[1] {
@@ -53,31 +67,35 @@ fn parse_vtype(vtype: &[&str]) -> Result<(BaseSew, Lmul, MaskBehavior, MaskBehav
         "m2" => Lmul::M2,
         "m4" => Lmul::M4,
         "m8" => Lmul::M8,
-        other => return Err(format!("Unknown Lmul value: {}", other)),
+        other => return Err(unknown_vtype_value("Lmul", other)),
     };
 
     let tail = match vtype[2] {
         "ta" => MaskBehavior::Agnostic,
         "tu" => MaskBehavior::Undisturbed,
-        other => return Err(format!("Unknown tail value: {}", other)),
+        other => return Err(unknown_vtype_value("tail", other)),
     };
 
     let mask = match vtype[3] {
         "ma" => MaskBehavior::Agnostic,
         "mu" => MaskBehavior::Undisturbed,
-        other => return Err(format!("Unknown mask value: {}", other)),
+        other => return Err(unknown_vtype_value("mask", other)),
     };
 
     Ok((sew, lmul, tail, mask))
 }
 
-pub fn parse_vsetvli_format(vsetvli: &str) -> Result<format::Vsetvli, String> {
+pub fn parse_vsetvli_format(vsetvli: &str) -> Result<format::Vsetvli, Diagnostic> {
     let tokens: Vec<&str> = vsetvli.split(',').map(str::trim).collect();
     if tokens.len() != 6 {
-        return Err(format!(
-            "Expected format: 'rd, rs1, BaseSew, Lmul, ta/tu, ma/mu', got {} instead",
-            vsetvli
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'rd, rs1, BaseSew, Lmul, ta/tu, ma/mu', got {} instead",
+                vsetvli
+            ),
+        )
+        .with_token(vsetvli));
     }
 
     let rd = integer::parse_operand(tokens[0])?;
@@ -91,17 +109,24 @@ pub fn parse_vsetvli_format(vsetvli: &str) -> Result<format::Vsetvli, String> {
     })
 }
 
-pub fn parse_vsetivli_format(vsetivli: &str) -> Result<format::Vsetivli, String> {
+pub fn parse_vsetivli_format(
+    vsetivli: &str,
+    ctx: &Context,
+) -> Result<format::Vsetivli, Diagnostic> {
     let tokens: Vec<&str> = vsetivli.split(',').map(str::trim).collect();
     if tokens.len() != 6 {
-        return Err(format!(
-            "Expected format: 'rd, uimm5, BaseSew, Lmul, ta/tu, ma/mu', got {} instead",
-            vsetivli
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'rd, uimm5, BaseSew, Lmul, ta/tu, ma/mu', got {} instead",
+                vsetivli
+            ),
+        )
+        .with_token(vsetivli));
     }
 
     let rd = integer::parse_operand(tokens[0])?;
-    let uimm = integer::parse_immediate(tokens[1])? as u32;
+    let uimm = integer::parse_immediate(tokens[1], ctx)? as u32;
 
     let vtype = parse_vtype(&tokens[2..])?;
 
@@ -112,13 +137,14 @@ pub fn parse_vsetivli_format(vsetivli: &str) -> Result<format::Vsetivli, String>
     })
 }
 
-pub fn parse_vsetvl_format(vsetvl: &str) -> Result<format::Vsetvl, String> {
+pub fn parse_vsetvl_format(vsetvl: &str) -> Result<format::Vsetvl, Diagnostic> {
     let tokens: Vec<&str> = vsetvl.split(',').map(str::trim).collect();
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'rd, rs1, rs2', got {} instead",
-            vsetvl
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, rs1, rs2', got {} instead", vsetvl),
+        )
+        .with_token(vsetvl));
     }
 
     let rd = integer::parse_operand(tokens[0])?;
@@ -128,13 +154,14 @@ pub fn parse_vsetvl_format(vsetvl: &str) -> Result<format::Vsetvl, String> {
     Ok(format::Vsetvl { rd, rs1, rs2 })
 }
 
-pub fn parse_vl_format(vl: &str) -> Result<format::Vl, String> {
+pub fn parse_vl_format(vl: &str) -> Result<format::Vl, Diagnostic> {
     let tokens: Vec<&str> = vl.split(',').map(str::trim).collect();
     if tokens.len() != 2 && tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'vd, (rs1), [vm]', got {} instead",
-            vl
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, (rs1), [vm]', got {} instead", vl),
+        )
+        .with_token(vl));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -149,10 +176,14 @@ pub fn parse_vl_format(vl: &str) -> Result<format::Vl, String> {
     Ok(format::Vl { vd, rs1, vm })
 }
 
-pub fn parse_vlm_format(vlm: &str) -> Result<format::Vl, String> {
+pub fn parse_vlm_format(vlm: &str) -> Result<format::Vl, Diagnostic> {
     let tokens: Vec<&str> = vlm.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!("Expected format: 'vd, (rs1)', got {} instead", vlm));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, (rs1)', got {} instead", vlm),
+        )
+        .with_token(vlm));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -161,13 +192,17 @@ pub fn parse_vlm_format(vlm: &str) -> Result<format::Vl, String> {
     Ok(format::Vl { vd, rs1, vm: false })
 }
 
-pub fn parse_vls_format(vls: &str) -> Result<format::Vls, String> {
+pub fn parse_vls_format(vls: &str) -> Result<format::Vls, Diagnostic> {
     let tokens: Vec<&str> = vls.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, (rs1), rs2, [vm]', got {} instead",
-            vls
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, (rs1), rs2, [vm]', got {} instead",
+                vls
+            ),
+        )
+        .with_token(vls));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -183,13 +218,17 @@ pub fn parse_vls_format(vls: &str) -> Result<format::Vls, String> {
     Ok(format::Vls { vd, rs1, rs2, vm })
 }
 
-pub fn parse_vlx_format(vlx: &str) -> Result<format::Vlx, String> {
+pub fn parse_vlx_format(vlx: &str) -> Result<format::Vlx, Diagnostic> {
     let tokens: Vec<&str> = vlx.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, (rs1), vs2, [vm]', got {} instead",
-            vlx
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, (rs1), vs2, [vm]', got {} instead",
+                vlx
+            ),
+        )
+        .with_token(vlx));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -205,10 +244,14 @@ pub fn parse_vlx_format(vlx: &str) -> Result<format::Vlx, String> {
     Ok(format::Vlx { vd, rs1, vs2, vm })
 }
 
-pub fn parse_vlr_format(vlr: &str) -> Result<format::Vlr, String> {
+pub fn parse_vlr_format(vlr: &str) -> Result<format::Vlr, Diagnostic> {
     let tokens: Vec<&str> = vlr.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!("Expected format: 'vd, (rs1)', got {} instead", vlr));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, (rs1)', got {} instead", vlr),
+        )
+        .with_token(vlr));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -217,13 +260,14 @@ pub fn parse_vlr_format(vlr: &str) -> Result<format::Vlr, String> {
     Ok(format::Vlr { vd, rs1 })
 }
 
-pub fn parse_vs_format(vs: &str) -> Result<format::Vs, String> {
+pub fn parse_vs_format(vs: &str) -> Result<format::Vs, Diagnostic> {
     let tokens: Vec<&str> = vs.split(',').map(str::trim).collect();
     if tokens.len() != 2 && tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'vs3, (rs1), [vm]', got {} instead",
-            vs
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vs3, (rs1), [vm]', got {} instead", vs),
+        )
+        .with_token(vs));
     }
 
     let vs3 = parse_operand(tokens[0])?.as_register()?;
@@ -238,13 +282,14 @@ pub fn parse_vs_format(vs: &str) -> Result<format::Vs, String> {
     Ok(format::Vs { vs3, rs1, vm })
 }
 
-pub fn parse_vsm_format(vsm: &str) -> Result<format::Vs, String> {
+pub fn parse_vsm_format(vsm: &str) -> Result<format::Vs, Diagnostic> {
     let tokens: Vec<&str> = vsm.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'vs3, (rs1)', got {} instead",
-            vsm
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vs3, (rs1)', got {} instead", vsm),
+        )
+        .with_token(vsm));
     }
 
     let vs3 = parse_operand(tokens[0])?.as_register()?;
@@ -257,13 +302,17 @@ pub fn parse_vsm_format(vsm: &str) -> Result<format::Vs, String> {
     })
 }
 
-pub fn parse_vss_format(vss: &str) -> Result<format::Vss, String> {
+pub fn parse_vss_format(vss: &str) -> Result<format::Vss, Diagnostic> {
     let tokens: Vec<&str> = vss.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vs3, (rs1), rs2, [vm]', got {} instead",
-            vss
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vs3, (rs1), rs2, [vm]', got {} instead",
+                vss
+            ),
+        )
+        .with_token(vss));
     }
 
     let vs3 = parse_operand(tokens[0])?.as_register()?;
@@ -279,13 +328,17 @@ pub fn parse_vss_format(vss: &str) -> Result<format::Vss, String> {
     Ok(format::Vss { vs3, rs1, rs2, vm })
 }
 
-pub fn parse_vsx_format(vsx: &str) -> Result<format::Vsx, String> {
+pub fn parse_vsx_format(vsx: &str) -> Result<format::Vsx, Diagnostic> {
     let tokens: Vec<&str> = vsx.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vs3, (rs1), vs2, [vm]', got {} instead",
-            vsx
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vs3, (rs1), vs2, [vm]', got {} instead",
+                vsx
+            ),
+        )
+        .with_token(vsx));
     }
 
     let vs3 = parse_operand(tokens[0])?.as_register()?;
@@ -301,13 +354,14 @@ pub fn parse_vsx_format(vsx: &str) -> Result<format::Vsx, String> {
     Ok(format::Vsx { vs3, rs1, vs2, vm })
 }
 
-pub fn parse_vsr_format(vsr: &str) -> Result<format::Vsr, String> {
+pub fn parse_vsr_format(vsr: &str) -> Result<format::Vsr, Diagnostic> {
     let tokens: Vec<&str> = vsr.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'vs3, (rs1)', got {} instead",
-            vsr
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vs3, (rs1)', got {} instead", vsr),
+        )
+        .with_token(vsr));
     }
 
     let vs3 = parse_operand(tokens[0])?.as_register()?;
@@ -316,13 +370,17 @@ pub fn parse_vsr_format(vsr: &str) -> Result<format::Vsr, String> {
     Ok(format::Vsr { vs3, rs1 })
 }
 
-pub fn parse_opivv_format(opivv: &str) -> Result<format::Opivv, String> {
+pub fn parse_opivv_format(opivv: &str) -> Result<format::Opivv, Diagnostic> {
     let tokens: Vec<&str> = opivv.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, vs1, [vm]', got {} instead",
-            opivv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, vs2, vs1, [vm]', got {} instead",
+                opivv
+            ),
+        )
+        .with_token(opivv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -338,20 +396,24 @@ pub fn parse_opivv_format(opivv: &str) -> Result<format::Opivv, String> {
     Ok(format::Opivv { vd, vs2, vs1, vm })
 }
 
-pub fn parse_opivv_v0_format(opivv: &str) -> Result<format::Opivv, String> {
+pub fn parse_opivv_v0_format(opivv: &str) -> Result<format::Opivv, Diagnostic> {
     let tokens: Vec<&str> = opivv.split(',').map(str::trim).collect();
     if tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, vs1, v0', got {} instead",
-            opivv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, vs1, v0', got {} instead", opivv),
+        )
+        .with_token(opivv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
     let vs2 = parse_operand(tokens[1])?.as_register()?;
     let vs1 = parse_operand(tokens[2])?.as_register()?;
     if parse_operand(tokens[3])?.as_register()? != 0 {
-        return Err("Expected last operand to be v0".to_owned());
+        return Err(
+            Diagnostic::error(Code::InvalidRegister, "Expected last operand to be v0")
+                .with_token(tokens[3]),
+        );
     }
 
     Ok(format::Opivv {
@@ -362,13 +424,14 @@ pub fn parse_opivv_v0_format(opivv: &str) -> Result<format::Opivv, String> {
     })
 }
 
-pub fn parse_opivv_maskless_format(opivv: &str) -> Result<format::Opivv, String> {
+pub fn parse_opivv_maskless_format(opivv: &str) -> Result<format::Opivv, Diagnostic> {
     let tokens: Vec<&str> = opivv.split(',').map(str::trim).collect();
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, vs1', got {} instead",
-            opivv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, vs1', got {} instead", opivv),
+        )
+        .with_token(opivv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -379,17 +442,18 @@ pub fn parse_opivv_maskless_format(opivv: &str) -> Result<format::Opivv, String>
         vd,
         vs2,
         vs1,
-        vm: true,
+        vm: false,
     })
 }
 
-pub fn parse_opivv_vmv_format(opivv_vmv: &str) -> Result<format::Opivv, String> {
+pub fn parse_opivv_vmv_format(opivv_vmv: &str) -> Result<format::Opivv, Diagnostic> {
     let tokens: Vec<&str> = opivv_vmv.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'vd, vs1', got {} instead",
-            opivv_vmv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs1', got {} instead", opivv_vmv),
+        )
+        .with_token(opivv_vmv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -403,13 +467,17 @@ pub fn parse_opivv_vmv_format(opivv_vmv: &str) -> Result<format::Opivv, String>
     })
 }
 
-pub fn parse_opivx_format(opivx: &str) -> Result<format::Opivx, String> {
+pub fn parse_opivx_format(opivx: &str) -> Result<format::Opivx, Diagnostic> {
     let tokens: Vec<&str> = opivx.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, rs1, [vm]', got {} instead",
-            opivx
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, vs2, rs1, [vm]', got {} instead",
+                opivx
+            ),
+        )
+        .with_token(opivx));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -425,37 +493,42 @@ pub fn parse_opivx_format(opivx: &str) -> Result<format::Opivx, String> {
     Ok(format::Opivx { vd, vs2, rs1, vm })
 }
 
-pub fn parse_opivx_v0_format(opivx: &str) -> Result<format::Opivx, String> {
+pub fn parse_opivx_v0_format(opivx: &str) -> Result<format::Opivx, Diagnostic> {
     let tokens: Vec<&str> = opivx.split(',').map(str::trim).collect();
     if tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, rs1, v0', got {} instead",
-            opivx
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, rs1, v0', got {} instead", opivx),
+        )
+        .with_token(opivx));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
     let vs2 = parse_operand(tokens[1])?.as_register()?;
     let rs1 = integer::parse_operand(tokens[2])?;
     if parse_operand(tokens[3])?.as_register()? != 0 {
-        return Err("Expected last operand to be v0".to_owned());
+        return Err(
+            Diagnostic::error(Code::InvalidRegister, "Expected last operand to be v0")
+                .with_token(tokens[3]),
+        );
     }
 
     Ok(format::Opivx {
         vd,
         vs2,
         rs1,
-        vm: false,
+        vm: true,
     })
 }
 
-pub fn parse_opivx_maskless_format(opivx: &str) -> Result<format::Opivx, String> {
+pub fn parse_opivx_maskless_format(opivx: &str) -> Result<format::Opivx, Diagnostic> {
     let tokens: Vec<&str> = opivx.split(',').map(str::trim).collect();
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, rs1', got {} instead",
-            opivx
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, rs1', got {} instead", opivx),
+        )
+        .with_token(opivx));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -470,13 +543,14 @@ pub fn parse_opivx_maskless_format(opivx: &str) -> Result<format::Opivx, String>
     })
 }
 
-pub fn parse_opivx_vmv_format(opivx_vmv: &str) -> Result<format::Opivx, String> {
+pub fn parse_opivx_vmv_format(opivx_vmv: &str) -> Result<format::Opivx, Diagnostic> {
     let tokens: Vec<&str> = opivx_vmv.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'vd, rs1', got {} instead",
-            opivx_vmv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, rs1', got {} instead", opivx_vmv),
+        )
+        .with_token(opivx_vmv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -490,18 +564,22 @@ pub fn parse_opivx_vmv_format(opivx_vmv: &str) -> Result<format::Opivx, String>
     })
 }
 
-pub fn parse_opivi_format(opivi: &str) -> Result<format::Opivi, String> {
+pub fn parse_opivi_format(opivi: &str, ctx: &Context) -> Result<format::Opivi, Diagnostic> {
     let tokens: Vec<&str> = opivi.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, imm, [vm]', got {} instead",
-            opivi
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, vs2, imm, [vm]', got {} instead",
+                opivi
+            ),
+        )
+        .with_token(opivi));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
     let vs2 = parse_operand(tokens[1])?.as_register()?;
-    let imm = integer::parse_immediate(tokens[2])?;
+    let imm = integer::parse_immediate(tokens[2], ctx)?;
     let vm = if tokens.len() == 4 {
         parse_operand(tokens[3])?.as_mask()?;
         true
@@ -517,42 +595,50 @@ pub fn parse_opivi_format(opivi: &str) -> Result<format::Opivi, String> {
     })
 }
 
-pub fn parse_opivi_v0_format(opivi: &str) -> Result<format::Opivi, String> {
+pub fn parse_opivi_v0_format(opivi: &str, ctx: &Context) -> Result<format::Opivi, Diagnostic> {
     let tokens: Vec<&str> = opivi.split(',').map(str::trim).collect();
     if tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, imm, v0', got {} instead",
-            opivi
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, imm, v0', got {} instead", opivi),
+        )
+        .with_token(opivi));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
     let vs2 = parse_operand(tokens[1])?.as_register()?;
-    let imm = integer::parse_immediate(tokens[2])?;
+    let imm = integer::parse_immediate(tokens[2], ctx)?;
     if parse_operand(tokens[3])?.as_register()? != 0 {
-        return Err("Expected last operand to be v0".to_owned());
+        return Err(
+            Diagnostic::error(Code::InvalidRegister, "Expected last operand to be v0")
+                .with_token(tokens[3]),
+        );
     }
 
     Ok(format::Opivi {
         vd,
         vs2,
         imm5: imm,
-        vm: false,
+        vm: true,
     })
 }
 
-pub fn parse_opivi_maskless_format(opivi: &str) -> Result<format::Opivi, String> {
+pub fn parse_opivi_maskless_format(
+    opivi: &str,
+    ctx: &Context,
+) -> Result<format::Opivi, Diagnostic> {
     let tokens: Vec<&str> = opivi.split(',').map(str::trim).collect();
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, imm', got {} instead",
-            opivi
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, imm', got {} instead", opivi),
+        )
+        .with_token(opivi));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
     let vs2 = parse_operand(tokens[1])?.as_register()?;
-    let imm = integer::parse_immediate(tokens[2])?;
+    let imm = integer::parse_immediate(tokens[2], ctx)?;
 
     Ok(format::Opivi {
         vd,
@@ -562,17 +648,18 @@ pub fn parse_opivi_maskless_format(opivi: &str) -> Result<format::Opivi, String>
     })
 }
 
-pub fn parse_opivi_vmv_format(opivi_vmv: &str) -> Result<format::Opivi, String> {
+pub fn parse_opivi_vmv_format(opivi_vmv: &str, ctx: &Context) -> Result<format::Opivi, Diagnostic> {
     let tokens: Vec<&str> = opivi_vmv.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'vd, imm', got {} instead",
-            opivi_vmv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, imm', got {} instead", opivi_vmv),
+        )
+        .with_token(opivi_vmv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
-    let imm = integer::parse_immediate(tokens[1])?;
+    let imm = integer::parse_immediate(tokens[1], ctx)?;
 
     Ok(format::Opivi {
         vd,
@@ -582,13 +669,39 @@ pub fn parse_opivi_vmv_format(opivi_vmv: &str) -> Result<format::Opivi, String>
     })
 }
 
-pub fn parse_opmvv_format(opmvv: &str) -> Result<format::Opmvv, String> {
+/// Whole register moves take the number of registers from the mnemonic, e.g. `vmv2r.v`
+pub fn parse_vmvnr_format(vmvnr: &str, registers: i32) -> Result<format::Opivi, Diagnostic> {
+    let tokens: Vec<&str> = vmvnr.split(',').map(str::trim).collect();
+    if tokens.len() != 2 {
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2', got {} instead", vmvnr),
+        )
+        .with_token(vmvnr));
+    }
+
+    let vd = parse_operand(tokens[0])?.as_register()?;
+    let vs2 = parse_operand(tokens[1])?.as_register()?;
+
+    Ok(format::Opivi {
+        vd,
+        imm5: registers - 1,
+        vs2,
+        vm: false,
+    })
+}
+
+pub fn parse_opmvv_format(opmvv: &str) -> Result<format::Opmvv, Diagnostic> {
     let tokens: Vec<&str> = opmvv.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, vs1, [vm]', got {} instead",
-            opmvv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, vs2, vs1, [vm]', got {} instead",
+                opmvv
+            ),
+        )
+        .with_token(opmvv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -609,13 +722,14 @@ pub fn parse_opmvv_format(opmvv: &str) -> Result<format::Opmvv, String> {
     })
 }
 
-pub fn parse_opmvv_maskless_format(opmvv: &str) -> Result<format::Opmvv, String> {
+pub fn parse_opmvv_maskless_format(opmvv: &str) -> Result<format::Opmvv, Diagnostic> {
     let tokens: Vec<&str> = opmvv.split(',').map(str::trim).collect();
     if tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, vs1', got {} instead",
-            opmvv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, vs1', got {} instead", opmvv),
+        )
+        .with_token(opmvv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -630,13 +744,17 @@ pub fn parse_opmvv_maskless_format(opmvv: &str) -> Result<format::Opmvv, String>
     })
 }
 
-pub fn parse_opmvx_format(opmvx: &str) -> Result<format::Opmvx, String> {
+pub fn parse_opmvx_format(opmvx: &str) -> Result<format::Opmvx, Diagnostic> {
     let tokens: Vec<&str> = opmvx.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, rs1, [vm]', got {} instead",
-            opmvx
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, vs2, rs1, [vm]', got {} instead",
+                opmvx
+            ),
+        )
+        .with_token(opmvx));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -657,13 +775,17 @@ pub fn parse_opmvx_format(opmvx: &str) -> Result<format::Opmvx, String> {
     })
 }
 
-pub fn parse_opmvv_fma_format(opmvv: &str) -> Result<format::Opmvv, String> {
+pub fn parse_opmvv_fma_format(opmvv: &str) -> Result<format::Opmvv, Diagnostic> {
     let tokens: Vec<&str> = opmvv.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs1, vs2, [vm]', got {} instead",
-            opmvv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, vs1, vs2, [vm]', got {} instead",
+                opmvv
+            ),
+        )
+        .with_token(opmvv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -684,13 +806,17 @@ pub fn parse_opmvv_fma_format(opmvv: &str) -> Result<format::Opmvv, String> {
     })
 }
 
-pub fn parse_opmvx_fma_format(opmvx: &str) -> Result<format::Opmvx, String> {
+pub fn parse_opmvx_fma_format(opmvx: &str) -> Result<format::Opmvx, Diagnostic> {
     let tokens: Vec<&str> = opmvx.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, rs1, vs2, [vm]', got {} instead",
-            opmvx
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, rs1, vs2, [vm]', got {} instead",
+                opmvx
+            ),
+        )
+        .with_token(opmvx));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -711,13 +837,14 @@ pub fn parse_opmvx_fma_format(opmvx: &str) -> Result<format::Opmvx, String> {
     })
 }
 
-pub fn parse_vwxunary0_vmvxs_format(vwxunary0: &str) -> Result<format::Vwxunary0, String> {
+pub fn parse_vwxunary0_vmvxs_format(vwxunary0: &str) -> Result<format::Vwxunary0, Diagnostic> {
     let tokens: Vec<&str> = vwxunary0.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'rd, vs2', got {} instead",
-            vwxunary0
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, vs2', got {} instead", vwxunary0),
+        )
+        .with_token(vwxunary0));
     }
 
     let rd = integer::parse_operand(tokens[0])?;
@@ -731,13 +858,17 @@ pub fn parse_vwxunary0_vmvxs_format(vwxunary0: &str) -> Result<format::Vwxunary0
     })
 }
 
-pub fn parse_vwxunary0_format(vwxunary0: &str) -> Result<format::Vwxunary0, String> {
+pub fn parse_vwxunary0_format(vwxunary0: &str) -> Result<format::Vwxunary0, Diagnostic> {
     let tokens: Vec<&str> = vwxunary0.split(',').map(str::trim).collect();
     if tokens.len() != 2 && tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'rd, vs2, [vm]', got {} instead",
-            vwxunary0
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'rd, vs2, [vm]', got {} instead",
+                vwxunary0
+            ),
+        )
+        .with_token(vwxunary0));
     }
 
     let rd = integer::parse_operand(tokens[0])?;
@@ -757,13 +888,14 @@ pub fn parse_vwxunary0_format(vwxunary0: &str) -> Result<format::Vwxunary0, Stri
     })
 }
 
-pub fn parse_vrxunary0_format(vrxunary0: &str) -> Result<format::Vrxunary0, String> {
+pub fn parse_vrxunary0_format(vrxunary0: &str) -> Result<format::Vrxunary0, Diagnostic> {
     let tokens: Vec<&str> = vrxunary0.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'vd, rs1', got {} instead",
-            vrxunary0
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, rs1', got {} instead", vrxunary0),
+        )
+        .with_token(vrxunary0));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -777,13 +909,14 @@ pub fn parse_vrxunary0_format(vrxunary0: &str) -> Result<format::Vrxunary0, Stri
     })
 }
 
-pub fn parse_vxunary0_format(vxunary0: &str) -> Result<format::Vxunary0, String> {
+pub fn parse_vxunary0_format(vxunary0: &str) -> Result<format::Vxunary0, Diagnostic> {
     let tokens: Vec<&str> = vxunary0.split(',').map(str::trim).collect();
     if tokens.len() != 2 && tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, [vm]', got {} instead",
-            vxunary0
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, [vm]', got {} instead", vxunary0),
+        )
+        .with_token(vxunary0));
     }
 
     let rd = parse_operand(tokens[0])?.as_register()?;
@@ -803,13 +936,14 @@ pub fn parse_vxunary0_format(vxunary0: &str) -> Result<format::Vxunary0, String>
     })
 }
 
-pub fn parse_vmunary0_vidv_format(vmunary0: &str) -> Result<format::Vmunary0, String> {
+pub fn parse_vmunary0_vidv_format(vmunary0: &str) -> Result<format::Vmunary0, Diagnostic> {
     let tokens: Vec<&str> = vmunary0.split(',').map(str::trim).collect();
     if tokens.len() != 1 && tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'vd, [vm]', got {} instead",
-            vmunary0
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, [vm]', got {} instead", vmunary0),
+        )
+        .with_token(vmunary0));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -828,13 +962,14 @@ pub fn parse_vmunary0_vidv_format(vmunary0: &str) -> Result<format::Vmunary0, St
     })
 }
 
-pub fn parse_vmunary0_format(vmunary0: &str) -> Result<format::Vmunary0, String> {
+pub fn parse_vmunary0_format(vmunary0: &str) -> Result<format::Vmunary0, Diagnostic> {
     let tokens: Vec<&str> = vmunary0.split(',').map(str::trim).collect();
     if tokens.len() != 2 && tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, [vm]', got {} instead",
-            vmunary0
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, [vm]', got {} instead", vmunary0),
+        )
+        .with_token(vmunary0));
     }
 
     let rd = parse_operand(tokens[0])?.as_register()?;
@@ -854,13 +989,17 @@ pub fn parse_vmunary0_format(vmunary0: &str) -> Result<format::Vmunary0, String>
     })
 }
 
-pub fn parse_opfvv_format(opfvv: &str) -> Result<format::Opfvv, String> {
+pub fn parse_opfvv_format(opfvv: &str) -> Result<format::Opfvv, Diagnostic> {
     let tokens: Vec<&str> = opfvv.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, vs1, [vm]', got {} instead",
-            opfvv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, vs2, vs1, [vm]', got {} instead",
+                opfvv
+            ),
+        )
+        .with_token(opfvv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -881,13 +1020,17 @@ pub fn parse_opfvv_format(opfvv: &str) -> Result<format::Opfvv, String> {
     })
 }
 
-pub fn parse_opfvf_format(opfvf: &str) -> Result<format::Opfvf, String> {
+pub fn parse_opfvf_format(opfvf: &str) -> Result<format::Opfvf, Diagnostic> {
     let tokens: Vec<&str> = opfvf.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, rs1, [vm]', got {} instead",
-            opfvf
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, vs2, rs1, [vm]', got {} instead",
+                opfvf
+            ),
+        )
+        .with_token(opfvf));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -903,13 +1046,45 @@ pub fn parse_opfvf_format(opfvf: &str) -> Result<format::Opfvf, String> {
     Ok(format::Opfvf { vd, vs2, rs1, vm })
 }
 
-pub fn parse_opfvv_fma_format(opfvv: &str) -> Result<format::Opfvv, String> {
+pub fn parse_opfvf_v0_format(opfvf: &str) -> Result<format::Opfvf, Diagnostic> {
+    let tokens: Vec<&str> = opfvf.split(',').map(str::trim).collect();
+    if tokens.len() != 4 {
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, rs1, v0', got {} instead", opfvf),
+        )
+        .with_token(opfvf));
+    }
+
+    let vd = parse_operand(tokens[0])?.as_register()?;
+    let vs2 = parse_operand(tokens[1])?.as_register()?;
+    let rs1 = float::parse_operand(tokens[2])?;
+    if parse_operand(tokens[3])?.as_register()? != 0 {
+        return Err(
+            Diagnostic::error(Code::InvalidRegister, "Expected last operand to be v0")
+                .with_token(tokens[3]),
+        );
+    }
+
+    Ok(format::Opfvf {
+        vd,
+        vs2,
+        rs1,
+        vm: true,
+    })
+}
+
+pub fn parse_opfvv_fma_format(opfvv: &str) -> Result<format::Opfvv, Diagnostic> {
     let tokens: Vec<&str> = opfvv.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, vs1, vs2, [vm]', got {} instead",
-            opfvv
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, vs1, vs2, [vm]', got {} instead",
+                opfvv
+            ),
+        )
+        .with_token(opfvv));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -930,13 +1105,17 @@ pub fn parse_opfvv_fma_format(opfvv: &str) -> Result<format::Opfvv, String> {
     })
 }
 
-pub fn parse_opfvf_fma_format(opfvf: &str) -> Result<format::Opfvf, String> {
+pub fn parse_opfvf_fma_format(opfvf: &str) -> Result<format::Opfvf, Diagnostic> {
     let tokens: Vec<&str> = opfvf.split(',').map(str::trim).collect();
     if tokens.len() != 3 && tokens.len() != 4 {
-        return Err(format!(
-            "Expected format: 'vd, rs1, vs2, [vm]', got {} instead",
-            opfvf
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!(
+                "Expected format: 'vd, rs1, vs2, [vm]', got {} instead",
+                opfvf
+            ),
+        )
+        .with_token(opfvf));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -952,13 +1131,14 @@ pub fn parse_opfvf_fma_format(opfvf: &str) -> Result<format::Opfvf, String> {
     Ok(format::Opfvf { vd, vs2, rs1, vm })
 }
 
-pub fn parse_vwfunary0_format(vwfunary0: &str) -> Result<format::Vwfunary0, String> {
+pub fn parse_vwfunary0_format(vwfunary0: &str) -> Result<format::Vwfunary0, Diagnostic> {
     let tokens: Vec<&str> = vwfunary0.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'rd, vs2', got {} instead",
-            vwfunary0
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'rd, vs2', got {} instead", vwfunary0),
+        )
+        .with_token(vwfunary0));
     }
 
     let rd = float::parse_operand(tokens[0])?;
@@ -972,13 +1152,14 @@ pub fn parse_vwfunary0_format(vwfunary0: &str) -> Result<format::Vwfunary0, Stri
     })
 }
 
-pub fn parse_vrfunary0_format(vrfunary0: &str) -> Result<format::Vrfunary0, String> {
+pub fn parse_vrfunary0_format(vrfunary0: &str) -> Result<format::Vrfunary0, Diagnostic> {
     let tokens: Vec<&str> = vrfunary0.split(',').map(str::trim).collect();
     if tokens.len() != 2 {
-        return Err(format!(
-            "Expected format: 'vd, rs1', got {} instead",
-            vrfunary0
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, rs1', got {} instead", vrfunary0),
+        )
+        .with_token(vrfunary0));
     }
 
     let vd = parse_operand(tokens[0])?.as_register()?;
@@ -992,13 +1173,14 @@ pub fn parse_vrfunary0_format(vrfunary0: &str) -> Result<format::Vrfunary0, Stri
     })
 }
 
-pub fn parse_vfunary0_format(vfunary0: &str) -> Result<format::Vfunary0, String> {
+pub fn parse_vfunary0_format(vfunary0: &str) -> Result<format::Vfunary0, Diagnostic> {
     let tokens: Vec<&str> = vfunary0.split(',').map(str::trim).collect();
     if tokens.len() != 2 && tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, [vm]', got {} instead",
-            vfunary0
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, [vm]', got {} instead", vfunary0),
+        )
+        .with_token(vfunary0));
     }
 
     let rd = parse_operand(tokens[0])?.as_register()?;
@@ -1018,13 +1200,14 @@ pub fn parse_vfunary0_format(vfunary0: &str) -> Result<format::Vfunary0, String>
     })
 }
 
-pub fn parse_vfunary1_format(vfunary1: &str) -> Result<format::Vfunary1, String> {
+pub fn parse_vfunary1_format(vfunary1: &str) -> Result<format::Vfunary1, Diagnostic> {
     let tokens: Vec<&str> = vfunary1.split(',').map(str::trim).collect();
     if tokens.len() != 2 && tokens.len() != 3 {
-        return Err(format!(
-            "Expected format: 'vd, vs2, [vm]', got {} instead",
-            vfunary1
-        ));
+        return Err(Diagnostic::error(
+            Code::InvalidFormat,
+            format!("Expected format: 'vd, vs2, [vm]', got {} instead", vfunary1),
+        )
+        .with_token(vfunary1));
     }
 
     let rd = parse_operand(tokens[0])?.as_register()?;
@@ -1051,25 +1234,30 @@ pub enum VectorOperand {
 }
 
 impl VectorOperand {
-    fn as_register(&self) -> Result<usize, String> {
+    fn as_register(&self) -> Result<usize, Diagnostic> {
         match self {
             Self::Register(nth) => Ok(*nth),
-            Self::Mask => Err("Expected register, parsed mask instead".to_owned()),
+            Self::Mask => Err(Diagnostic::error(
+                Code::InvalidRegister,
+                "Expected register, parsed mask instead",
+            )
+            .with_token("v0.t")),
         }
     }
 
-    fn as_mask(&self) -> Result<(), String> {
+    fn as_mask(&self) -> Result<(), Diagnostic> {
         match self {
-            Self::Register(nth) => Err(format!(
-                "Expected mask, parsed vector register {} instead",
-                nth
-            )),
+            Self::Register(nth) => Err(Diagnostic::error(
+                Code::InvalidRegister,
+                format!("Expected mask, parsed vector register {} instead", nth),
+            )
+            .with_token(format!("v{}", nth))),
             Self::Mask => Ok(()),
         }
     }
 }
 
-fn parse_operand(op: &str) -> Result<VectorOperand, String> {
+fn parse_operand(op: &str) -> Result<VectorOperand, Diagnostic> {
     let operand = match op {
         "v0" => 0,
         "v1" => 1,
@@ -1104,18 +1292,34 @@ fn parse_operand(op: &str) -> Result<VectorOperand, String> {
         "v30" => 30,
         "v31" => 31,
         "v0.t" => return Ok(VectorOperand::Mask),
-        _ => return Err(format!("Unknown vector operand {}", op)),
+        _ => {
+            return Err(Diagnostic::error(
+                Code::InvalidRegister,
+                format!("Unknown vector operand {}", op),
+            )
+            .with_token(op)
+            .with_help("vector registers are v0-v31, the mask operand is v0.t"))
+        }
     };
 
     Ok(VectorOperand::Register(operand))
 }
 
 pub mod pseudo {
-    pub fn parse_op_format(op: &str) -> Result<usize, String> {
+    use crate::interpreter::{
+        decoder::expression::Context,
+        diagnostic::{Code, Diagnostic},
+    };
+
+    pub fn parse_op_format(op: &str) -> Result<usize, Diagnostic> {
         let tokens: Vec<&str> = op.split(',').map(str::trim).collect();
 
         if tokens.len() != 1 {
-            return Err(format!("Expected format: 'vreg', got {} instead", op));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'vreg', got {} instead", op),
+            )
+            .with_token(op));
         }
 
         let reg1 = super::parse_operand(tokens[0])?.as_register()?;
@@ -1123,14 +1327,15 @@ pub mod pseudo {
         Ok(reg1)
     }
 
-    pub fn parse_op_op_format(op_op: &str) -> Result<(usize, usize), String> {
+    pub fn parse_op_op_format(op_op: &str) -> Result<(usize, usize), Diagnostic> {
         let tokens: Vec<&str> = op_op.split(',').map(str::trim).collect();
 
         if tokens.len() != 2 {
-            return Err(format!(
-                "Expected format: 'vreg1, vreg2', got {} instead",
-                op_op
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!("Expected format: 'vreg1, vreg2', got {} instead", op_op),
+            )
+            .with_token(op_op));
         }
 
         let reg1 = super::parse_operand(tokens[0])?.as_register()?;
@@ -1139,14 +1344,18 @@ pub mod pseudo {
         Ok((reg1, reg2))
     }
 
-    pub fn parse_op_op_mask_format(op_op_mask: &str) -> Result<(usize, usize, bool), String> {
+    pub fn parse_op_op_mask_format(op_op_mask: &str) -> Result<(usize, usize, bool), Diagnostic> {
         let tokens: Vec<&str> = op_op_mask.split(',').map(str::trim).collect();
 
         if tokens.len() != 2 && tokens.len() != 3 {
-            return Err(format!(
-                "Expected format: 'vreg1, vreg2, [vm]', got {} instead",
-                op_op_mask
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: 'vreg1, vreg2, [vm]', got {} instead",
+                    op_op_mask
+                ),
+            )
+            .with_token(op_op_mask));
         }
 
         let reg1 = super::parse_operand(tokens[0])?.as_register()?;
@@ -1162,14 +1371,18 @@ pub mod pseudo {
         Ok((reg1, reg2, vm))
     }
 
-    pub fn parse_op_op_xreg_format(op_op_xreg: &str) -> Result<(usize, usize, usize), String> {
+    pub fn parse_op_op_xreg_format(op_op_xreg: &str) -> Result<(usize, usize, usize), Diagnostic> {
         let tokens: Vec<&str> = op_op_xreg.split(',').map(str::trim).collect();
 
         if tokens.len() != 3 {
-            return Err(format!(
-                "Expected format: 'vreg1, vreg2, xreg', got {} instead",
-                op_op_xreg
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: 'vreg1, vreg2, xreg', got {} instead",
+                    op_op_xreg
+                ),
+            )
+            .with_token(op_op_xreg));
         }
 
         let reg1 = super::parse_operand(tokens[0])?.as_register()?;
@@ -1181,22 +1394,28 @@ pub mod pseudo {
 
     pub fn parse_op_op_xreg_mask_vd_nonzero_format(
         op_op_xreg_mask_vd_nonzero: &str,
-    ) -> Result<(usize, usize, usize), String> {
+    ) -> Result<(usize, usize, usize), Diagnostic> {
         let tokens: Vec<&str> = op_op_xreg_mask_vd_nonzero
             .split(',')
             .map(str::trim)
             .collect();
 
         if tokens.len() != 4 {
-            return Err(format!(
-                "Expected format: 'vreg1, vreg2, xreg, v0.t', got {} instead",
-                op_op_xreg_mask_vd_nonzero
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: 'vreg1, vreg2, xreg, v0.t', got {} instead",
+                    op_op_xreg_mask_vd_nonzero
+                ),
+            )
+            .with_token(op_op_xreg_mask_vd_nonzero));
         }
 
         let reg1 = super::parse_operand(tokens[0])?.as_register()?;
         if reg1 == 0 {
-            return Err("Expected vd != v0".to_owned());
+            return Err(
+                Diagnostic::error(Code::InvalidRegister, "Expected vd != v0").with_token(tokens[0]),
+            );
         }
 
         let reg2 = super::parse_operand(tokens[1])?.as_register()?;
@@ -1208,14 +1427,18 @@ pub mod pseudo {
 
     pub fn parse_op_op_xreg_mask_temp_format(
         op_op_xreg_mask: &str,
-    ) -> Result<(usize, usize, usize, usize), String> {
+    ) -> Result<(usize, usize, usize, usize), Diagnostic> {
         let tokens: Vec<&str> = op_op_xreg_mask.split(',').map(str::trim).collect();
 
         if tokens.len() != 5 {
-            return Err(format!(
-                "Expected format: 'vreg1, vreg2, xreg, v0.t, vt', got {} instead",
-                op_op_xreg_mask
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: 'vreg1, vreg2, xreg, v0.t, vt', got {} instead",
+                    op_op_xreg_mask
+                ),
+            )
+            .with_token(op_op_xreg_mask));
         }
 
         let reg1 = super::parse_operand(tokens[0])?.as_register()?;
@@ -1229,14 +1452,18 @@ pub mod pseudo {
 
     pub fn parse_op_op_op_mask_format(
         op_op_op_mask: &str,
-    ) -> Result<(usize, usize, usize, bool), String> {
+    ) -> Result<(usize, usize, usize, bool), Diagnostic> {
         let tokens: Vec<&str> = op_op_op_mask.split(',').map(str::trim).collect();
 
         if tokens.len() != 3 && tokens.len() != 4 {
-            return Err(format!(
-                "Expected format: 'vreg1, vreg2, vreg3, [vm]', got {} instead",
-                op_op_op_mask
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: 'vreg1, vreg2, vreg3, [vm]', got {} instead",
+                    op_op_op_mask
+                ),
+            )
+            .with_token(op_op_op_mask));
         }
 
         let reg1 = super::parse_operand(tokens[0])?.as_register()?;
@@ -1255,19 +1482,24 @@ pub mod pseudo {
 
     pub fn parse_op_op_imm_mask_format(
         op_op_imm_mask: &str,
-    ) -> Result<(usize, usize, i32, bool), String> {
+        ctx: &Context,
+    ) -> Result<(usize, usize, i32, bool), Diagnostic> {
         let tokens: Vec<&str> = op_op_imm_mask.split(',').map(str::trim).collect();
 
         if tokens.len() != 3 && tokens.len() != 4 {
-            return Err(format!(
-                "Expected format: 'vreg1, vreg2, imm, [vm]', got {} instead",
-                op_op_imm_mask
-            ));
+            return Err(Diagnostic::error(
+                Code::InvalidFormat,
+                format!(
+                    "Expected format: 'vreg1, vreg2, imm, [vm]', got {} instead",
+                    op_op_imm_mask
+                ),
+            )
+            .with_token(op_op_imm_mask));
         }
 
         let reg1 = super::parse_operand(tokens[0])?.as_register()?;
         let reg2 = super::parse_operand(tokens[1])?.as_register()?;
-        let reg3 = super::integer::parse_immediate(tokens[2])?;
+        let reg3 = super::integer::parse_immediate(tokens[2], ctx)?;
 
         let vm = if tokens.len() == 4 {
             super::parse_operand(tokens[3])?.as_mask()?;
@@ -0,0 +1,229 @@
+use std::{fmt, ops::Range, sync::Arc};
+
+use super::source::{strip_comment, ColumnMap, SourceLine};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Code {
+    UnknownMnemonic,
+    InvalidFormat,
+    InvalidRegister,
+    InvalidImmediate,
+    UnknownLabel,
+    InvalidLabel,
+    InvalidVtype,
+    UnknownDirective,
+    InvalidData,
+    Preprocessor,
+    InvalidMacro,
+    InvalidConditional,
+    InvalidInclude,
+    IllegalInstruction,
+    InvalidElf,
+    InvalidLayout,
+    UnusedLabel,
+    WriteToZero,
+    ShadowedLabel,
+}
+
+impl Code {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnknownMnemonic => "E0001",
+            Self::InvalidFormat => "E0002",
+            Self::InvalidRegister => "E0003",
+            Self::InvalidImmediate => "E0004",
+            Self::UnknownLabel => "E0005",
+            Self::InvalidLabel => "E0006",
+            Self::InvalidVtype => "E0007",
+            Self::UnknownDirective => "E0008",
+            Self::InvalidData => "E0009",
+            Self::Preprocessor => "E0010",
+            Self::InvalidMacro => "E0011",
+            Self::InvalidConditional => "E0012",
+            Self::InvalidInclude => "E0013",
+            Self::IllegalInstruction => "E0014",
+            Self::InvalidElf => "E0015",
+            Self::InvalidLayout => "E0016",
+            Self::UnusedLabel => "W0001",
+            // W0002 warned about truncated `li` immediates, which are materialized in full since
+            Self::WriteToZero => "W0003",
+            Self::ShadowedLabel => "W0004",
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A single compilation finding
+///
+/// Parsers only fill in the message and the offending token,
+/// `file`, `line` and `columns` (byte offsets into the source line) are resolved by `Interpreter::compile`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diagnostic {
+    /// Path of the included file, `None` for the compiled program itself
+    pub file: Option<Arc<str>>,
+    pub line: usize,
+    pub columns: Range<usize>,
+    pub severity: Severity,
+    pub code: Code,
+    pub message: String,
+    pub token: Option<String>,
+    pub help: Option<String>,
+    /// Addresses of the token, which parsers slice out of the line they decode
+    pub(crate) token_addresses: Option<Range<usize>>,
+    /// Columns of the token in the source line, resolved from `token_addresses` by `Diagnostic::within`
+    pub(crate) token_columns: Option<Range<usize>>,
+}
+
+impl Diagnostic {
+    pub fn error(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            file: None,
+            line: 0,
+            columns: 0..0,
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            token: None,
+            help: None,
+            token_addresses: None,
+            token_columns: None,
+        }
+    }
+
+    pub fn warning(code: Code, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(code, message)
+        }
+    }
+
+    pub fn with_token(mut self, token: impl AsRef<str>) -> Self {
+        let token = token.as_ref();
+        let trimmed = token.trim();
+        let start = trimmed.as_ptr() as usize;
+
+        self.token = Some(token.to_owned());
+        self.token_addresses = Some(start..start + trimmed.len());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Merges diagnostics of alternative operand formats, keeping location details of the first one
+    pub fn or(mut self, other: Diagnostic) -> Self {
+        self.message = format!("{} or {}", self.message, other.message);
+        self.help = self.help.or(other.help);
+        self
+    }
+
+    /// Resolves the token to columns of the source line, if it is a slice of the decoded `line`
+    ///
+    /// Unlike searching the source line for the token, this points at the operand which was parsed,
+    /// and at the name of a `#define` for tokens it was substituted with.
+    pub(crate) fn within(mut self, line: &str, columns: &ColumnMap) -> Self {
+        let line_start = line.as_ptr() as usize;
+
+        self.token_columns = self
+            .token_addresses
+            .take()
+            .filter(|addresses| {
+                line_start <= addresses.start && addresses.end <= line_start + line.len()
+            })
+            .and_then(|addresses| {
+                columns.resolve(addresses.start - line_start..addresses.end - line_start)
+            });
+        self
+    }
+
+    /// Tokens which are not resolved by `Diagnostic::within`, e.g. of macro expansions, are searched for in the source line
+    pub(crate) fn locate(mut self, source: &SourceLine) -> Self {
+        self.file = source.file.clone();
+        self.line = source.line;
+        self.token_addresses = None;
+        self.columns = self
+            .token_columns
+            .take()
+            .or_else(|| {
+                self.token
+                    .as_deref()
+                    .and_then(|token| find_token(&source.text, token))
+            })
+            .unwrap_or_else(|| code_span(&source.text));
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+
+        write!(
+            f,
+            "{}:{}: {}[{}]: {}",
+            self.line + 1,
+            self.columns.start + 1,
+            severity,
+            self.code,
+            self.message
+        )?;
+
+        if let Some(help) = &self.help {
+            write!(f, "\nhelp: {}", help)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn find_token(source: &str, token: &str) -> Option<Range<usize>> {
+    let token = token.trim();
+
+    if token.is_empty() {
+        return None;
+    }
+
+    let is_word_char = |ch: char| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.';
+
+    source.match_indices(token).find_map(|(start, _)| {
+        let end = start + token.len();
+        let bounded_before =
+            !source[..start].ends_with(is_word_char) || !token.starts_with(is_word_char);
+        let bounded_after =
+            !source[end..].starts_with(is_word_char) || !token.ends_with(is_word_char);
+
+        (bounded_before && bounded_after).then_some(start..end)
+    })
+}
+
+pub(super) fn code_span(source: &str) -> Range<usize> {
+    let code = if source.trim_start().starts_with('#') {
+        source
+    } else {
+        strip_comment(source)
+    };
+    let start = code.len() - code.trim_start().len();
+    let end = code.trim_end().len();
+
+    start..end.max(start)
+}
@@ -0,0 +1,1028 @@
+use std::collections::HashMap;
+
+use eeric_core::prelude::*;
+
+/// Renders instructions back to assembly, using canonical mnemonics rather than pseudo-instructions
+#[derive(Default)]
+pub struct Disassembler {
+    labels: HashMap<usize, String>,
+}
+
+impl Disassembler {
+    /// Jump and branch targets are shown as one of the labels at their address
+    pub fn new(labels: &HashMap<String, usize>) -> Self {
+        let mut by_address = HashMap::<usize, String>::new();
+
+        for (label, &address) in labels {
+            by_address
+                .entry(address)
+                .and_modify(|existing| {
+                    if label < existing {
+                        existing.clone_from(label);
+                    }
+                })
+                .or_insert_with(|| label.clone());
+        }
+
+        Self { labels: by_address }
+    }
+
+    /// Fused pseudo-instructions are rendered one instruction per line
+    pub fn disassemble(&self, instruction: &Instruction, address: usize) -> String {
+        match instruction {
+            Instruction::Fusion(instructions) => {
+                let mut lines = Vec::new();
+
+                for (index, part) in instructions.iter().enumerate() {
+                    let line = self.disassemble_single(part, address);
+                    let label = index.checked_sub(1).and_then(|previous| {
+                        self.pcrel_label(&instructions[previous], part, address)
+                    });
+
+                    lines.push(match label {
+                        Some(label) => format!("{}  # {}", line, label),
+                        None => line,
+                    });
+                }
+
+                lines.join("\n")
+            }
+            single => self.disassemble_single(single, address),
+        }
+    }
+
+    fn disassemble_single(&self, instruction: &Instruction, address: usize) -> String {
+        use Instruction::*;
+
+        let (mnemonic, operands) = match instruction {
+            Add(args) => ("add", r(args)),
+            Addw(args) => ("addw", r(args)),
+            Sub(args) => ("sub", r(args)),
+            Subw(args) => ("subw", r(args)),
+            Addi(args) => ("addi", i(args)),
+            Addiw(args) => ("addiw", i(args)),
+            Slt(args) => ("slt", r(args)),
+            Slti(args) => ("slti", i(args)),
+            Sltu(args) => ("sltu", r(args)),
+            Sltiu(args) => ("sltiu", i(args)),
+            Lui(args) => ("lui", upper(args)),
+            Auipc(args) => ("auipc", upper(args)),
+
+            And(args) => ("and", r(args)),
+            Or(args) => ("or", r(args)),
+            Xor(args) => ("xor", r(args)),
+            Andi(args) => ("andi", i(args)),
+            Ori(args) => ("ori", i(args)),
+            Xori(args) => ("xori", i(args)),
+            Sll(args) => ("sll", r(args)),
+            Sllw(args) => ("sllw", r(args)),
+            Srl(args) => ("srl", r(args)),
+            Srlw(args) => ("srlw", r(args)),
+            Sra(args) => ("sra", r(args)),
+            Sraw(args) => ("sraw", r(args)),
+            Slli(args) => ("slli", i(args)),
+            Slliw(args) => ("slliw", i(args)),
+            Srli(args) => ("srli", i(args)),
+            Srliw(args) => ("srliw", i(args)),
+            Srai(args) => ("srai", i(args)),
+            Sraiw(args) => ("sraiw", i(args)),
+
+            Ld(args) => ("ld", load(args)),
+            Lw(args) => ("lw", load(args)),
+            Lh(args) => ("lh", load(args)),
+            Lb(args) => ("lb", load(args)),
+            Lwu(args) => ("lwu", load(args)),
+            Lhu(args) => ("lhu", load(args)),
+            Lbu(args) => ("lbu", load(args)),
+            Sd(args) => ("sd", store(args)),
+            Sw(args) => ("sw", store(args)),
+            Sh(args) => ("sh", store(args)),
+            Sb(args) => ("sb", store(args)),
+
+            Beq(args) => ("beq", self.branch(args, address)),
+            Bne(args) => ("bne", self.branch(args, address)),
+            Bge(args) => ("bge", self.branch(args, address)),
+            Bgeu(args) => ("bgeu", self.branch(args, address)),
+            Blt(args) => ("blt", self.branch(args, address)),
+            Bltu(args) => ("bltu", self.branch(args, address)),
+            Jal(args) => (
+                "jal",
+                format!("{}, {}", x(args.rd), self.target(args.imm20, address)),
+            ),
+            Jalr(args) => ("jalr", load(args)),
+
+            Csrrw(args) => ("csrrw", csrr(args)),
+            Csrrs(args) => ("csrrs", csrr(args)),
+            Csrrc(args) => ("csrrc", csrr(args)),
+            Csrrwi(args) => ("csrrwi", csri(args)),
+            Csrrsi(args) => ("csrrsi", csri(args)),
+            Csrrci(args) => ("csrrci", csri(args)),
+
+            Mul(args) => ("mul", r(args)),
+            Mulh(args) => ("mulh", r(args)),
+            Mulhsu(args) => ("mulhsu", r(args)),
+            Mulhu(args) => ("mulhu", r(args)),
+            Div(args) => ("div", r(args)),
+            Divu(args) => ("divu", r(args)),
+            Rem(args) => ("rem", r(args)),
+            Remu(args) => ("remu", r(args)),
+            Mulw(args) => ("mulw", r(args)),
+            Divw(args) => ("divw", r(args)),
+            Divuw(args) => ("divuw", r(args)),
+            Remw(args) => ("remw", r(args)),
+            Remuw(args) => ("remuw", r(args)),
+
+            Flw(args) => ("flw", fl(args)),
+            Fsw(args) => ("fsw", fs(args)),
+            Fmadds(args) => ("fmadd.s", r4(args)),
+            Fmsubs(args) => ("fmsub.s", r4(args)),
+            Fnmsubs(args) => ("fnmsub.s", r4(args)),
+            Fnmadds(args) => ("fnmadd.s", r4(args)),
+            Fadds(args) => ("fadd.s", fr(args)),
+            Fsubs(args) => ("fsub.s", fr(args)),
+            Fmuls(args) => ("fmul.s", fr(args)),
+            Fdivs(args) => ("fdiv.s", fr(args)),
+            Fsqrts(args) => ("fsqrt.s", frs(args)),
+            Fsgnjs(args) => ("fsgnj.s", fr(args)),
+            Fsgnjns(args) => ("fsgnjn.s", fr(args)),
+            Fsgnjxs(args) => ("fsgnjx.s", fr(args)),
+            Fmins(args) => ("fmin.s", fr(args)),
+            Fmaxs(args) => ("fmax.s", fr(args)),
+            Fcvtws(args) => ("fcvt.w.s", frs_to_x(args)),
+            Fcvtwus(args) => ("fcvt.wu.s", frs_to_x(args)),
+            Fmvxw(args) => ("fmv.x.w", frs_to_x(args)),
+            Feqs(args) => ("feq.s", frx(args)),
+            Flts(args) => ("flt.s", frx(args)),
+            Fles(args) => ("fle.s", frx(args)),
+            Fclasss(args) => ("fclass.s", frs_to_x(args)),
+            Fcvtsw(args) => ("fcvt.s.w", frs_to_f(args)),
+            Fcvtswu(args) => ("fcvt.s.wu", frs_to_f(args)),
+            Fmvwx(args) => ("fmv.w.x", frs_to_f(args)),
+            Fcvtls(args) => ("fcvt.l.s", frs_to_x(args)),
+            Fcvtlus(args) => ("fcvt.lu.s", frs_to_x(args)),
+            Fcvtsl(args) => ("fcvt.s.l", frs_to_f(args)),
+            Fcvtslu(args) => ("fcvt.s.lu", frs_to_f(args)),
+
+            Fld(args) => ("fld", fl(args)),
+            Fsd(args) => ("fsd", fs(args)),
+            Fmaddd(args) => ("fmadd.d", r4(args)),
+            Fmsubd(args) => ("fmsub.d", r4(args)),
+            Fnmsubd(args) => ("fnmsub.d", r4(args)),
+            Fnmaddd(args) => ("fnmadd.d", r4(args)),
+            Faddd(args) => ("fadd.d", fr(args)),
+            Fsubd(args) => ("fsub.d", fr(args)),
+            Fmuld(args) => ("fmul.d", fr(args)),
+            Fdivd(args) => ("fdiv.d", fr(args)),
+            Fsqrtd(args) => ("fsqrt.d", frs(args)),
+            Fsgnjd(args) => ("fsgnj.d", fr(args)),
+            Fsgnjnd(args) => ("fsgnjn.d", fr(args)),
+            Fsgnjxd(args) => ("fsgnjx.d", fr(args)),
+            Fmind(args) => ("fmin.d", fr(args)),
+            Fmaxd(args) => ("fmax.d", fr(args)),
+            Fcvtsd(args) => ("fcvt.s.d", frs(args)),
+            Fcvtds(args) => ("fcvt.d.s", frs(args)),
+            Feqd(args) => ("feq.d", frx(args)),
+            Fltd(args) => ("flt.d", frx(args)),
+            Fled(args) => ("fle.d", frx(args)),
+            Fclassd(args) => ("fclass.d", frs_to_x(args)),
+            Fcvtwd(args) => ("fcvt.w.d", frs_to_x(args)),
+            Fcvtwud(args) => ("fcvt.wu.d", frs_to_x(args)),
+            Fcvtdw(args) => ("fcvt.d.w", frs_to_f(args)),
+            Fcvtdwu(args) => ("fcvt.d.wu", frs_to_f(args)),
+            Fcvtld(args) => ("fcvt.l.d", frs_to_x(args)),
+            Fcvtlud(args) => ("fcvt.lu.d", frs_to_x(args)),
+            Fmvxd(args) => ("fmv.x.d", frs_to_x(args)),
+            Fcvtdl(args) => ("fcvt.d.l", frs_to_f(args)),
+            Fcvtdlu(args) => ("fcvt.d.lu", frs_to_f(args)),
+            Fmvdx(args) => ("fmv.d.x", frs_to_f(args)),
+
+            Vsetvli(args) => ("vsetvli", vsetvli(args)),
+            Vsetivli(args) => ("vsetivli", vsetivli(args)),
+
+            Vsetvl(args) => ("vsetvl", vsetvl(args)),
+
+            Vlv { data, eew } => return format!("vle{}.v {}", bits(eew), vl(data)),
+            Vsv { data, eew } => return format!("vse{}.v {}", bits(eew), vs(data)),
+            Vlmv(args) => ("vlm.v", vlm(args)),
+            Vsmv(args) => ("vsm.v", vsm(args)),
+            Vlsv { data, eew } => return format!("vlse{}.v {}", bits(eew), vls(data)),
+            Vssv { data, eew } => return format!("vsse{}.v {}", bits(eew), vss(data)),
+            Vluxv { data, eew } => return format!("vluxei{}.v {}", bits(eew), vlx(data)),
+            Vloxv { data, eew } => return format!("vloxei{}.v {}", bits(eew), vlx(data)),
+            Vsuxv { data, eew } => return format!("vsuxei{}.v {}", bits(eew), vsx(data)),
+            Vsoxv { data, eew } => return format!("vsoxei{}.v {}", bits(eew), vsx(data)),
+            Vlffv { data, eew } => return format!("vle{}ff.v {}", bits(eew), vl(data)),
+            Vlsegv { data, eew, nf } => return format!("vlseg{}e{}.v {}", nf, bits(eew), vl(data)),
+            Vssegv { data, eew, nf } => return format!("vsseg{}e{}.v {}", nf, bits(eew), vs(data)),
+            Vlssegv { data, eew, nf } => {
+                return format!("vlsseg{}e{}.v {}", nf, bits(eew), vls(data))
+            }
+            Vsssegv { data, eew, nf } => {
+                return format!("vssseg{}e{}.v {}", nf, bits(eew), vss(data))
+            }
+            Vluxsegv { data, eew, nf } => {
+                return format!("vluxseg{}ei{}.v {}", nf, bits(eew), vlx(data))
+            }
+            Vloxsegv { data, eew, nf } => {
+                return format!("vloxseg{}ei{}.v {}", nf, bits(eew), vlx(data))
+            }
+            Vsuxsegv { data, eew, nf } => {
+                return format!("vsuxseg{}ei{}.v {}", nf, bits(eew), vsx(data))
+            }
+            Vsoxsegv { data, eew, nf } => {
+                return format!("vsoxseg{}ei{}.v {}", nf, bits(eew), vsx(data))
+            }
+            Vlrv { data, eew, nf } => return format!("vl{}re{}.v {}", nf, bits(eew), vlr(data)),
+            Vsrv { data, nf } => return format!("vs{}r.v {}", nf, vsr(data)),
+
+            Vaddvv(args) => ("vadd.vv", opivv(args)),
+            Vaddvx(args) => ("vadd.vx", opivx(args)),
+            Vaddvi(args) => ("vadd.vi", opivi(args)),
+            Vsubvv(args) => ("vsub.vv", opivv(args)),
+            Vsubvx(args) => ("vsub.vx", opivx(args)),
+            Vrsubvx(args) => ("vrsub.vx", opivx(args)),
+            Vrsubvi(args) => ("vrsub.vi", opivi(args)),
+            Vminuvv(args) => ("vminu.vv", opivv(args)),
+            Vminuvx(args) => ("vminu.vx", opivx(args)),
+            Vminvv(args) => ("vmin.vv", opivv(args)),
+            Vminvx(args) => ("vmin.vx", opivx(args)),
+            Vmaxuvv(args) => ("vmaxu.vv", opivv(args)),
+            Vmaxuvx(args) => ("vmaxu.vx", opivx(args)),
+            Vmaxvv(args) => ("vmax.vv", opivv(args)),
+            Vmaxvx(args) => ("vmax.vx", opivx(args)),
+            Vandvv(args) => ("vand.vv", opivv(args)),
+            Vandvx(args) => ("vand.vx", opivx(args)),
+            Vandvi(args) => ("vand.vi", opivi(args)),
+            Vorvv(args) => ("vor.vv", opivv(args)),
+            Vorvx(args) => ("vor.vx", opivx(args)),
+            Vorvi(args) => ("vor.vi", opivi(args)),
+            Vxorvv(args) => ("vxor.vv", opivv(args)),
+            Vxorvx(args) => ("vxor.vx", opivx(args)),
+            Vxorvi(args) => ("vxor.vi", opivi(args)),
+            Vrgathervv(args) => ("vrgather.vv", opivv(args)),
+            Vrgathervx(args) => ("vrgather.vx", opivx(args)),
+            Vrgathervi(args) => ("vrgather.vi", opivi(args)),
+            Vrgatherei16vv(args) => ("vrgatherei16.v", opivv(args)),
+            Vslideupvx(args) => ("vslideup.vx", opivx(args)),
+            Vslideupvi(args) => ("vslideup.vi", opivi(args)),
+            Vslidedownvx(args) => ("vslidedown.vx", opivx(args)),
+            Vslidedownvi(args) => ("vslidedown.vi", opivi(args)),
+
+            Vadcvvm(args) => ("vadc.vvm", opivv_v0(args)),
+            Vadcvxm(args) => ("vadc.vxm", opivx_v0(args)),
+            Vadcvim(args) => ("vadc.vim", opivi_v0(args)),
+            Vmadcvvm(args) => ("vmadc.vvm", opivv_v0(args)),
+            Vmadcvxm(args) => ("vmadc.vxm", opivx_v0(args)),
+            Vmadcvim(args) => ("vmadc.vim", opivi_v0(args)),
+            Vmadcvv(args) => ("vmadc.vv", opivv_maskless(args)),
+            Vmadcvx(args) => ("vmadc.vx", opivx_maskless(args)),
+            Vmadcvi(args) => ("vmadc.vi", opivi_maskless(args)),
+            Vsbcvvm(args) => ("vsbc.vvm", opivv_v0(args)),
+            Vsbcvxm(args) => ("vsbc.vxm", opivx_v0(args)),
+            Vmsbcvvm(args) => ("vmsbc.vvm", opivv_v0(args)),
+            Vmsbcvxm(args) => ("vmsbc.vxm", opivx_v0(args)),
+            Vmsbcvv(args) => ("vmsbc.vv", opivv_maskless(args)),
+            Vmsbcvx(args) => ("vmsbc.vx", opivx_maskless(args)),
+            Vmergevvm(args) => ("vmerge.vvm", opivv_v0(args)),
+            Vmergevxm(args) => ("vmerge.vxm", opivx_v0(args)),
+            Vmergevim(args) => ("vmerge.vim", opivi_v0(args)),
+            Vmvvv(args) => ("vmv.v.v", opivv_vmv(args)),
+            Vmvvx(args) => ("vmv.v.x", opivx_vmv(args)),
+            Vmvvi(args) => ("vmv.v.i", opivi_vmv(args)),
+
+            Vmseqvv(args) => ("vmseq.vv", opivv(args)),
+            Vmseqvx(args) => ("vmseq.vx", opivx(args)),
+            Vmseqvi(args) => ("vmseq.vi", opivi(args)),
+            Vmsnevv(args) => ("vmsne.vv", opivv(args)),
+            Vmsnevx(args) => ("vmsne.vx", opivx(args)),
+            Vmsnevi(args) => ("vmsne.vi", opivi(args)),
+            Vmsltuvv(args) => ("vmsltu.vv", opivv(args)),
+            Vmsltuvx(args) => ("vmsltu.vx", opivx(args)),
+            Vmsltvv(args) => ("vmslt.vv", opivv(args)),
+            Vmsltvx(args) => ("vmslt.vx", opivx(args)),
+            Vmsleuvv(args) => ("vmsleu.vv", opivv(args)),
+            Vmsleuvx(args) => ("vmsleu.vx", opivx(args)),
+            Vmsleuvi(args) => ("vmsleu.vi", opivi(args)),
+            Vmslevv(args) => ("vmsle.vv", opivv(args)),
+            Vmslevx(args) => ("vmsle.vx", opivx(args)),
+            Vmslevi(args) => ("vmsle.vi", opivi(args)),
+            Vmsgtuvx(args) => ("vmsgtu.vx", opivx(args)),
+            Vmsgtuvi(args) => ("vmsgtu.vi", opivi(args)),
+            Vmsgtvx(args) => ("vmsgt.vx", opivx(args)),
+            Vmsgtvi(args) => ("vmsgt.vi", opivi(args)),
+
+            Vsadduvv(args) => ("vsaddu.vv", opivv(args)),
+            Vsadduvx(args) => ("vsaddu.vx", opivx(args)),
+            Vsadduvi(args) => ("vsaddu.vi", opivi(args)),
+            Vsaddvv(args) => ("vsadd.vv", opivv(args)),
+            Vsaddvx(args) => ("vsadd.vx", opivx(args)),
+            Vsaddvi(args) => ("vsadd.vi", opivi(args)),
+            Vssubuvv(args) => ("vssubu.vv", opivv(args)),
+            Vssubuvx(args) => ("vssubu.vx", opivx(args)),
+            Vssubvv(args) => ("vssub.vv", opivv(args)),
+            Vssubvx(args) => ("vssub.vx", opivx(args)),
+            Vsllvv(args) => ("vsll.vv", opivv(args)),
+            Vsllvx(args) => ("vsll.vx", opivx(args)),
+            Vsllvi(args) => ("vsll.vi", opivi(args)),
+            Vsmulvv(args) => ("vsmul.vv", opivv(args)),
+            Vsmulvx(args) => ("vsmul.vx", opivx(args)),
+            Vmv1rv(args) => ("vmv1r.v", vmvnr(args)),
+            Vmv2rv(args) => ("vmv2r.v", vmvnr(args)),
+            Vmv4rv(args) => ("vmv4r.v", vmvnr(args)),
+            Vmv8rv(args) => ("vmv8r.v", vmvnr(args)),
+            Vsrlvv(args) => ("vsrl.vv", opivv(args)),
+            Vsrlvx(args) => ("vsrl.vx", opivx(args)),
+            Vsrlvi(args) => ("vsrl.vi", opivi(args)),
+            Vsravv(args) => ("vsra.vv", opivv(args)),
+            Vsravx(args) => ("vsra.vx", opivx(args)),
+            Vsravi(args) => ("vsra.vi", opivi(args)),
+            Vssrlvv(args) => ("vssrl.vv", opivv(args)),
+            Vssrlvx(args) => ("vssrl.vx", opivx(args)),
+            Vssrlvi(args) => ("vssrl.vi", opivi(args)),
+            Vssravv(args) => ("vssra.vv", opivv(args)),
+            Vssravx(args) => ("vssra.vx", opivx(args)),
+            Vssravi(args) => ("vssra.vi", opivi(args)),
+            Vnsrlwv(args) => ("vnsrl.wv", opivv(args)),
+            Vnsrlwx(args) => ("vnsrl.wx", opivx(args)),
+            Vnsrlwi(args) => ("vnsrl.wi", opivi(args)),
+            Vnsrawv(args) => ("vnsra.wv", opivv(args)),
+            Vnsrawx(args) => ("vnsra.wx", opivx(args)),
+            Vnsrawi(args) => ("vnsra.wi", opivi(args)),
+            Vnclipuwv(args) => ("vnclipu.wv", opivv(args)),
+            Vnclipuwx(args) => ("vnclipu.wx", opivx(args)),
+            Vnclipuwi(args) => ("vnclipu.wi", opivi(args)),
+            Vnclipwv(args) => ("vnclip.wv", opivv(args)),
+            Vnclipwx(args) => ("vnclip.wx", opivx(args)),
+            Vnclipwi(args) => ("vnclip.wi", opivi(args)),
+            Vwredsumuvs(args) => ("vwredsumu.vs", opivv(args)),
+            Vwredsumvs(args) => ("vwredsum.vs", opivv(args)),
+
+            Vredsumvs(args) => ("vredsum.vs", opmvv(args)),
+            Vredandvs(args) => ("vredand.vs", opmvv(args)),
+            Vredorvs(args) => ("vredor.vs", opmvv(args)),
+            Vredxorvs(args) => ("vredxor.vs", opmvv(args)),
+            Vredminuvs(args) => ("vredminu.vs", opmvv(args)),
+            Vredminvs(args) => ("vredmin.vs", opmvv(args)),
+            Vredmaxuvs(args) => ("vredmaxu.vs", opmvv(args)),
+            Vredmaxvs(args) => ("vredmax.vs", opmvv(args)),
+            Vaadduvv(args) => ("vaaddu.vv", opmvv(args)),
+            Vaadduvx(args) => ("vaaddu.vx", opmvx(args)),
+            Vaaddvv(args) => ("vaadd.vv", opmvv(args)),
+            Vaaddvx(args) => ("vaadd.vx", opmvx(args)),
+            Vasubuvv(args) => ("vasubu.vv", opmvv(args)),
+            Vasubuvx(args) => ("vasubu.vx", opmvx(args)),
+            Vasubvv(args) => ("vasub.vv", opmvv(args)),
+            Vasubvx(args) => ("vasub.vx", opmvx(args)),
+            Vslide1upvx(args) => ("vslide1up.vx", opmvx(args)),
+            Vslide1downvx(args) => ("vslide1down.vx", opmvx(args)),
+
+            Vmvxs(args) => ("vmv.x.s", vmvxs(args)),
+            Vcpopm(args) => ("vcpop.m", vwxunary0(args)),
+            Vfirstm(args) => ("vfirst.m", vwxunary0(args)),
+            Vmvsx(args) => ("vmv.s.x", vrxunary0(args)),
+            Vzextvf8(args) => ("vzext.vf8", opmvv_unary(args)),
+            Vsextvf8(args) => ("vsext.vf8", opmvv_unary(args)),
+            Vzextvf4(args) => ("vzext.vf4", opmvv_unary(args)),
+            Vsextvf4(args) => ("vsext.vf4", opmvv_unary(args)),
+            Vzextvf2(args) => ("vzext.vf2", opmvv_unary(args)),
+            Vsextvf2(args) => ("vsext.vf2", opmvv_unary(args)),
+            Vmsbfm(args) => ("vmsbf.m", opmvv_unary(args)),
+            Vmsofm(args) => ("vmsof.m", opmvv_unary(args)),
+            Vmsifm(args) => ("vmsif.m", opmvv_unary(args)),
+            Viotam(args) => ("viota.m", opmvv_unary(args)),
+            Vidv(args) => ("vid.v", vidv(args)),
+            Vcompressvm(args) => ("vcompress.vm", opmvv_maskless(args)),
+            Vmandnmm(args) => ("vmandn.mm", opmvv_maskless(args)),
+            Vmandmm(args) => ("vmand.mm", opmvv_maskless(args)),
+            Vmormm(args) => ("vmor.mm", opmvv_maskless(args)),
+            Vmxormm(args) => ("vmxor.mm", opmvv_maskless(args)),
+            Vmornmm(args) => ("vmorn.mm", opmvv_maskless(args)),
+            Vmnandmm(args) => ("vmnand.mm", opmvv_maskless(args)),
+            Vmnormm(args) => ("vmnor.mm", opmvv_maskless(args)),
+            Vmxnormm(args) => ("vmxnor.mm", opmvv_maskless(args)),
+
+            Vdivuvv(args) => ("vdivu.vv", opmvv(args)),
+            Vdivuvx(args) => ("vdivu.vx", opmvx(args)),
+            Vdivvv(args) => ("vdiv.vv", opmvv(args)),
+            Vdivvx(args) => ("vdiv.vx", opmvx(args)),
+            Vremuvv(args) => ("vremu.vv", opmvv(args)),
+            Vremuvx(args) => ("vremu.vx", opmvx(args)),
+            Vremvv(args) => ("vrem.vv", opmvv(args)),
+            Vremvx(args) => ("vrem.vx", opmvx(args)),
+            Vmulhuvv(args) => ("vmulhu.vv", opmvv(args)),
+            Vmulhuvx(args) => ("vmulhu.vx", opmvx(args)),
+            Vmulvv(args) => ("vmul.vv", opmvv(args)),
+            Vmulvx(args) => ("vmul.vx", opmvx(args)),
+            Vmulhsuvv(args) => ("vmulhsu.vv", opmvv(args)),
+            Vmulhsuvx(args) => ("vmulhsu.vx", opmvx(args)),
+            Vmulhvv(args) => ("vmulh.vv", opmvv(args)),
+            Vmulhvx(args) => ("vmulh.vx", opmvx(args)),
+            Vmaddvv(args) => ("vmadd.vv", opmvv_fma(args)),
+            Vmaddvx(args) => ("vmadd.vx", opmvx_fma(args)),
+            Vnmsubvv(args) => ("vnmsub.vv", opmvv_fma(args)),
+            Vnmsubvx(args) => ("vnmsub.vx", opmvx_fma(args)),
+            Vmaccvv(args) => ("vmacc.vv", opmvv_fma(args)),
+            Vmaccvx(args) => ("vmacc.vx", opmvx_fma(args)),
+            Vnmsacvv(args) => ("vnmsac.vv", opmvv_fma(args)),
+            Vnmsacvx(args) => ("vnmsac.vx", opmvx_fma(args)),
+            Vwadduvv(args) => ("vwaddu.vv", opmvv(args)),
+            Vwadduvx(args) => ("vwaddu.vx", opmvx(args)),
+            Vwaddvv(args) => ("vwadd.vv", opmvv(args)),
+            Vwaddvx(args) => ("vwadd.vx", opmvx(args)),
+            Vwsubuvv(args) => ("vwsubu.vv", opmvv(args)),
+            Vwsubuvx(args) => ("vwsubu.vx", opmvx(args)),
+            Vwsubvv(args) => ("vwsub.vv", opmvv(args)),
+            Vwsubvx(args) => ("vwsub.vx", opmvx(args)),
+            Vwadduwv(args) => ("vwaddu.wv", opmvv(args)),
+            Vwadduwx(args) => ("vwaddu.wx", opmvx(args)),
+            Vwaddwv(args) => ("vwadd.wv", opmvv(args)),
+            Vwaddwx(args) => ("vwadd.wx", opmvx(args)),
+            Vwsubuwv(args) => ("vwsubu.wv", opmvv(args)),
+            Vwsubuwx(args) => ("vwsubu.wx", opmvx(args)),
+            Vwsubwv(args) => ("vwsub.wv", opmvv(args)),
+            Vwsubwx(args) => ("vwsub.wx", opmvx(args)),
+            Vwmuluvv(args) => ("vwmulu.vv", opmvv(args)),
+            Vwmuluvx(args) => ("vwmulu.vx", opmvx(args)),
+            Vwmulsuvv(args) => ("vwmulsu.vv", opmvv(args)),
+            Vwmulsuvx(args) => ("vwmulsu.vx", opmvx(args)),
+            Vwmulvv(args) => ("vwmul.vv", opmvv(args)),
+            Vwmulvx(args) => ("vwmul.vx", opmvx(args)),
+            Vwmaccuvv(args) => ("vwmaccu.vv", opmvv_fma(args)),
+            Vwmaccuvx(args) => ("vwmaccu.vx", opmvx_fma(args)),
+            Vwmaccvv(args) => ("vwmacc.vv", opmvv_fma(args)),
+            Vwmaccvx(args) => ("vwmacc.vx", opmvx_fma(args)),
+            Vwmaccusvx(args) => ("vwmaccus.vx", opmvx_fma(args)),
+            Vwmaccsuvv(args) => ("vwmaccsu.vv", opmvv_fma(args)),
+            Vwmaccsuvx(args) => ("vwmaccsu.vx", opmvx_fma(args)),
+
+            Vfaddvv(args) => ("vfadd.vv", opfvv(args)),
+            Vfaddvf(args) => ("vfadd.vf", opfvf(args)),
+            Vfredusumvs(args) => ("vfredusum.vs", opfvv(args)),
+            Vfsubvv(args) => ("vfsub.vv", opfvv(args)),
+            Vfsubvf(args) => ("vfsub.vf", opfvf(args)),
+            Vfredosumvs(args) => ("vfredosum.vs", opfvv(args)),
+            Vfminvv(args) => ("vfmin.vv", opfvv(args)),
+            Vfminvf(args) => ("vfmin.vf", opfvf(args)),
+            Vfredminvs(args) => ("vfredmin.vs", opfvv(args)),
+            Vfmaxvv(args) => ("vfmax.vv", opfvv(args)),
+            Vfmaxvf(args) => ("vfmax.vf", opfvf(args)),
+            Vfredmaxvs(args) => ("vfredmax.vs", opfvv(args)),
+            Vfsgnjvv(args) => ("vfsgnj.vv", opfvv(args)),
+            Vfsgnjvf(args) => ("vfsgnj.vf", opfvf(args)),
+            Vfsgnjnvv(args) => ("vfsgnjn.vv", opfvv(args)),
+            Vfsgnjnvf(args) => ("vfsgnjn.vf", opfvf(args)),
+            Vfsgnjxvv(args) => ("vfsgnjx.vv", opfvv(args)),
+            Vfsgnjxvf(args) => ("vfsgnjx.vf", opfvf(args)),
+            Vfslide1upvf(args) => ("vfslide1up.vf", opfvf(args)),
+            Vfslide1downvf(args) => ("vfslide1down.vf", opfvf(args)),
+            Vfmvfs(args) => ("vfmv.f.s", vwfunary0(args)),
+            Vfmvsf(args) => ("vfmv.s.f", vrfunary0(args)),
+
+            Vfcvtxufv(args) => ("vfcvt.xu.f.v", opfvv_unary(args)),
+            Vfcvtxfv(args) => ("vfcvt.x.f.v", opfvv_unary(args)),
+            Vfcvtfxuv(args) => ("vfcvt.f.xu.v", opfvv_unary(args)),
+            Vfcvtfxv(args) => ("vfcvt.f.x.v", opfvv_unary(args)),
+            VfcvtRtzxufv(args) => ("vfcvt.rtz.xu.f.v", opfvv_unary(args)),
+            VfcvtRtzxfv(args) => ("vfcvt.rtz.x.f.v", opfvv_unary(args)),
+            Vfwcvtxufv(args) => ("vfwcvt.xu.f.v", opfvv_unary(args)),
+            Vfwcvtxfv(args) => ("vfwcvt.x.f.v", opfvv_unary(args)),
+            Vfwcvtfxuv(args) => ("vfwcvt.f.xu.v", opfvv_unary(args)),
+            Vfwcvtfxv(args) => ("vfwcvt.f.x.v", opfvv_unary(args)),
+            Vfwcvtffv(args) => ("vfwcvt.f.f.v", opfvv_unary(args)),
+            VfwcvtRtzxufv(args) => ("vfwcvt.rtz.xu.f.v", opfvv_unary(args)),
+            VfwcvtRtzxfv(args) => ("vfwcvt.rtz.x.f.v", opfvv_unary(args)),
+            Vfncvtxufw(args) => ("vfncvt.xu.f.w", opfvv_unary(args)),
+            Vfncvtxfw(args) => ("vfncvt.x.f.w", opfvv_unary(args)),
+            Vfncvtfxuw(args) => ("vfncvt.f.xu.w", opfvv_unary(args)),
+            Vfncvtfxw(args) => ("vfncvt.f.x.w", opfvv_unary(args)),
+            Vfncvtffw(args) => ("vfncvt.f.f.w", opfvv_unary(args)),
+            VfncvtRodffw(args) => ("vfncvt.rod.f.f.w", opfvv_unary(args)),
+            VfncvtRtzxufw(args) => ("vfncvt.rtz.xu.f.w", opfvv_unary(args)),
+            VfncvtRtzxfw(args) => ("vfncvt.rtz.x.f.w", opfvv_unary(args)),
+            Vfsqrtv(args) => ("vfsqrt.v", opfvv_unary(args)),
+            Vfrsqrt7v(args) => ("vfrsqrt7.v", opfvv_unary(args)),
+            Vfrec7v(args) => ("vfrec7.v", opfvv_unary(args)),
+            Vfclassv(args) => ("vfclass.v", opfvv_unary(args)),
+            Vfmergevfm(args) => ("vfmerge.vfm", opfvf_v0(args)),
+            Vfmvvf(args) => ("vfmv.v.f", vrfunary0(args)),
+
+            Vmfeqvv(args) => ("vmfeq.vv", opfvv(args)),
+            Vmfeqvf(args) => ("vmfeq.vf", opfvf(args)),
+            Vmflevv(args) => ("vmfle.vv", opfvv(args)),
+            Vmflevf(args) => ("vmfle.vf", opfvf(args)),
+            Vmfltvv(args) => ("vmflt.vv", opfvv(args)),
+            Vmfltvf(args) => ("vmflt.vf", opfvf(args)),
+            Vmfnevv(args) => ("vmfne.vv", opfvv(args)),
+            Vmfnevf(args) => ("vmfne.vf", opfvf(args)),
+            Vmfgtvf(args) => ("vmfgt.vf", opfvf(args)),
+            Vmfgevf(args) => ("vmfge.vf", opfvf(args)),
+            Vfdivvv(args) => ("vfdiv.vv", opfvv(args)),
+            Vfdivvf(args) => ("vfdiv.vf", opfvf(args)),
+            Vfrdivvf(args) => ("vfrdiv.vf", opfvf(args)),
+            Vfmulvv(args) => ("vfmul.vv", opfvv(args)),
+            Vfmulvf(args) => ("vfmul.vf", opfvf(args)),
+            Vfrsubvf(args) => ("vfrsub.vf", opfvf(args)),
+            Vfmaddvv(args) => ("vfmadd.vv", opfvv_fma(args)),
+            Vfmaddvf(args) => ("vfmadd.vf", opfvf_fma(args)),
+            Vfnmaddvv(args) => ("vfnmadd.vv", opfvv_fma(args)),
+            Vfnmaddvf(args) => ("vfnmadd.vf", opfvf_fma(args)),
+            Vfmsubvv(args) => ("vfmsub.vv", opfvv_fma(args)),
+            Vfmsubvf(args) => ("vfmsub.vf", opfvf_fma(args)),
+            Vfnmsubvv(args) => ("vfnmsub.vv", opfvv_fma(args)),
+            Vfnmsubvf(args) => ("vfnmsub.vf", opfvf_fma(args)),
+            Vfmaccvv(args) => ("vfmacc.vv", opfvv_fma(args)),
+            Vfmaccvf(args) => ("vfmacc.vf", opfvf_fma(args)),
+            Vfnmaccvv(args) => ("vfnmacc.vv", opfvv_fma(args)),
+            Vfnmaccvf(args) => ("vfnmacc.vf", opfvf_fma(args)),
+            Vfmsacvv(args) => ("vfmsac.vv", opfvv_fma(args)),
+            Vfmsacvf(args) => ("vfmsac.vf", opfvf_fma(args)),
+            Vfnmsacvv(args) => ("vfnmsac.vv", opfvv_fma(args)),
+            Vfnmsacvf(args) => ("vfnmsac.vf", opfvf_fma(args)),
+            Vfwaddvv(args) => ("vfwadd.vv", opfvv(args)),
+            Vfwaddvf(args) => ("vfwadd.vf", opfvf(args)),
+            Vfwredusumvs(args) => ("vfwredusum.vs", opfvv(args)),
+            Vfwsubvv(args) => ("vfwsub.vv", opfvv(args)),
+            Vfwsubvf(args) => ("vfwsub.vf", opfvf(args)),
+            Vfwredosumvs(args) => ("vfwredosum.vs", opfvv(args)),
+            Vfwaddwv(args) => ("vfwadd.wv", opfvv(args)),
+            Vfwaddwf(args) => ("vfwadd.wf", opfvf(args)),
+            Vfwsubwv(args) => ("vfwsub.wv", opfvv(args)),
+            Vfwsubwf(args) => ("vfwsub.wf", opfvf(args)),
+            Vfwmulvv(args) => ("vfwmul.vv", opfvv(args)),
+            Vfwmulvf(args) => ("vfwmul.vf", opfvf(args)),
+            Vfwmaccvv(args) => ("vfwmacc.vv", opfvv_fma(args)),
+            Vfwmaccvf(args) => ("vfwmacc.vf", opfvf_fma(args)),
+            Vfwnmaccvv(args) => ("vfwnmacc.vv", opfvv_fma(args)),
+            Vfwnmaccvf(args) => ("vfwnmacc.vf", opfvf_fma(args)),
+            Vfwmsacvv(args) => ("vfwmsac.vv", opfvv_fma(args)),
+            Vfwmsacvf(args) => ("vfwmsac.vf", opfvf_fma(args)),
+            Vfwnmsacvv(args) => ("vfwnmsac.vv", opfvv_fma(args)),
+            Vfwnmsacvf(args) => ("vfwnmsac.vf", opfvf_fma(args)),
+
+            Fusion(_) => unreachable!("fused instructions are disassembled one by one"),
+        };
+
+        format!("{} {}", mnemonic, operands)
+    }
+
+    fn target(&self, offset: i32, address: usize) -> String {
+        let target = address.wrapping_add_signed(offset as isize);
+
+        match self.labels.get(&target) {
+            Some(label) => label.clone(),
+            None => format!("{:#x}", target),
+        }
+    }
+
+    fn branch(&self, format::S { rs1, rs2, imm12 }: &format::S, address: usize) -> String {
+        format!("{}, {}, {}", x(*rs1), x(*rs2), self.target(*imm12, address))
+    }
+
+    /// Label addressed by an `auipc` and the instruction completing its offset, e.g. in `call`
+    fn pcrel_label(&self, auipc: &Instruction, part: &Instruction, address: usize) -> Option<&str> {
+        let Instruction::Auipc(format::U { rd, imm20 }) = auipc else {
+            return None;
+        };
+
+        let (Instruction::Addi(format::I { rs1, imm12, .. })
+        | Instruction::Jalr(format::I { rs1, imm12, .. })) = part
+        else {
+            return None;
+        };
+
+        let offset = (i64::from(*imm20) << 12) + i64::from(*imm12);
+
+        (rs1 == rd)
+            .then(|| {
+                self.labels
+                    .get(&address.wrapping_add_signed(offset as isize))
+            })
+            .flatten()
+            .map(String::as_str)
+    }
+}
+
+const INTEGER_REGISTERS: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+const FLOAT_REGISTERS: [&str; 32] = [
+    "ft0", "ft1", "ft2", "ft3", "ft4", "ft5", "ft6", "ft7", "fs0", "fs1", "fa0", "fa1", "fa2",
+    "fa3", "fa4", "fa5", "fa6", "fa7", "fs2", "fs3", "fs4", "fs5", "fs6", "fs7", "fs8", "fs9",
+    "fs10", "fs11", "ft8", "ft9", "ft10", "ft11",
+];
+
+fn x(register: usize) -> &'static str {
+    INTEGER_REGISTERS[register % 32]
+}
+
+fn f(register: usize) -> &'static str {
+    FLOAT_REGISTERS[register % 32]
+}
+
+fn v(register: usize) -> String {
+    format!("v{}", register)
+}
+
+fn csr(csr: usize) -> String {
+    let name = match csr {
+        alias::INSTRET => "instret",
+        alias::CYCLE => "cycle",
+        alias::TIME => "time",
+        alias::MARCHID => "marchid",
+        alias::FCSR => "fcsr",
+        alias::FFLAGS => "fflags",
+        alias::FRM => "frm",
+        alias::MSTATUS => "mstatus",
+        alias::VSSTATUS => "vsstatus",
+        alias::VTYPE => "vtype",
+        alias::VL => "vl",
+        alias::VLENB => "vlenb",
+        alias::VSTART => "vstart",
+        alias::VXRM => "vxrm",
+        alias::VXSAT => "vxsat",
+        alias::VCSR => "vcsr",
+        _ => return format!("{:#x}", csr),
+    };
+
+    name.to_owned()
+}
+
+/// Renders the vtype immediate like it is written in `vsetvli`, e.g. `e32, m2, ta, mu`
+fn vtype(vtypei: u32) -> String {
+    let sew = match (vtypei >> 3) & 0b111 {
+        0b000 => "e8",
+        0b001 => "e16",
+        0b010 => "e32",
+        0b011 => "e64",
+        _ => return format!("{:#x}", vtypei),
+    };
+
+    let lmul = match vtypei & 0b111 {
+        0b101 => "mf8",
+        0b110 => "mf4",
+        0b111 => "mf2",
+        0b000 => "m1",
+        0b001 => "m2",
+        0b010 => "m4",
+        0b011 => "m8",
+        _ => return format!("{:#x}", vtypei),
+    };
+
+    let tail = if vtypei & (1 << 6) != 0 { "ta" } else { "tu" };
+    let mask = if vtypei & (1 << 7) != 0 { "ma" } else { "mu" };
+
+    format!("{}, {}, {}, {}", sew, lmul, tail, mask)
+}
+
+fn bits(eew: &BaseSew) -> usize {
+    match eew {
+        BaseSew::E8 => 8,
+        BaseSew::E16 => 16,
+        BaseSew::E32 => 32,
+        BaseSew::E64 => 64,
+    }
+}
+
+/// In eeric `vm` is set for instructions masked by v0
+fn mask(vm: bool) -> &'static str {
+    if vm {
+        ", v0.t"
+    } else {
+        ""
+    }
+}
+
+fn r(format::R { rd, rs1, rs2 }: &format::R) -> String {
+    format!("{}, {}, {}", x(*rd), x(*rs1), x(*rs2))
+}
+
+fn i(format::I { rd, rs1, imm12 }: &format::I) -> String {
+    format!("{}, {}, {}", x(*rd), x(*rs1), imm12)
+}
+
+fn load(format::I { rd, rs1, imm12 }: &format::I) -> String {
+    format!("{}, {}({})", x(*rd), imm12, x(*rs1))
+}
+
+fn store(format::S { rs1, rs2, imm12 }: &format::S) -> String {
+    format!("{}, {}({})", x(*rs2), imm12, x(*rs1))
+}
+
+fn upper(format::U { rd, imm20 }: &format::U) -> String {
+    format!("{}, {:#x}", x(*rd), imm20 & 0xfffff)
+}
+
+fn csrr(
+    format::Csrr {
+        rd,
+        rs1,
+        csr: number,
+    }: &format::Csrr,
+) -> String {
+    format!("{}, {}, {}", x(*rd), csr(*number), x(*rs1))
+}
+
+fn csri(
+    format::Csri {
+        rd,
+        uimm,
+        csr: number,
+    }: &format::Csri,
+) -> String {
+    format!("{}, {}, {}", x(*rd), csr(*number), uimm)
+}
+
+fn fl(format::I { rd, rs1, imm12 }: &format::I) -> String {
+    format!("{}, {}({})", f(*rd), imm12, x(*rs1))
+}
+
+fn fs(format::S { rs1, rs2, imm12 }: &format::S) -> String {
+    format!("{}, {}({})", f(*rs2), imm12, x(*rs1))
+}
+
+fn fr(format::R { rd, rs1, rs2 }: &format::R) -> String {
+    format!("{}, {}, {}", f(*rd), f(*rs1), f(*rs2))
+}
+
+fn frs(format::R { rd, rs1, .. }: &format::R) -> String {
+    format!("{}, {}", f(*rd), f(*rs1))
+}
+
+fn frs_to_x(format::R { rd, rs1, .. }: &format::R) -> String {
+    format!("{}, {}", x(*rd), f(*rs1))
+}
+
+fn frs_to_f(format::R { rd, rs1, .. }: &format::R) -> String {
+    format!("{}, {}", f(*rd), x(*rs1))
+}
+
+fn frx(format::R { rd, rs1, rs2 }: &format::R) -> String {
+    format!("{}, {}, {}", x(*rd), f(*rs1), f(*rs2))
+}
+
+fn r4(format::R4 { rd, rs1, rs2, rs3 }: &format::R4) -> String {
+    format!("{}, {}, {}, {}", f(*rd), f(*rs1), f(*rs2), f(*rs3))
+}
+
+fn vsetvli(format::Vsetvli { rd, rs1, vtypei }: &format::Vsetvli) -> String {
+    format!("{}, {}, {}", x(*rd), x(*rs1), vtype(*vtypei))
+}
+
+fn vsetivli(format::Vsetivli { rd, uimm, vtypei }: &format::Vsetivli) -> String {
+    format!("{}, {}, {}", x(*rd), uimm, vtype(*vtypei))
+}
+
+fn vsetvl(format::Vsetvl { rd, rs1, rs2 }: &format::Vsetvl) -> String {
+    format!("{}, {}, {}", x(*rd), x(*rs1), x(*rs2))
+}
+
+fn vl(format::Vl { vd, rs1, vm }: &format::Vl) -> String {
+    format!("{}, ({}){}", v(*vd), x(*rs1), mask(*vm))
+}
+
+fn vlm(format::Vl { vd, rs1, .. }: &format::Vl) -> String {
+    format!("{}, ({})", v(*vd), x(*rs1))
+}
+
+fn vls(format::Vls { vd, rs1, rs2, vm }: &format::Vls) -> String {
+    format!("{}, ({}), {}{}", v(*vd), x(*rs1), x(*rs2), mask(*vm))
+}
+
+fn vlx(format::Vlx { vd, rs1, vs2, vm }: &format::Vlx) -> String {
+    format!("{}, ({}), {}{}", v(*vd), x(*rs1), v(*vs2), mask(*vm))
+}
+
+fn vlr(format::Vlr { vd, rs1 }: &format::Vlr) -> String {
+    format!("{}, ({})", v(*vd), x(*rs1))
+}
+
+fn vs(format::Vs { vs3, rs1, vm }: &format::Vs) -> String {
+    format!("{}, ({}){}", v(*vs3), x(*rs1), mask(*vm))
+}
+
+fn vsm(format::Vs { vs3, rs1, .. }: &format::Vs) -> String {
+    format!("{}, ({})", v(*vs3), x(*rs1))
+}
+
+fn vss(format::Vss { vs3, rs1, rs2, vm }: &format::Vss) -> String {
+    format!("{}, ({}), {}{}", v(*vs3), x(*rs1), x(*rs2), mask(*vm))
+}
+
+fn vsx(format::Vsx { vs3, rs1, vs2, vm }: &format::Vsx) -> String {
+    format!("{}, ({}), {}{}", v(*vs3), x(*rs1), v(*vs2), mask(*vm))
+}
+
+fn vsr(format::Vsr { vs3, rs1 }: &format::Vsr) -> String {
+    format!("{}, ({})", v(*vs3), x(*rs1))
+}
+
+fn opivv(format::Opivv { vd, vs1, vs2, vm }: &format::Opivv) -> String {
+    format!("{}, {}, {}{}", v(*vd), v(*vs2), v(*vs1), mask(*vm))
+}
+
+/// Carry-in and merge variants always read v0
+fn opivv_v0(format::Opivv { vd, vs1, vs2, .. }: &format::Opivv) -> String {
+    format!("{}, {}, {}, v0", v(*vd), v(*vs2), v(*vs1))
+}
+
+fn opivv_maskless(format::Opivv { vd, vs1, vs2, .. }: &format::Opivv) -> String {
+    format!("{}, {}, {}", v(*vd), v(*vs2), v(*vs1))
+}
+
+fn opivv_vmv(format::Opivv { vd, vs1, .. }: &format::Opivv) -> String {
+    format!("{}, {}", v(*vd), v(*vs1))
+}
+
+fn opivx(format::Opivx { vd, rs1, vs2, vm }: &format::Opivx) -> String {
+    format!("{}, {}, {}{}", v(*vd), v(*vs2), x(*rs1), mask(*vm))
+}
+
+fn opivx_v0(format::Opivx { vd, rs1, vs2, .. }: &format::Opivx) -> String {
+    format!("{}, {}, {}, v0", v(*vd), v(*vs2), x(*rs1))
+}
+
+fn opivx_maskless(format::Opivx { vd, rs1, vs2, .. }: &format::Opivx) -> String {
+    format!("{}, {}, {}", v(*vd), v(*vs2), x(*rs1))
+}
+
+fn opivx_vmv(format::Opivx { vd, rs1, .. }: &format::Opivx) -> String {
+    format!("{}, {}", v(*vd), x(*rs1))
+}
+
+fn opivi(format::Opivi { vd, imm5, vs2, vm }: &format::Opivi) -> String {
+    format!("{}, {}, {}{}", v(*vd), v(*vs2), imm5, mask(*vm))
+}
+
+fn opivi_v0(format::Opivi { vd, imm5, vs2, .. }: &format::Opivi) -> String {
+    format!("{}, {}, {}, v0", v(*vd), v(*vs2), imm5)
+}
+
+fn opivi_maskless(format::Opivi { vd, imm5, vs2, .. }: &format::Opivi) -> String {
+    format!("{}, {}, {}", v(*vd), v(*vs2), imm5)
+}
+
+fn opivi_vmv(format::Opivi { vd, imm5, .. }: &format::Opivi) -> String {
+    format!("{}, {}", v(*vd), imm5)
+}
+
+fn vmvnr(format::Opivi { vd, vs2, .. }: &format::Opivi) -> String {
+    format!("{}, {}", v(*vd), v(*vs2))
+}
+
+fn opmvv(format::Opmvv { dest, vs1, vs2, vm }: &format::Opmvv) -> String {
+    format!("{}, {}, {}{}", v(*dest), v(*vs2), v(*vs1), mask(*vm))
+}
+
+fn opmvv_maskless(format::Opmvv { dest, vs1, vs2, .. }: &format::Opmvv) -> String {
+    format!("{}, {}, {}", v(*dest), v(*vs2), v(*vs1))
+}
+
+/// Multiply-add instructions take the multiplicand before vs2
+fn opmvv_fma(format::Opmvv { dest, vs1, vs2, vm }: &format::Opmvv) -> String {
+    format!("{}, {}, {}{}", v(*dest), v(*vs1), v(*vs2), mask(*vm))
+}
+
+/// Unary instructions select the operation with vs1, which is not an operand
+fn opmvv_unary(format::Opmvv { dest, vs2, vm, .. }: &format::Opmvv) -> String {
+    format!("{}, {}{}", v(*dest), v(*vs2), mask(*vm))
+}
+
+fn opmvx(format::Opmvx { dest, rs1, vs2, vm }: &format::Opmvx) -> String {
+    format!("{}, {}, {}{}", v(*dest), v(*vs2), x(*rs1), mask(*vm))
+}
+
+fn opmvx_fma(format::Opmvx { dest, rs1, vs2, vm }: &format::Opmvx) -> String {
+    format!("{}, {}, {}{}", v(*dest), x(*rs1), v(*vs2), mask(*vm))
+}
+
+fn vidv(format::Opmvv { dest, vm, .. }: &format::Opmvv) -> String {
+    format!("{}{}", v(*dest), mask(*vm))
+}
+
+fn vmvxs(format::Opmvv { dest, vs2, .. }: &format::Opmvv) -> String {
+    format!("{}, {}", x(*dest), v(*vs2))
+}
+
+fn vwxunary0(format::Opmvv { dest, vs2, vm, .. }: &format::Opmvv) -> String {
+    format!("{}, {}{}", x(*dest), v(*vs2), mask(*vm))
+}
+
+fn vrxunary0(format::Opmvx { dest, rs1, .. }: &format::Opmvx) -> String {
+    format!("{}, {}", v(*dest), x(*rs1))
+}
+
+fn opfvv(format::Opfvv { dest, vs1, vs2, vm }: &format::Opfvv) -> String {
+    format!("{}, {}, {}{}", v(*dest), v(*vs2), v(*vs1), mask(*vm))
+}
+
+fn opfvv_fma(format::Opfvv { dest, vs1, vs2, vm }: &format::Opfvv) -> String {
+    format!("{}, {}, {}{}", v(*dest), v(*vs1), v(*vs2), mask(*vm))
+}
+
+fn opfvv_unary(format::Opfvv { dest, vs2, vm, .. }: &format::Opfvv) -> String {
+    format!("{}, {}{}", v(*dest), v(*vs2), mask(*vm))
+}
+
+fn opfvf(format::Opfvf { vd, rs1, vs2, vm }: &format::Opfvf) -> String {
+    format!("{}, {}, {}{}", v(*vd), v(*vs2), f(*rs1), mask(*vm))
+}
+
+fn opfvf_v0(format::Opfvf { vd, rs1, vs2, .. }: &format::Opfvf) -> String {
+    format!("{}, {}, {}, v0", v(*vd), v(*vs2), f(*rs1))
+}
+
+fn opfvf_fma(format::Opfvf { vd, rs1, vs2, vm }: &format::Opfvf) -> String {
+    format!("{}, {}, {}{}", v(*vd), f(*rs1), v(*vs2), mask(*vm))
+}
+
+fn vwfunary0(format::Opfvv { dest, vs2, .. }: &format::Opfvv) -> String {
+    format!("{}, {}", f(*dest), v(*vs2))
+}
+
+fn vrfunary0(format::Opfvf { vd, rs1, .. }: &format::Opfvf) -> String {
+    format!("{}, {}", v(*vd), f(*rs1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::decoder::{expression::Context, Decoder};
+
+    fn decode(line: &str, labels: &HashMap<String, usize>) -> Instruction {
+        Decoder::decode_text_section(line, &Context::new(labels, labels, 8)).unwrap()
+    }
+
+    #[test]
+    fn renders_canonical_assembly() {
+        let labels = HashMap::from([("target".to_owned(), 16)]);
+        let disassembler = Disassembler::new(&labels);
+
+        let cases = [
+            ("mv a0, a1", "addi a0, a1, 0"),
+            ("beqz x10, target", "beq a0, zero, target"),
+            ("j 0x40", "jal zero, 0x40"),
+            ("call target", "auipc ra, 0x0\njalr ra, 8(ra)  # target"),
+            ("csrr a0, vlenb", "csrrs a0, vlenb, zero"),
+            ("fsqrt.d fa0, f11", "fsqrt.d fa0, fa1"),
+            (
+                "vsetvli t0, a0, e32, m2, ta, mu",
+                "vsetvli t0, a0, e32, m2, ta, mu",
+            ),
+            ("vfneg.v v1, v2", "vfsgnjn.vv v1, v2, v2"),
+            (
+                "vmsge.vx v1, v2, a0",
+                "vmslt.vx v1, v2, a0\nvmnand.mm v1, v1, v1",
+            ),
+            ("vle32.v v1, (a0), v0.t", "vle32.v v1, (a0), v0.t"),
+            ("vmerge.vvm v1, v2, v3, v0", "vmerge.vvm v1, v2, v3, v0"),
+            ("vfmacc.vf v1, fa0, v2", "vfmacc.vf v1, fa0, v2"),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(
+                disassembler.disassemble(&decode(line, &labels), 8),
+                expected,
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn disassembly_decodes_to_the_same_instruction() {
+        let labels = HashMap::from([("target".to_owned(), 16)]);
+        let disassembler = Disassembler::new(&labels);
+
+        let lines = [
+            "sd ra, -8(sp)",
+            "lui a0, 0x12345",
+            "slli a0, a0, 12",
+            "bltu a0, a1, target",
+            "csrrwi zero, vxrm, 2",
+            "fld fs0, 16(a0)",
+            "fmadd.d fa0, fa1, fa2, fa3",
+            "fcvt.l.d a0, fa0",
+            "feq.s a0, fa0, fa1",
+            "vsetivli zero, 4, e8, mf2, tu, ma",
+            "vadd.vi v1, v2, -3, v0.t",
+            "vwmacc.vx v2, a0, v4",
+            "vfmv.v.f v1, fa0",
+            "vcpop.m a0, v2",
+            "vid.v v3, v0.t",
+            "vlsseg2e16.v v4, (a0), a1",
+            "vsoxei32.v v1, (a0), v2",
+            "vl2re64.v v2, (a0)",
+            "vmsbc.vvm v1, v2, v3, v0",
+        ];
+
+        for line in lines {
+            let instruction = decode(line, &labels);
+            let disassembly = disassembler.disassemble(&instruction, 8);
+
+            assert_eq!(decode(&disassembly, &labels), instruction, "{}", line);
+        }
+    }
+}
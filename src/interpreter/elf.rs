@@ -0,0 +1,1105 @@
+use std::collections::HashMap;
+
+use eeric_core::prelude::*;
+
+use super::{
+    binary::BinaryDecoder,
+    decoder::Section,
+    diagnostic::{Code, Diagnostic},
+    encoder::Encoder,
+    CompilationResult, Listing, MemoryLayout, SourceMap, Symbol,
+};
+
+const EI_CLASS_64: u8 = 2;
+const EI_DATA_LE: u8 = 1;
+const ET_EXEC: u16 = 2;
+const EM_RISCV: u16 = 243;
+
+const EHDR_SIZE: usize = 64;
+const PAGE_SIZE: usize = 0x1000;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+
+const SHF_WRITE: u64 = 1;
+const SHF_ALLOC: u64 = 2;
+const SHF_EXECINSTR: u64 = 4;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: u16 = 0xff00;
+const SHN_ABS: u16 = 0xfff1;
+
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+
+const PHDR_SIZE: usize = 56;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+
+/// Headers of a statically linked RV64 executable
+struct Elf<'a> {
+    bytes: &'a [u8],
+    entry: u64,
+    segments: Vec<Segment>,
+    sections: Vec<SectionHeader>,
+}
+
+struct Segment {
+    kind: u32,
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+    memsz: u64,
+}
+
+struct SectionHeader {
+    name: String,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+}
+
+struct SymbolEntry {
+    name: String,
+    kind: u8,
+    section: u16,
+    value: u64,
+    size: u64,
+}
+
+impl<'a> Elf<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self, Diagnostic> {
+        if bytes.get(..4) != Some(b"\x7fELF") {
+            return Err(Diagnostic::error(Code::InvalidElf, "Not an ELF file"));
+        }
+
+        if read::<1>(bytes, 4)? != [EI_CLASS_64] || read::<1>(bytes, 5)? != [EI_DATA_LE] {
+            return Err(Diagnostic::error(
+                Code::InvalidElf,
+                "Only little-endian 64-bit ELF files are supported",
+            ));
+        }
+
+        if u16_at(bytes, 18)? != EM_RISCV {
+            return Err(Diagnostic::error(
+                Code::InvalidElf,
+                "ELF file is not built for RISC-V",
+            ));
+        }
+
+        if u16_at(bytes, 16)? != ET_EXEC {
+            return Err(
+                Diagnostic::error(Code::InvalidElf, "ELF file is not an executable")
+                    .with_help("link the program, e.g. with `-static`"),
+            );
+        }
+
+        let phoff = u64_at(bytes, 32)?;
+        let shoff = u64_at(bytes, 40)?;
+        let phnum = u16_at(bytes, 56)? as u64;
+        let shnum = u16_at(bytes, 60)? as u64;
+        let shstrndx = u16_at(bytes, 62)? as usize;
+
+        let segments = slice(bytes, phoff, phnum * PHDR_SIZE as u64)?
+            .chunks_exact(PHDR_SIZE)
+            .map(|phdr| {
+                Ok(Segment {
+                    kind: u32_at(phdr, 0)?,
+                    offset: u64_at(phdr, 8)?,
+                    vaddr: u64_at(phdr, 16)?,
+                    filesz: u64_at(phdr, 32)?,
+                    memsz: u64_at(phdr, 40)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Diagnostic>>()?;
+
+        let headers = slice(bytes, shoff, shnum * SHDR_SIZE as u64)?
+            .chunks_exact(SHDR_SIZE)
+            .collect::<Vec<_>>();
+
+        let names = match headers.get(shstrndx) {
+            Some(shdr) => slice(bytes, u64_at(shdr, 24)?, u64_at(shdr, 32)?)?,
+            None => &[],
+        };
+
+        let sections = headers
+            .iter()
+            .map(|shdr| {
+                Ok(SectionHeader {
+                    name: string_at(names, u32_at(shdr, 0)? as usize),
+                    kind: u32_at(shdr, 4)?,
+                    flags: u64_at(shdr, 8)?,
+                    addr: u64_at(shdr, 16)?,
+                    offset: u64_at(shdr, 24)?,
+                    size: u64_at(shdr, 32)?,
+                    link: u32_at(shdr, 40)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Diagnostic>>()?;
+
+        Ok(Self {
+            bytes,
+            entry: u64_at(bytes, 24)?,
+            segments,
+            sections,
+        })
+    }
+
+    fn contents(&self, section: &SectionHeader) -> Result<&'a [u8], Diagnostic> {
+        match section.kind {
+            SHT_NOBITS => Ok(&[]),
+            _ => slice(self.bytes, section.offset, section.size),
+        }
+    }
+
+    fn symbols(&self) -> Result<Vec<SymbolEntry>, Diagnostic> {
+        let Some(symtab) = self
+            .sections
+            .iter()
+            .find(|section| section.kind == SHT_SYMTAB)
+        else {
+            return Ok(Vec::new());
+        };
+
+        let names = match self.sections.get(symtab.link as usize) {
+            Some(strtab) => self.contents(strtab)?,
+            None => &[],
+        };
+
+        self.contents(symtab)?
+            .chunks_exact(SYM_SIZE)
+            .map(|sym| {
+                Ok(SymbolEntry {
+                    name: string_at(names, u32_at(sym, 0)? as usize),
+                    kind: read::<1>(sym, 4)?[0] & 0xf,
+                    section: u16_at(sym, 6)?,
+                    value: u64_at(sym, 8)?,
+                    size: u64_at(sym, 16)?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Loads `PT_LOAD` segments into memory and decodes `.text`
+///
+/// Instructions stay indexed by `pc / 4`, so everything below `.text` is filled with `nop`s.
+pub fn load(bytes: &[u8], memory_size: usize) -> Result<CompilationResult, Vec<Diagnostic>> {
+    let elf = Elf::parse(bytes).map_err(|diagnostic| vec![diagnostic])?;
+
+    if elf
+        .segments
+        .iter()
+        .any(|segment| matches!(segment.kind, PT_DYNAMIC | PT_INTERP))
+    {
+        return Err(vec![Diagnostic::error(
+            Code::InvalidElf,
+            "Dynamically linked executables are not supported",
+        )
+        .with_help("link the program with `-static`")]);
+    }
+
+    let mut raw = vec![0; memory_size];
+
+    for segment in elf
+        .segments
+        .iter()
+        .filter(|segment| segment.kind == PT_LOAD)
+    {
+        let end = segment.vaddr.saturating_add(segment.memsz);
+
+        if end > memory_size as u64 || segment.filesz > segment.memsz {
+            return Err(vec![Diagnostic::error(
+                Code::InvalidElf,
+                format!(
+                    "Segment at {:#x}..{:#x} does not fit in {} bytes of memory",
+                    segment.vaddr, end, memory_size
+                ),
+            )
+            .with_help("increase the memory size")]);
+        }
+
+        let contents = slice(bytes, segment.offset, segment.filesz).map_err(|err| vec![err])?;
+        let start = segment.vaddr as usize;
+
+        raw[start..start + contents.len()].copy_from_slice(contents);
+    }
+
+    let (text_index, text_section) = elf
+        .sections
+        .iter()
+        .enumerate()
+        .find(|(_, section)| section.name == ".text")
+        .ok_or_else(|| {
+            vec![Diagnostic::error(
+                Code::InvalidElf,
+                "ELF file has no .text section",
+            )]
+        })?;
+
+    let text = elf.contents(text_section).map_err(|err| vec![err])?;
+
+    if text_section.addr % 4 != 0 {
+        return Err(vec![Diagnostic::error(
+            Code::InvalidElf,
+            format!(
+                ".text at {:#x} is not aligned to 4 bytes",
+                text_section.addr
+            ),
+        )]);
+    }
+
+    // Bounds the `nop`s padding the instructions up to `.text`
+    let text_end = text_section.addr.saturating_add(text_section.size);
+
+    if text_end > memory_size as u64 {
+        return Err(vec![Diagnostic::error(
+            Code::InvalidElf,
+            format!(
+                ".text at {:#x}..{:#x} does not fit in {} bytes of memory",
+                text_section.addr, text_end, memory_size
+            ),
+        )
+        .with_help("increase the memory size")]);
+    }
+
+    let decoded = BinaryDecoder::decode_bytes(text).map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|diagnostic| Diagnostic {
+                message: format!(
+                    "{} at {:#x}",
+                    diagnostic.message,
+                    text_section.addr as usize + diagnostic.line * 4
+                ),
+                line: 0,
+                ..diagnostic
+            })
+            .collect::<Vec<_>>()
+    })?;
+
+    let nop = Instruction::Addi(format::I {
+        rd: 0,
+        rs1: 0,
+        imm12: 0,
+    });
+
+    let mut instructions = vec![nop; text_section.addr as usize / 4];
+    instructions.extend(decoded);
+
+    let mut data_headers = elf
+        .sections
+        .iter()
+        .filter(|section| matches!(section.kind, SHT_PROGBITS | SHT_NOBITS))
+        .filter(|section| section.flags & (SHF_ALLOC | SHF_EXECINSTR) == SHF_ALLOC)
+        .collect::<Vec<_>>();
+    data_headers.sort_by_key(|section| section.addr);
+
+    let data_sections = data_headers
+        .iter()
+        .map(|section| {
+            let start = section.addr as usize;
+            (
+                Section::from_name(&section.name),
+                start..start + section.size as usize,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Like for assembled programs, the image ends with the last section which is not `.bss`
+    let data_base = data_sections
+        .first()
+        .map_or(0, |(_, addresses)| addresses.start);
+    let data_end = data_headers
+        .iter()
+        .filter(|section| section.kind != SHT_NOBITS)
+        .map(|section| (section.addr + section.size) as usize)
+        .max()
+        .unwrap_or(data_base);
+    let data = raw.get(data_base..data_end).unwrap_or_default().to_vec();
+
+    let heap_base = elf
+        .segments
+        .iter()
+        .filter(|segment| segment.kind == PT_LOAD)
+        .map(|segment| (segment.vaddr + segment.memsz) as usize)
+        .max()
+        .unwrap_or_default();
+
+    let layout = MemoryLayout {
+        text_base: text_section.addr as usize,
+        data_base: Some(data_base),
+        stack_top: Some(memory_size),
+        heap_base: Some(heap_base.next_multiple_of(8)),
+        section_bases: elf
+            .sections
+            .iter()
+            .filter(|section| matches!(section.kind, SHT_PROGBITS | SHT_NOBITS))
+            .filter(|section| Section::from_name(&section.name) != Section::Text)
+            .map(|section| (section.name.clone(), section.addr as usize))
+            .collect(),
+    };
+
+    let mut labels = HashMap::new();
+    let mut data_labels = HashMap::new();
+    let mut symbols = Vec::new();
+
+    for symbol in elf.symbols().map_err(|err| vec![err])? {
+        let is_label = !matches!(symbol.kind, STT_SECTION | STT_FILE)
+            && !symbol.name.is_empty()
+            // Mapping symbols mark instructions and data, e.g. `$x`
+            && !symbol.name.starts_with('$')
+            && (SHN_UNDEF + 1..SHN_LORESERVE).contains(&symbol.section)
+            && (symbol.value as usize) < memory_size;
+
+        if !is_label {
+            continue;
+        }
+
+        let (section, section_labels) = match symbol.section as usize == text_index {
+            true => (Section::Text, &mut labels),
+            false => {
+                // Symbols of other executable sections, e.g. `.init`, are treated as data
+                let section = elf
+                    .sections
+                    .get(symbol.section as usize)
+                    .map(|header| Section::from_name(&header.name))
+                    .filter(|section| *section != Section::Text)
+                    .unwrap_or(Section::Data);
+
+                (section, &mut data_labels)
+            }
+        };
+
+        section_labels
+            .entry(symbol.name.clone())
+            .or_insert(symbol.value as usize);
+
+        symbols.push(Symbol {
+            name: symbol.name,
+            section,
+            address: symbol.value as usize,
+            machine_address: symbol.value as usize,
+            size: (symbol.size != 0).then_some(symbol.size as usize),
+            line: None,
+            references: Vec::new(),
+        });
+    }
+
+    symbols.sort_by(|a, b| {
+        (a.section != Section::Text, a.address, &a.name).cmp(&(
+            b.section != Section::Text,
+            b.address,
+            &b.name,
+        ))
+    });
+
+    Ok(CompilationResult {
+        instructions,
+        instructions_addresses: Vec::new(),
+        labels,
+        memory: Memory::new(raw.into_iter()),
+        data,
+        data_labels,
+        data_sections,
+        text: text.to_vec(),
+        listing: Listing::default(),
+        symbols,
+        source_map: SourceMap::default(),
+        entry: elf.entry as usize,
+        layout,
+        warnings: Vec::new(),
+    })
+}
+
+/// Writes an executable with `.text` and the data sections at the addresses of the memory layout
+///
+/// `.rodata` is loaded read-only and `.bss` only takes memory, not space in the file.
+/// Data sections overlapping `.text` are refused, as their segments would overlap.
+pub fn write(result: &CompilationResult) -> Result<Vec<u8>, Diagnostic> {
+    let MemoryLayout {
+        text_base,
+        data_base,
+        ..
+    } = result.layout;
+    let text_end = text_base + result.text.len();
+
+    if let Some((section, addresses)) = result
+        .data_sections
+        .iter()
+        .find(|(_, addresses)| addresses.start < text_end && text_base < addresses.end)
+    {
+        return Err(Diagnostic::error(
+            Code::InvalidLayout,
+            format!(
+                "Section {} at {:#x}..{:#x} overlaps .text at {:#x}..{:#x}",
+                section.name(),
+                addresses.start,
+                addresses.end,
+                text_base,
+                text_end
+            ),
+        )
+        .with_help("place the data base after the end of the text"));
+    }
+
+    let data_base = data_base.unwrap_or_default();
+    let mut machine_addresses = vec![text_base];
+
+    // Skips the nops padding the instructions up to the text base
+    for instruction in result.instructions.get(text_base / 4..).unwrap_or_default() {
+        machine_addresses
+            .push(machine_addresses[machine_addresses.len() - 1] + Encoder::size(instruction));
+    }
+
+    // Section headers are the null one, `.text`, the data sections, `.symtab`, `.strtab` and `.shstrtab`
+    let symtab_index = 2 + result.data_sections.len();
+
+    let mut symbols = result
+        .symbols
+        .iter()
+        .map(|symbol| match symbol.section {
+            Section::Text => (
+                machine_addresses[(symbol.address - text_base) / 4],
+                &symbol.name,
+                1u16,
+            ),
+            _ => {
+                let index = result
+                    .data_sections
+                    .iter()
+                    .position(|(section, addresses)| {
+                        *section == symbol.section
+                            && (addresses.start..=addresses.end).contains(&symbol.address)
+                    });
+
+                (
+                    symbol.address,
+                    &symbol.name,
+                    index.map_or(SHN_ABS, |index| index as u16 + 2),
+                )
+            }
+        })
+        .collect::<Vec<_>>();
+    symbols.sort();
+
+    let mut strtab = vec![0];
+    let mut symtab = vec![0; SYM_SIZE];
+
+    for (address, label, section) in symbols {
+        symtab.extend((strtab.len() as u32).to_le_bytes());
+        symtab.extend([0, 0]);
+        symtab.extend(section.to_le_bytes());
+        symtab.extend((address as u64).to_le_bytes());
+        symtab.extend(0u64.to_le_bytes());
+        strtab.extend(label.bytes().chain([0]));
+    }
+
+    // Loaders map whole pages, so offsets match the addresses modulo the page size
+    let text_offset = PAGE_SIZE + text_base % PAGE_SIZE;
+    let data_offset =
+        (text_offset + result.text.len()).next_multiple_of(PAGE_SIZE) + data_base % PAGE_SIZE;
+    let offset_of = |address: usize| data_offset + address - data_base;
+
+    let mut file = vec![0; text_offset];
+    file.extend(&result.text);
+    file.resize(data_offset, 0);
+    file.extend(&result.data);
+
+    let symtab_offset = file.len().next_multiple_of(8);
+    file.resize(symtab_offset, 0);
+    file.extend(&symtab);
+    let strtab_offset = file.len();
+    file.extend(&strtab);
+
+    let mut shstrtab = vec![0];
+    let mut name_of = |name: &str| {
+        let offset = shstrtab.len();
+        shstrtab.extend(name.bytes().chain([0]));
+        offset
+    };
+
+    let mut sections = vec![
+        (0, 0, 0, 0, 0, 0, 0),
+        (
+            name_of(".text"),
+            SHT_PROGBITS,
+            SHF_ALLOC | SHF_EXECINSTR,
+            text_base,
+            text_offset,
+            result.text.len(),
+            4,
+        ),
+    ];
+
+    for (section, addresses) in &result.data_sections {
+        let (kind, flags) = match section {
+            Section::Bss => (SHT_NOBITS, SHF_ALLOC | SHF_WRITE),
+            Section::ReadOnlyData => (SHT_PROGBITS, SHF_ALLOC),
+            _ => (SHT_PROGBITS, SHF_ALLOC | SHF_WRITE),
+        };
+
+        sections.push((
+            name_of(section.name()),
+            kind,
+            flags,
+            addresses.start,
+            offset_of(addresses.start),
+            addresses.len(),
+            1,
+        ));
+    }
+
+    sections.push((
+        name_of(".symtab"),
+        SHT_SYMTAB,
+        0,
+        0,
+        symtab_offset,
+        symtab.len(),
+        8,
+    ));
+    sections.push((
+        name_of(".strtab"),
+        SHT_STRTAB,
+        0,
+        0,
+        strtab_offset,
+        strtab.len(),
+        1,
+    ));
+    let shstrtab_name = name_of(".shstrtab");
+
+    let shstrtab_offset = file.len();
+    file.extend(&shstrtab);
+    let shoff = file.len().next_multiple_of(8);
+    file.resize(shoff, 0);
+
+    sections.push((
+        shstrtab_name,
+        SHT_STRTAB,
+        0,
+        0,
+        shstrtab_offset,
+        shoff - shstrtab_offset,
+        1,
+    ));
+
+    for (name, kind, flags, addr, offset, size, align) in &sections {
+        let (link, info, entsize) = match *kind {
+            // Symbols are all local, `info` points past the last one
+            SHT_SYMTAB => (
+                symtab_index as u32 + 1,
+                (symtab.len() / SYM_SIZE) as u32,
+                SYM_SIZE as u64,
+            ),
+            _ => (0, 0, 0),
+        };
+
+        file.extend((*name as u32).to_le_bytes());
+        file.extend(kind.to_le_bytes());
+        file.extend(flags.to_le_bytes());
+        file.extend((*addr as u64).to_le_bytes());
+        file.extend((*offset as u64).to_le_bytes());
+        file.extend((*size as u64).to_le_bytes());
+        file.extend(link.to_le_bytes());
+        file.extend(info.to_le_bytes());
+        file.extend((*align as u64).to_le_bytes());
+        file.extend(entsize.to_le_bytes());
+    }
+
+    // Consecutive data sections with the same permissions share a segment
+    let mut data_loads = Vec::<(u32, usize, usize, usize)>::new();
+
+    for (section, addresses) in &result.data_sections {
+        let flags = match section {
+            Section::ReadOnlyData => PF_R,
+            _ => PF_R | PF_W,
+        };
+        let in_file = *section != Section::Bss;
+
+        match data_loads.last_mut() {
+            Some((last_flags, start, filesz, memsz)) if *last_flags == flags => {
+                if in_file {
+                    *filesz = addresses.end - *start;
+                }
+                *memsz = addresses.end - *start;
+            }
+            _ => data_loads.push((
+                flags,
+                addresses.start,
+                if in_file { addresses.len() } else { 0 },
+                addresses.len(),
+            )),
+        }
+    }
+
+    let loads = [(
+        PF_R | PF_X,
+        text_offset,
+        text_base,
+        result.text.len(),
+        result.text.len(),
+    )]
+    .into_iter()
+    .chain(
+        data_loads
+            .into_iter()
+            .filter(|(.., memsz)| *memsz != 0)
+            .map(|(flags, start, filesz, memsz)| (flags, offset_of(start), start, filesz, memsz)),
+    )
+    .collect::<Vec<_>>();
+
+    let mut header = Vec::with_capacity(EHDR_SIZE + loads.len() * PHDR_SIZE);
+    header.extend(b"\x7fELF");
+    header.extend([EI_CLASS_64, EI_DATA_LE, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    header.extend(ET_EXEC.to_le_bytes());
+    header.extend(EM_RISCV.to_le_bytes());
+    header.extend(1u32.to_le_bytes());
+    header.extend((machine_addresses[(result.entry - text_base) / 4] as u64).to_le_bytes());
+    header.extend((EHDR_SIZE as u64).to_le_bytes());
+    header.extend((shoff as u64).to_le_bytes());
+    // Double-precision floating-point ABI
+    header.extend(0x4u32.to_le_bytes());
+    header.extend((EHDR_SIZE as u16).to_le_bytes());
+    header.extend((PHDR_SIZE as u16).to_le_bytes());
+    header.extend((loads.len() as u16).to_le_bytes());
+    header.extend((SHDR_SIZE as u16).to_le_bytes());
+    header.extend((sections.len() as u16).to_le_bytes());
+    header.extend(((sections.len() - 1) as u16).to_le_bytes());
+
+    for (flags, offset, address, filesz, memsz) in loads {
+        header.extend(PT_LOAD.to_le_bytes());
+        header.extend(flags.to_le_bytes());
+        header.extend((offset as u64).to_le_bytes());
+        header.extend((address as u64).to_le_bytes());
+        header.extend((address as u64).to_le_bytes());
+        header.extend((filesz as u64).to_le_bytes());
+        header.extend((memsz as u64).to_le_bytes());
+        header.extend((PAGE_SIZE as u64).to_le_bytes());
+    }
+
+    file[..header.len()].copy_from_slice(&header);
+    Ok(file)
+}
+
+fn truncated() -> Diagnostic {
+    Diagnostic::error(Code::InvalidElf, "ELF file is truncated")
+}
+
+fn slice(bytes: &[u8], offset: u64, size: u64) -> Result<&[u8], Diagnostic> {
+    let start = usize::try_from(offset).map_err(|_| truncated())?;
+    let end = usize::try_from(offset.saturating_add(size)).map_err(|_| truncated())?;
+
+    bytes.get(start..end).ok_or_else(truncated)
+}
+
+fn read<const N: usize>(bytes: &[u8], offset: usize) -> Result<[u8; N], Diagnostic> {
+    bytes
+        .get(offset..offset + N)
+        .and_then(|field| field.try_into().ok())
+        .ok_or_else(truncated)
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, Diagnostic> {
+    read(bytes, offset).map(u16::from_le_bytes)
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, Diagnostic> {
+    read(bytes, offset).map(u32::from_le_bytes)
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> Result<u64, Diagnostic> {
+    read(bytes, offset).map(u64::from_le_bytes)
+}
+
+/// Null-terminated entry of a string table
+fn string_at(table: &[u8], offset: usize) -> String {
+    let name = table.get(offset..).unwrap_or_default();
+    let end = name
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(name.len());
+
+    String::from_utf8_lossy(&name[..end]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{CompileOptions, Interpreter};
+
+    const BASE: u64 = 0x1000;
+    const TEXT: u64 = BASE + 64 + PHDR_SIZE as u64;
+
+    fn push_section(
+        headers: &mut Vec<u8>,
+        (name, kind, flags): (u32, u32, u64),
+        addr: u64,
+        (start, end): (usize, usize),
+    ) {
+        let (link, entsize) = match kind {
+            // The symbol table links to the string table following it
+            SHT_SYMTAB => (4u32, SYM_SIZE as u64),
+            _ => (0, 0),
+        };
+
+        headers.extend(name.to_le_bytes());
+        headers.extend(kind.to_le_bytes());
+        headers.extend(flags.to_le_bytes());
+        headers.extend(addr.to_le_bytes());
+        headers.extend((start as u64).to_le_bytes());
+        headers.extend(((end - start) as u64).to_le_bytes());
+        headers.extend(link.to_le_bytes());
+        headers.extend([0; 12]);
+        headers.extend(entsize.to_le_bytes());
+    }
+
+    /// Executable with a single segment mapping `.text` and `.data` right after the headers at `BASE`
+    fn executable(text: &[u32], data: &[u8], symbols: &[(&str, u16, u64)]) -> Vec<u8> {
+        let text_start = 64 + PHDR_SIZE;
+        let data_start = text_start + text.len() * 4;
+        let data_end = data_start + data.len();
+
+        let mut file = vec![0; text_start];
+        file.extend(text.iter().flat_map(|word| word.to_le_bytes()));
+        file.extend(data);
+
+        let mut strtab = vec![0];
+        let mut symtab = vec![0; SYM_SIZE];
+
+        for &(name, section, value) in symbols {
+            symtab.extend((strtab.len() as u32).to_le_bytes());
+            symtab.extend([0, 0]);
+            symtab.extend(section.to_le_bytes());
+            symtab.extend(value.to_le_bytes());
+            symtab.extend(0u64.to_le_bytes());
+            strtab.extend(name.bytes().chain([0]));
+        }
+
+        let symtab_start = file.len();
+        file.extend(symtab);
+        let strtab_start = file.len();
+        file.extend(strtab);
+        let shstrtab_start = file.len();
+        file.extend(b"\0.text\0.data\0.symtab\0.strtab\0.shstrtab\0");
+        let shoff = file.len();
+
+        let mut headers = vec![0; SHDR_SIZE];
+        push_section(
+            &mut headers,
+            (1, 1, 0b110),
+            BASE + text_start as u64,
+            (text_start, data_start),
+        );
+        push_section(
+            &mut headers,
+            (7, 1, 0b011),
+            BASE + data_start as u64,
+            (data_start, data_end),
+        );
+        push_section(
+            &mut headers,
+            (13, SHT_SYMTAB, 0),
+            0,
+            (symtab_start, strtab_start),
+        );
+        push_section(&mut headers, (21, 3, 0), 0, (strtab_start, shstrtab_start));
+        push_section(&mut headers, (29, 3, 0), 0, (shstrtab_start, shoff));
+        file.extend(headers);
+
+        file[..16].copy_from_slice(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0");
+        file[16..18].copy_from_slice(&ET_EXEC.to_le_bytes());
+        file[18..20].copy_from_slice(&EM_RISCV.to_le_bytes());
+        file[20..24].copy_from_slice(&1u32.to_le_bytes());
+        file[24..32].copy_from_slice(&TEXT.to_le_bytes());
+        file[32..40].copy_from_slice(&64u64.to_le_bytes());
+        file[40..48].copy_from_slice(&(shoff as u64).to_le_bytes());
+        file[52..54].copy_from_slice(&64u16.to_le_bytes());
+        file[54..56].copy_from_slice(&(PHDR_SIZE as u16).to_le_bytes());
+        file[56..58].copy_from_slice(&1u16.to_le_bytes());
+        file[58..60].copy_from_slice(&(SHDR_SIZE as u16).to_le_bytes());
+        file[60..62].copy_from_slice(&6u16.to_le_bytes());
+        file[62..64].copy_from_slice(&5u16.to_le_bytes());
+
+        file[64..68].copy_from_slice(&PT_LOAD.to_le_bytes());
+        file[68..72].copy_from_slice(&7u32.to_le_bytes());
+        file[80..88].copy_from_slice(&BASE.to_le_bytes());
+        file[88..96].copy_from_slice(&BASE.to_le_bytes());
+        file[96..104].copy_from_slice(&(data_end as u64).to_le_bytes());
+        file[104..112].copy_from_slice(&(data_end as u64).to_le_bytes());
+        file[112..120].copy_from_slice(&0x1000u64.to_le_bytes());
+
+        file
+    }
+
+    #[test]
+    fn loads_static_executables() {
+        // addi a0, zero, 1; ld a1, 0(a2)
+        let elf = executable(
+            &[0x00100513, 0x00063583],
+            &[1, 2, 3, 4],
+            &[("_start", 1, TEXT), ("$x", 1, TEXT), ("value", 2, TEXT + 8)],
+        );
+
+        let result = load(&elf, 0x2000).unwrap();
+
+        assert_eq!(result.entry, TEXT as usize);
+        assert_eq!(result.instructions.len(), TEXT as usize / 4 + 2);
+        assert_eq!(
+            result.instructions[TEXT as usize / 4..],
+            [
+                Instruction::Addi(format::I {
+                    rd: 10,
+                    rs1: 0,
+                    imm12: 1
+                }),
+                Instruction::Ld(format::I {
+                    rd: 11,
+                    rs1: 12,
+                    imm12: 0
+                }),
+            ]
+        );
+        assert_eq!(
+            result.labels,
+            HashMap::from([("_start".to_owned(), TEXT as usize)])
+        );
+        assert_eq!(
+            result.data_labels,
+            HashMap::from([("value".to_owned(), TEXT as usize + 8)])
+        );
+        assert_eq!(
+            result
+                .symbols
+                .iter()
+                .map(|symbol| (&*symbol.name, symbol.section.clone(), symbol.address))
+                .collect::<Vec<_>>(),
+            [
+                ("_start", Section::Text, TEXT as usize),
+                ("value", Section::Data, TEXT as usize + 8)
+            ]
+        );
+        assert_eq!(result.memory.get::<4>(TEXT as usize + 8), [1, 2, 3, 4]);
+        assert_eq!(result.text, elf[TEXT as usize - 0x1000..][..8]);
+    }
+
+    #[test]
+    fn runs_programs_built_without_compressed_instructions() {
+        // As built with `-march=rv64imfdv -nostdlib`, storing 1.5 * 4, [1.5, 4] * 2 and 6 * 7 after the inputs
+        let text = [
+            0x00000517, // auipc a0, 0
+            0x03c50513, // addi a0, a0, 60
+            0x00053507, // fld fa0, 0(a0)
+            0x00853587, // fld fa1, 8(a0)
+            0x12b57653, // fmul.d fa2, fa0, fa1
+            0x00c53827, // fsd fa2, 16(a0)
+            0xcd8172d7, // vsetivli t0, 2, e64, m1, ta, ma
+            0x02057087, // vle64.v v1, (a0)
+            0x02109157, // vfadd.vv v2, v1, v1
+            0x01850593, // addi a1, a0, 24
+            0x0205f127, // vse64.v v2, (a1)
+            0x00600613, // addi a2, zero, 6
+            0x00700693, // addi a3, zero, 7
+            0x02d60733, // mul a4, a2, a3
+            0x02e53423, // sd a4, 40(a0)
+        ];
+        let data = [
+            1.5f64.to_le_bytes(),
+            4f64.to_le_bytes(),
+            [0; 8],
+            [0; 8],
+            [0; 8],
+            [0; 8],
+        ]
+        .concat();
+        let values = TEXT as usize + text.len() * 4;
+
+        let result = load(&executable(&text, &data, &[]), 0x2000).unwrap();
+
+        let mut core = RvCoreBuilder::default()
+            .instructions(result.instructions)
+            .memory(result.memory)
+            .build();
+        core.registers.pc = result.entry as u64;
+
+        for result in core.run() {
+            result.unwrap();
+        }
+
+        assert_eq!(core.memory.get::<8>(values + 16), 6f64.to_le_bytes());
+        assert_eq!(core.memory.get::<8>(values + 24), 3f64.to_le_bytes());
+        assert_eq!(core.memory.get::<8>(values + 32), 8f64.to_le_bytes());
+        assert_eq!(core.memory.get::<8>(values + 40), 42u64.to_le_bytes());
+
+        // Neither ecall nor fence are supported, so there is no C runtime to start the program
+        for word in [0x00000073, 0x0ff0000f] {
+            let errors = load(&executable(&[word], &[], &[]), 0x2000).err().unwrap();
+
+            assert_eq!(errors[0].code, Code::IllegalInstruction);
+        }
+    }
+
+    #[test]
+    fn written_executables_load_back() {
+        let program = r#"
+        .text
+        main:
+            ld a0, value(zero)
+            beqz a0, main
+            fmadd.d fa0, fa1, fa2, fa3
+        .data
+        value:
+            .dword 42
+        "#;
+
+        let mut result = Interpreter::compile(program.to_owned(), 64).unwrap();
+        let bytes = result.to_elf().unwrap();
+        let elf = Elf::parse(&bytes).unwrap();
+
+        let segments = elf
+            .segments
+            .iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                let flags = u32_at(&bytes, EHDR_SIZE + index * PHDR_SIZE + 4).unwrap();
+                (flags, segment.vaddr, segment.filesz, segment.memsz)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            segments,
+            [(PF_R | PF_X, 0, 12, 12), (PF_R | PF_W, 16, 8, 8)]
+        );
+
+        let loaded = load(&bytes, 64).unwrap();
+
+        assert_eq!(loaded.instructions, result.instructions);
+        assert_eq!(loaded.labels, result.labels);
+        assert_eq!(loaded.data_labels, result.data_labels);
+        assert_eq!(loaded.data_labels["value"], 16);
+        assert_eq!(loaded.data, result.data);
+        assert_eq!(loaded.text, result.text);
+        assert_eq!(loaded.entry, 0);
+
+        result.data_sections[0].1 = 8..16;
+
+        assert_eq!(result.to_elf().unwrap_err().code, Code::InvalidLayout);
+    }
+
+    #[test]
+    fn written_executables_keep_sections() {
+        let program = r#"
+        main:
+            la a0, message
+            la a1, value
+            la a2, counter
+        .rodata
+        message:
+            .byte 1, 2, 3
+        .data
+        value:
+            .word 7
+        .bss
+        counter:
+            .zero 16
+        "#;
+        let options = CompileOptions {
+            layout: MemoryLayout {
+                data_base: Some(0x100),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let result = Interpreter::compile_with_options(program.to_owned(), 0x200, options).unwrap();
+        let bytes = result.to_elf().unwrap();
+        let elf = Elf::parse(&bytes).unwrap();
+
+        let sections = elf
+            .sections
+            .iter()
+            .map(|section| {
+                (
+                    &*section.name,
+                    section.kind,
+                    section.flags,
+                    section.addr,
+                    section.size,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            sections[2..5],
+            [
+                (".rodata", SHT_PROGBITS, SHF_ALLOC, 0x100, 3),
+                (".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, 0x108, 4),
+                (".bss", SHT_NOBITS, SHF_ALLOC | SHF_WRITE, 0x110, 16),
+            ]
+        );
+
+        let segments = elf
+            .segments
+            .iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                let flags = u32_at(&bytes, EHDR_SIZE + index * PHDR_SIZE + 4).unwrap();
+                (flags, segment.vaddr, segment.filesz, segment.memsz)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            segments[1..],
+            [(PF_R, 0x100, 3, 3), (PF_R | PF_W, 0x108, 4, 24)]
+        );
+
+        let counter = elf
+            .symbols()
+            .unwrap()
+            .into_iter()
+            .find(|symbol| symbol.name == "counter")
+            .unwrap();
+        assert_eq!((counter.section, counter.value), (4, 0x110));
+
+        let loaded = load(&bytes, 0x200).unwrap();
+
+        assert_eq!(loaded.data, result.data);
+        assert_eq!(loaded.data_labels, result.data_labels);
+        assert_eq!(loaded.data_sections, result.data_sections);
+        assert_eq!(loaded.layout.heap_base, result.layout.heap_base);
+    }
+
+    #[test]
+    fn rejects_unsupported_executables() {
+        let error = |elf: &[u8], memory_size| load(elf, memory_size).err().unwrap()[0].clone();
+
+        assert_eq!(error(b"#!/bin/sh", 0x2000).message, "Not an ELF file");
+
+        // c.li a0, 1; c.li a0, 1
+        let compressed = executable(&[0x45054505], &[], &[]);
+        assert_eq!(
+            error(&compressed, 0x2000).help.as_deref(),
+            Some("compressed instructions are not supported")
+        );
+
+        let small = executable(&[0x00100513], &[], &[]);
+        assert_eq!(
+            error(&small, 0x1000).help.as_deref(),
+            Some("increase the memory size")
+        );
+    }
+}
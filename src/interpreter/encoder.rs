@@ -0,0 +1,1056 @@
+use eeric_core::prelude::*;
+
+use super::diagnostic::{Code, Diagnostic};
+
+pub(super) const LOAD: u32 = 0b0000011;
+pub(super) const LOAD_FP: u32 = 0b0000111;
+pub(super) const OP_IMM: u32 = 0b0010011;
+pub(super) const AUIPC: u32 = 0b0010111;
+pub(super) const OP_IMM_32: u32 = 0b0011011;
+pub(super) const STORE: u32 = 0b0100011;
+pub(super) const STORE_FP: u32 = 0b0100111;
+pub(super) const OP: u32 = 0b0110011;
+pub(super) const LUI: u32 = 0b0110111;
+pub(super) const OP_32: u32 = 0b0111011;
+pub(super) const MADD: u32 = 0b1000011;
+pub(super) const MSUB: u32 = 0b1000111;
+pub(super) const NMSUB: u32 = 0b1001011;
+pub(super) const NMADD: u32 = 0b1001111;
+pub(super) const OP_FP: u32 = 0b1010011;
+pub(super) const OP_V: u32 = 0b1010111;
+pub(super) const BRANCH: u32 = 0b1100011;
+pub(super) const JALR: u32 = 0b1100111;
+pub(super) const JAL: u32 = 0b1101111;
+pub(super) const SYSTEM: u32 = 0b1110011;
+
+// Floating point formats
+pub(super) const S: u32 = 0b00;
+pub(super) const D: u32 = 0b01;
+
+// Rounding modes, GNU as picks the dynamic one unless the conversion is exact
+const RNE: u32 = 0b000;
+const DYN: u32 = 0b111;
+
+// Vector arithmetic funct3
+pub(super) const OPIVV: u32 = 0b000;
+pub(super) const OPFVV: u32 = 0b001;
+pub(super) const OPMVV: u32 = 0b010;
+pub(super) const OPIVI: u32 = 0b011;
+pub(super) const OPIVX: u32 = 0b100;
+pub(super) const OPFVF: u32 = 0b101;
+pub(super) const OPMVX: u32 = 0b110;
+pub(super) const OPCFG: u32 = 0b111;
+
+// Vector memory addressing modes
+pub(super) const UNIT_STRIDE: u32 = 0b00;
+pub(super) const INDEXED_UNORDERED: u32 = 0b01;
+pub(super) const STRIDED: u32 = 0b10;
+pub(super) const INDEXED_ORDERED: u32 = 0b11;
+
+// Unit-stride lumop/sumop values
+pub(super) const WHOLE_REGISTER: u32 = 0b01000;
+pub(super) const MASK: u32 = 0b01011;
+pub(super) const FAULT_ONLY_FIRST: u32 = 0b10000;
+
+pub struct Encoder;
+
+impl Encoder {
+    /// Size of the machine code in bytes, fused pseudo-instructions take all of their words
+    pub fn size(instruction: &Instruction) -> usize {
+        match instruction {
+            Instruction::Fusion(instructions) => instructions.iter().map(Self::size).sum(),
+            _ => 4,
+        }
+    }
+
+    pub fn encode(instruction: &Instruction) -> Result<Vec<u32>, Diagnostic> {
+        match instruction {
+            Instruction::Fusion(instructions) => instructions
+                .iter()
+                .map(Self::encode)
+                .collect::<Result<Vec<_>, _>>()
+                .map(|words| words.concat()),
+            single => Self::encode_single(single).map(|word| vec![word]),
+        }
+    }
+
+    fn encode_single(instruction: &Instruction) -> Result<u32, Diagnostic> {
+        use Instruction::*;
+
+        Ok(match instruction {
+            Add(args) => r(OP, 0b000, 0b0000000, args),
+            Addw(args) => r(OP_32, 0b000, 0b0000000, args),
+            Sub(args) => r(OP, 0b000, 0b0100000, args),
+            Subw(args) => r(OP_32, 0b000, 0b0100000, args),
+            Addi(args) => i(OP_IMM, 0b000, args)?,
+            Addiw(args) => i(OP_IMM_32, 0b000, args)?,
+            Slt(args) => r(OP, 0b010, 0b0000000, args),
+            Slti(args) => i(OP_IMM, 0b010, args)?,
+            Sltu(args) => r(OP, 0b011, 0b0000000, args),
+            Sltiu(args) => i(OP_IMM, 0b011, args)?,
+            Lui(args) => u(LUI, args)?,
+            Auipc(args) => u(AUIPC, args)?,
+
+            And(args) => r(OP, 0b111, 0b0000000, args),
+            Or(args) => r(OP, 0b110, 0b0000000, args),
+            Xor(args) => r(OP, 0b100, 0b0000000, args),
+            Andi(args) => i(OP_IMM, 0b111, args)?,
+            Ori(args) => i(OP_IMM, 0b110, args)?,
+            Xori(args) => i(OP_IMM, 0b100, args)?,
+            Sll(args) => r(OP, 0b001, 0b0000000, args),
+            Sllw(args) => r(OP_32, 0b001, 0b0000000, args),
+            Srl(args) => r(OP, 0b101, 0b0000000, args),
+            Srlw(args) => r(OP_32, 0b101, 0b0000000, args),
+            Sra(args) => r(OP, 0b101, 0b0100000, args),
+            Sraw(args) => r(OP_32, 0b101, 0b0100000, args),
+            Slli(args) => shift(OP_IMM, 0b001, 0b000000, 6, args)?,
+            Slliw(args) => shift(OP_IMM_32, 0b001, 0b000000, 5, args)?,
+            Srli(args) => shift(OP_IMM, 0b101, 0b000000, 6, args)?,
+            Srliw(args) => shift(OP_IMM_32, 0b101, 0b000000, 5, args)?,
+            Srai(args) => shift(OP_IMM, 0b101, 0b010000, 6, args)?,
+            Sraiw(args) => shift(OP_IMM_32, 0b101, 0b010000, 5, args)?,
+
+            Ld(args) => i(LOAD, 0b011, args)?,
+            Lw(args) => i(LOAD, 0b010, args)?,
+            Lh(args) => i(LOAD, 0b001, args)?,
+            Lb(args) => i(LOAD, 0b000, args)?,
+            Lwu(args) => i(LOAD, 0b110, args)?,
+            Lhu(args) => i(LOAD, 0b101, args)?,
+            Lbu(args) => i(LOAD, 0b100, args)?,
+            Sd(args) => s(STORE, 0b011, args)?,
+            Sw(args) => s(STORE, 0b010, args)?,
+            Sh(args) => s(STORE, 0b001, args)?,
+            Sb(args) => s(STORE, 0b000, args)?,
+
+            Beq(args) => b(0b000, args)?,
+            Bne(args) => b(0b001, args)?,
+            Bge(args) => b(0b101, args)?,
+            Bgeu(args) => b(0b111, args)?,
+            Blt(args) => b(0b100, args)?,
+            Bltu(args) => b(0b110, args)?,
+            Jal(args) => j(args)?,
+            Jalr(args) => i(JALR, 0b000, args)?,
+
+            Csrrw(args) => csrr(0b001, args)?,
+            Csrrs(args) => csrr(0b010, args)?,
+            Csrrc(args) => csrr(0b011, args)?,
+            Csrrwi(args) => csri(0b101, args)?,
+            Csrrsi(args) => csri(0b110, args)?,
+            Csrrci(args) => csri(0b111, args)?,
+
+            Mul(args) => r(OP, 0b000, 0b0000001, args),
+            Mulh(args) => r(OP, 0b001, 0b0000001, args),
+            Mulhsu(args) => r(OP, 0b010, 0b0000001, args),
+            Mulhu(args) => r(OP, 0b011, 0b0000001, args),
+            Div(args) => r(OP, 0b100, 0b0000001, args),
+            Divu(args) => r(OP, 0b101, 0b0000001, args),
+            Rem(args) => r(OP, 0b110, 0b0000001, args),
+            Remu(args) => r(OP, 0b111, 0b0000001, args),
+            Mulw(args) => r(OP_32, 0b000, 0b0000001, args),
+            Divw(args) => r(OP_32, 0b100, 0b0000001, args),
+            Divuw(args) => r(OP_32, 0b101, 0b0000001, args),
+            Remw(args) => r(OP_32, 0b110, 0b0000001, args),
+            Remuw(args) => r(OP_32, 0b111, 0b0000001, args),
+
+            Flw(args) => i(LOAD_FP, 0b010, args)?,
+            Fsw(args) => s(STORE_FP, 0b010, args)?,
+            Fmadds(args) => r4(MADD, S, args),
+            Fmsubs(args) => r4(MSUB, S, args),
+            Fnmsubs(args) => r4(NMSUB, S, args),
+            Fnmadds(args) => r4(NMADD, S, args),
+            Fadds(args) => fp(0b00000, S, DYN, args),
+            Fsubs(args) => fp(0b00001, S, DYN, args),
+            Fmuls(args) => fp(0b00010, S, DYN, args),
+            Fdivs(args) => fp(0b00011, S, DYN, args),
+            Fsqrts(args) => fp_unary(0b01011, S, DYN, 0, args),
+            Fsgnjs(args) => fp(0b00100, S, 0b000, args),
+            Fsgnjns(args) => fp(0b00100, S, 0b001, args),
+            Fsgnjxs(args) => fp(0b00100, S, 0b010, args),
+            Fmins(args) => fp(0b00101, S, 0b000, args),
+            Fmaxs(args) => fp(0b00101, S, 0b001, args),
+            Fcvtws(args) => fp_unary(0b11000, S, DYN, 0, args),
+            Fcvtwus(args) => fp_unary(0b11000, S, DYN, 1, args),
+            Fmvxw(args) => fp_unary(0b11100, S, 0b000, 0, args),
+            Feqs(args) => fp(0b10100, S, 0b010, args),
+            Flts(args) => fp(0b10100, S, 0b001, args),
+            Fles(args) => fp(0b10100, S, 0b000, args),
+            Fclasss(args) => fp_unary(0b11100, S, 0b001, 0, args),
+            Fcvtsw(args) => fp_unary(0b11010, S, DYN, 0, args),
+            Fcvtswu(args) => fp_unary(0b11010, S, DYN, 1, args),
+            Fmvwx(args) => fp_unary(0b11110, S, 0b000, 0, args),
+            Fcvtls(args) => fp_unary(0b11000, S, DYN, 2, args),
+            Fcvtlus(args) => fp_unary(0b11000, S, DYN, 3, args),
+            Fcvtsl(args) => fp_unary(0b11010, S, DYN, 2, args),
+            Fcvtslu(args) => fp_unary(0b11010, S, DYN, 3, args),
+
+            Fld(args) => i(LOAD_FP, 0b011, args)?,
+            Fsd(args) => s(STORE_FP, 0b011, args)?,
+            Fmaddd(args) => r4(MADD, D, args),
+            Fmsubd(args) => r4(MSUB, D, args),
+            Fnmsubd(args) => r4(NMSUB, D, args),
+            Fnmaddd(args) => r4(NMADD, D, args),
+            Faddd(args) => fp(0b00000, D, DYN, args),
+            Fsubd(args) => fp(0b00001, D, DYN, args),
+            Fmuld(args) => fp(0b00010, D, DYN, args),
+            Fdivd(args) => fp(0b00011, D, DYN, args),
+            Fsqrtd(args) => fp_unary(0b01011, D, DYN, 0, args),
+            Fsgnjd(args) => fp(0b00100, D, 0b000, args),
+            Fsgnjnd(args) => fp(0b00100, D, 0b001, args),
+            Fsgnjxd(args) => fp(0b00100, D, 0b010, args),
+            Fmind(args) => fp(0b00101, D, 0b000, args),
+            Fmaxd(args) => fp(0b00101, D, 0b001, args),
+            Fcvtsd(args) => fp_unary(0b01000, S, DYN, 1, args),
+            Fcvtds(args) => fp_unary(0b01000, D, RNE, 0, args),
+            Feqd(args) => fp(0b10100, D, 0b010, args),
+            Fltd(args) => fp(0b10100, D, 0b001, args),
+            Fled(args) => fp(0b10100, D, 0b000, args),
+            Fclassd(args) => fp_unary(0b11100, D, 0b001, 0, args),
+            Fcvtwd(args) => fp_unary(0b11000, D, DYN, 0, args),
+            Fcvtwud(args) => fp_unary(0b11000, D, DYN, 1, args),
+            Fcvtdw(args) => fp_unary(0b11010, D, RNE, 0, args),
+            Fcvtdwu(args) => fp_unary(0b11010, D, RNE, 1, args),
+            Fcvtld(args) => fp_unary(0b11000, D, DYN, 2, args),
+            Fcvtlud(args) => fp_unary(0b11000, D, DYN, 3, args),
+            Fmvxd(args) => fp_unary(0b11100, D, 0b000, 0, args),
+            Fcvtdl(args) => fp_unary(0b11010, D, DYN, 2, args),
+            Fcvtdlu(args) => fp_unary(0b11010, D, DYN, 3, args),
+            Fmvdx(args) => fp_unary(0b11110, D, 0b000, 0, args),
+
+            Vsetvli(format::Vsetvli { rd, rs1, vtypei }) => {
+                ((vtypei & 0x7ff) << 20) | reg(*rs1, 15) | (OPCFG << 12) | reg(*rd, 7) | OP_V
+            }
+            Vsetivli(format::Vsetivli { rd, uimm, vtypei }) => {
+                let uimm = unsigned(*uimm as i64, 5)?;
+
+                (0b11 << 30)
+                    | ((vtypei & 0x3ff) << 20)
+                    | (uimm << 15)
+                    | (OPCFG << 12)
+                    | reg(*rd, 7)
+                    | OP_V
+            }
+            Vsetvl(format::Vsetvl { rd, rs1, rs2 }) => {
+                (0b1000000 << 25)
+                    | reg(*rs2, 20)
+                    | reg(*rs1, 15)
+                    | (OPCFG << 12)
+                    | reg(*rd, 7)
+                    | OP_V
+            }
+
+            Vlv { data, eew } => vl(1, UNIT_STRIDE, 0, eew, data),
+            Vsv { data, eew } => vs(1, UNIT_STRIDE, 0, eew, data),
+            Vlmv(data) => vl(1, UNIT_STRIDE, MASK, &BaseSew::E8, &unmasked(data)),
+            Vsmv(data) => vs(
+                1,
+                UNIT_STRIDE,
+                MASK,
+                &BaseSew::E8,
+                &format::Vs {
+                    vm: false,
+                    ..data.clone()
+                },
+            ),
+            Vlsv { data, eew } => vls(1, data, eew),
+            Vssv { data, eew } => vss(1, data, eew),
+            Vluxv { data, eew } => vlx(1, INDEXED_UNORDERED, data, eew),
+            Vloxv { data, eew } => vlx(1, INDEXED_ORDERED, data, eew),
+            Vsuxv { data, eew } => vsx(1, INDEXED_UNORDERED, data, eew),
+            Vsoxv { data, eew } => vsx(1, INDEXED_ORDERED, data, eew),
+            Vlffv { data, eew } => vl(1, UNIT_STRIDE, FAULT_ONLY_FIRST, eew, data),
+            Vlsegv { data, eew, nf } => vl(*nf, UNIT_STRIDE, 0, eew, data),
+            Vssegv { data, eew, nf } => vs(*nf, UNIT_STRIDE, 0, eew, data),
+            Vlssegv { data, eew, nf } => vls(*nf, data, eew),
+            Vsssegv { data, eew, nf } => vss(*nf, data, eew),
+            Vluxsegv { data, eew, nf } => vlx(*nf, INDEXED_UNORDERED, data, eew),
+            Vloxsegv { data, eew, nf } => vlx(*nf, INDEXED_ORDERED, data, eew),
+            Vsuxsegv { data, eew, nf } => vsx(*nf, INDEXED_UNORDERED, data, eew),
+            Vsoxsegv { data, eew, nf } => vsx(*nf, INDEXED_ORDERED, data, eew),
+            Vlrv { data, eew, nf } => vl(
+                *nf,
+                UNIT_STRIDE,
+                WHOLE_REGISTER,
+                eew,
+                &format::Vl {
+                    vd: data.vd,
+                    rs1: data.rs1,
+                    vm: false,
+                },
+            ),
+            Vsrv { data, nf } => vs(
+                *nf,
+                UNIT_STRIDE,
+                WHOLE_REGISTER,
+                &BaseSew::E8,
+                &format::Vs {
+                    vs3: data.vs3,
+                    rs1: data.rs1,
+                    vm: false,
+                },
+            ),
+
+            Vaddvv(args) => opivv(0b000000, args),
+            Vaddvx(args) => opivx(0b000000, args),
+            Vaddvi(args) => opivi(0b000000, args)?,
+            Vsubvv(args) => opivv(0b000010, args),
+            Vsubvx(args) => opivx(0b000010, args),
+            Vrsubvx(args) => opivx(0b000011, args),
+            Vrsubvi(args) => opivi(0b000011, args)?,
+            Vminuvv(args) => opivv(0b000100, args),
+            Vminuvx(args) => opivx(0b000100, args),
+            Vminvv(args) => opivv(0b000101, args),
+            Vminvx(args) => opivx(0b000101, args),
+            Vmaxuvv(args) => opivv(0b000110, args),
+            Vmaxuvx(args) => opivx(0b000110, args),
+            Vmaxvv(args) => opivv(0b000111, args),
+            Vmaxvx(args) => opivx(0b000111, args),
+            Vandvv(args) => opivv(0b001001, args),
+            Vandvx(args) => opivx(0b001001, args),
+            Vandvi(args) => opivi(0b001001, args)?,
+            Vorvv(args) => opivv(0b001010, args),
+            Vorvx(args) => opivx(0b001010, args),
+            Vorvi(args) => opivi(0b001010, args)?,
+            Vxorvv(args) => opivv(0b001011, args),
+            Vxorvx(args) => opivx(0b001011, args),
+            Vxorvi(args) => opivi(0b001011, args)?,
+            Vrgathervv(args) => opivv(0b001100, args),
+            Vrgathervx(args) => opivx(0b001100, args),
+            Vrgathervi(args) => opivi(0b001100, args)?,
+            Vrgatherei16vv(args) => opivv(0b001110, args),
+            Vslideupvx(args) => opivx(0b001110, args),
+            Vslideupvi(args) => opivi(0b001110, args)?,
+            Vslidedownvx(args) => opivx(0b001111, args),
+            Vslidedownvi(args) => opivi(0b001111, args)?,
+
+            // Carry-in and merge variants always read v0, i.e. are encoded as masked
+            Vadcvvm(args) => opivv(0b010000, &masked(args)),
+            Vadcvxm(args) => opivx(0b010000, &masked(args)),
+            Vadcvim(args) => opivi(0b010000, &masked(args))?,
+            Vmadcvvm(args) => opivv(0b010001, &masked(args)),
+            Vmadcvxm(args) => opivx(0b010001, &masked(args)),
+            Vmadcvim(args) => opivi(0b010001, &masked(args))?,
+            Vmadcvv(args) => opivv(0b010001, &unmasked(args)),
+            Vmadcvx(args) => opivx(0b010001, &unmasked(args)),
+            Vmadcvi(args) => opivi(0b010001, &unmasked(args))?,
+            Vsbcvvm(args) => opivv(0b010010, &masked(args)),
+            Vsbcvxm(args) => opivx(0b010010, &masked(args)),
+            Vmsbcvvm(args) => opivv(0b010011, &masked(args)),
+            Vmsbcvxm(args) => opivx(0b010011, &masked(args)),
+            Vmsbcvv(args) => opivv(0b010011, &unmasked(args)),
+            Vmsbcvx(args) => opivx(0b010011, &unmasked(args)),
+            Vmergevvm(args) => opivv(0b010111, &masked(args)),
+            Vmergevxm(args) => opivx(0b010111, &masked(args)),
+            Vmergevim(args) => opivi(0b010111, &masked(args))?,
+            Vmvvv(args) => opivv(
+                0b010111,
+                &format::Opivv {
+                    vs2: 0,
+                    ..unmasked(args)
+                },
+            ),
+            Vmvvx(args) => opivx(
+                0b010111,
+                &format::Opivx {
+                    vs2: 0,
+                    ..unmasked(args)
+                },
+            ),
+            Vmvvi(args) => opivi(
+                0b010111,
+                &format::Opivi {
+                    vs2: 0,
+                    ..unmasked(args)
+                },
+            )?,
+
+            Vmseqvv(args) => opivv(0b011000, args),
+            Vmseqvx(args) => opivx(0b011000, args),
+            Vmseqvi(args) => opivi(0b011000, args)?,
+            Vmsnevv(args) => opivv(0b011001, args),
+            Vmsnevx(args) => opivx(0b011001, args),
+            Vmsnevi(args) => opivi(0b011001, args)?,
+            Vmsltuvv(args) => opivv(0b011010, args),
+            Vmsltuvx(args) => opivx(0b011010, args),
+            Vmsltvv(args) => opivv(0b011011, args),
+            Vmsltvx(args) => opivx(0b011011, args),
+            Vmsleuvv(args) => opivv(0b011100, args),
+            Vmsleuvx(args) => opivx(0b011100, args),
+            Vmsleuvi(args) => opivi(0b011100, args)?,
+            Vmslevv(args) => opivv(0b011101, args),
+            Vmslevx(args) => opivx(0b011101, args),
+            Vmslevi(args) => opivi(0b011101, args)?,
+            Vmsgtuvx(args) => opivx(0b011110, args),
+            Vmsgtuvi(args) => opivi(0b011110, args)?,
+            Vmsgtvx(args) => opivx(0b011111, args),
+            Vmsgtvi(args) => opivi(0b011111, args)?,
+
+            Vsadduvv(args) => opivv(0b100000, args),
+            Vsadduvx(args) => opivx(0b100000, args),
+            Vsadduvi(args) => opivi(0b100000, args)?,
+            Vsaddvv(args) => opivv(0b100001, args),
+            Vsaddvx(args) => opivx(0b100001, args),
+            Vsaddvi(args) => opivi(0b100001, args)?,
+            Vssubuvv(args) => opivv(0b100010, args),
+            Vssubuvx(args) => opivx(0b100010, args),
+            Vssubvv(args) => opivv(0b100011, args),
+            Vssubvx(args) => opivx(0b100011, args),
+            Vsllvv(args) => opivv(0b100101, args),
+            Vsllvx(args) => opivx(0b100101, args),
+            Vsllvi(args) => opivi(0b100101, args)?,
+            Vsmulvv(args) => opivv(0b100111, args),
+            Vsmulvx(args) => opivx(0b100111, args),
+            Vmv1rv(args) => vmvnr(1, args)?,
+            Vmv2rv(args) => vmvnr(2, args)?,
+            Vmv4rv(args) => vmvnr(4, args)?,
+            Vmv8rv(args) => vmvnr(8, args)?,
+            Vsrlvv(args) => opivv(0b101000, args),
+            Vsrlvx(args) => opivx(0b101000, args),
+            Vsrlvi(args) => opivi(0b101000, args)?,
+            Vsravv(args) => opivv(0b101001, args),
+            Vsravx(args) => opivx(0b101001, args),
+            Vsravi(args) => opivi(0b101001, args)?,
+            Vssrlvv(args) => opivv(0b101010, args),
+            Vssrlvx(args) => opivx(0b101010, args),
+            Vssrlvi(args) => opivi(0b101010, args)?,
+            Vssravv(args) => opivv(0b101011, args),
+            Vssravx(args) => opivx(0b101011, args),
+            Vssravi(args) => opivi(0b101011, args)?,
+            Vnsrlwv(args) => opivv(0b101100, args),
+            Vnsrlwx(args) => opivx(0b101100, args),
+            Vnsrlwi(args) => opivi(0b101100, args)?,
+            Vnsrawv(args) => opivv(0b101101, args),
+            Vnsrawx(args) => opivx(0b101101, args),
+            Vnsrawi(args) => opivi(0b101101, args)?,
+            Vnclipuwv(args) => opivv(0b101110, args),
+            Vnclipuwx(args) => opivx(0b101110, args),
+            Vnclipuwi(args) => opivi(0b101110, args)?,
+            Vnclipwv(args) => opivv(0b101111, args),
+            Vnclipwx(args) => opivx(0b101111, args),
+            Vnclipwi(args) => opivi(0b101111, args)?,
+            Vwredsumuvs(args) => opivv(0b110000, args),
+            Vwredsumvs(args) => opivv(0b110001, args),
+
+            Vredsumvs(args) => opmvv(0b000000, args),
+            Vredandvs(args) => opmvv(0b000001, args),
+            Vredorvs(args) => opmvv(0b000010, args),
+            Vredxorvs(args) => opmvv(0b000011, args),
+            Vredminuvs(args) => opmvv(0b000100, args),
+            Vredminvs(args) => opmvv(0b000101, args),
+            Vredmaxuvs(args) => opmvv(0b000110, args),
+            Vredmaxvs(args) => opmvv(0b000111, args),
+            Vaadduvv(args) => opmvv(0b001000, args),
+            Vaadduvx(args) => opmvx(0b001000, args),
+            Vaaddvv(args) => opmvv(0b001001, args),
+            Vaaddvx(args) => opmvx(0b001001, args),
+            Vasubuvv(args) => opmvv(0b001010, args),
+            Vasubuvx(args) => opmvx(0b001010, args),
+            Vasubvv(args) => opmvv(0b001011, args),
+            Vasubvx(args) => opmvx(0b001011, args),
+            Vslide1upvx(args) => opmvx(0b001110, args),
+            Vslide1downvx(args) => opmvx(0b001111, args),
+
+            // Unary instructions select the operation with their vs1 or vs2 field
+            Vmvxs(args) => opmvv_unary(0b010000, 0b00000, &unmasked(args)),
+            Vcpopm(args) => opmvv_unary(0b010000, 0b10000, args),
+            Vfirstm(args) => opmvv_unary(0b010000, 0b10001, args),
+            Vmvsx(args) => opmvx(
+                0b010000,
+                &format::Opmvx {
+                    vs2: 0b00000,
+                    ..unmasked(args)
+                },
+            ),
+            Vzextvf8(args) => opmvv_unary(0b010010, 0b00010, args),
+            Vsextvf8(args) => opmvv_unary(0b010010, 0b00011, args),
+            Vzextvf4(args) => opmvv_unary(0b010010, 0b00100, args),
+            Vsextvf4(args) => opmvv_unary(0b010010, 0b00101, args),
+            Vzextvf2(args) => opmvv_unary(0b010010, 0b00110, args),
+            Vsextvf2(args) => opmvv_unary(0b010010, 0b00111, args),
+            Vmsbfm(args) => opmvv_unary(0b010100, 0b00001, args),
+            Vmsofm(args) => opmvv_unary(0b010100, 0b00010, args),
+            Vmsifm(args) => opmvv_unary(0b010100, 0b00011, args),
+            Viotam(args) => opmvv_unary(0b010100, 0b10000, args),
+            Vidv(args) => opmvv_unary(0b010100, 0b10001, args),
+            Vcompressvm(args) => opmvv(0b010111, &unmasked(args)),
+            Vmandnmm(args) => opmvv(0b011000, &unmasked(args)),
+            Vmandmm(args) => opmvv(0b011001, &unmasked(args)),
+            Vmormm(args) => opmvv(0b011010, &unmasked(args)),
+            Vmxormm(args) => opmvv(0b011011, &unmasked(args)),
+            Vmornmm(args) => opmvv(0b011100, &unmasked(args)),
+            Vmnandmm(args) => opmvv(0b011101, &unmasked(args)),
+            Vmnormm(args) => opmvv(0b011110, &unmasked(args)),
+            Vmxnormm(args) => opmvv(0b011111, &unmasked(args)),
+
+            Vdivuvv(args) => opmvv(0b100000, args),
+            Vdivuvx(args) => opmvx(0b100000, args),
+            Vdivvv(args) => opmvv(0b100001, args),
+            Vdivvx(args) => opmvx(0b100001, args),
+            Vremuvv(args) => opmvv(0b100010, args),
+            Vremuvx(args) => opmvx(0b100010, args),
+            Vremvv(args) => opmvv(0b100011, args),
+            Vremvx(args) => opmvx(0b100011, args),
+            Vmulhuvv(args) => opmvv(0b100100, args),
+            Vmulhuvx(args) => opmvx(0b100100, args),
+            Vmulvv(args) => opmvv(0b100101, args),
+            Vmulvx(args) => opmvx(0b100101, args),
+            Vmulhsuvv(args) => opmvv(0b100110, args),
+            Vmulhsuvx(args) => opmvx(0b100110, args),
+            Vmulhvv(args) => opmvv(0b100111, args),
+            Vmulhvx(args) => opmvx(0b100111, args),
+            Vmaddvv(args) => opmvv(0b101001, args),
+            Vmaddvx(args) => opmvx(0b101001, args),
+            Vnmsubvv(args) => opmvv(0b101011, args),
+            Vnmsubvx(args) => opmvx(0b101011, args),
+            Vmaccvv(args) => opmvv(0b101101, args),
+            Vmaccvx(args) => opmvx(0b101101, args),
+            Vnmsacvv(args) => opmvv(0b101111, args),
+            Vnmsacvx(args) => opmvx(0b101111, args),
+            Vwadduvv(args) => opmvv(0b110000, args),
+            Vwadduvx(args) => opmvx(0b110000, args),
+            Vwaddvv(args) => opmvv(0b110001, args),
+            Vwaddvx(args) => opmvx(0b110001, args),
+            Vwsubuvv(args) => opmvv(0b110010, args),
+            Vwsubuvx(args) => opmvx(0b110010, args),
+            Vwsubvv(args) => opmvv(0b110011, args),
+            Vwsubvx(args) => opmvx(0b110011, args),
+            Vwadduwv(args) => opmvv(0b110100, args),
+            Vwadduwx(args) => opmvx(0b110100, args),
+            Vwaddwv(args) => opmvv(0b110101, args),
+            Vwaddwx(args) => opmvx(0b110101, args),
+            Vwsubuwv(args) => opmvv(0b110110, args),
+            Vwsubuwx(args) => opmvx(0b110110, args),
+            Vwsubwv(args) => opmvv(0b110111, args),
+            Vwsubwx(args) => opmvx(0b110111, args),
+            Vwmuluvv(args) => opmvv(0b111000, args),
+            Vwmuluvx(args) => opmvx(0b111000, args),
+            Vwmulsuvv(args) => opmvv(0b111010, args),
+            Vwmulsuvx(args) => opmvx(0b111010, args),
+            Vwmulvv(args) => opmvv(0b111011, args),
+            Vwmulvx(args) => opmvx(0b111011, args),
+            Vwmaccuvv(args) => opmvv(0b111100, args),
+            Vwmaccuvx(args) => opmvx(0b111100, args),
+            Vwmaccvv(args) => opmvv(0b111101, args),
+            Vwmaccvx(args) => opmvx(0b111101, args),
+            Vwmaccusvx(args) => opmvx(0b111110, args),
+            Vwmaccsuvv(args) => opmvv(0b111111, args),
+            Vwmaccsuvx(args) => opmvx(0b111111, args),
+
+            Vfaddvv(args) => opfvv(0b000000, args),
+            Vfaddvf(args) => opfvf(0b000000, args),
+            Vfredusumvs(args) => opfvv(0b000001, args),
+            Vfsubvv(args) => opfvv(0b000010, args),
+            Vfsubvf(args) => opfvf(0b000010, args),
+            Vfredosumvs(args) => opfvv(0b000011, args),
+            Vfminvv(args) => opfvv(0b000100, args),
+            Vfminvf(args) => opfvf(0b000100, args),
+            Vfredminvs(args) => opfvv(0b000101, args),
+            Vfmaxvv(args) => opfvv(0b000110, args),
+            Vfmaxvf(args) => opfvf(0b000110, args),
+            Vfredmaxvs(args) => opfvv(0b000111, args),
+            Vfsgnjvv(args) => opfvv(0b001000, args),
+            Vfsgnjvf(args) => opfvf(0b001000, args),
+            Vfsgnjnvv(args) => opfvv(0b001001, args),
+            Vfsgnjnvf(args) => opfvf(0b001001, args),
+            Vfsgnjxvv(args) => opfvv(0b001010, args),
+            Vfsgnjxvf(args) => opfvf(0b001010, args),
+            Vfslide1upvf(args) => opfvf(0b001110, args),
+            Vfslide1downvf(args) => opfvf(0b001111, args),
+            Vfmvfs(args) => opfvv_unary(0b010000, 0b00000, &unmasked(args)),
+            Vfmvsf(args) => opfvf(
+                0b010000,
+                &format::Opfvf {
+                    vs2: 0,
+                    ..unmasked(args)
+                },
+            ),
+
+            Vfcvtxufv(args) => opfvv_unary(0b010010, 0b00000, args),
+            Vfcvtxfv(args) => opfvv_unary(0b010010, 0b00001, args),
+            Vfcvtfxuv(args) => opfvv_unary(0b010010, 0b00010, args),
+            Vfcvtfxv(args) => opfvv_unary(0b010010, 0b00011, args),
+            VfcvtRtzxufv(args) => opfvv_unary(0b010010, 0b00110, args),
+            VfcvtRtzxfv(args) => opfvv_unary(0b010010, 0b00111, args),
+            Vfwcvtxufv(args) => opfvv_unary(0b010010, 0b01000, args),
+            Vfwcvtxfv(args) => opfvv_unary(0b010010, 0b01001, args),
+            Vfwcvtfxuv(args) => opfvv_unary(0b010010, 0b01010, args),
+            Vfwcvtfxv(args) => opfvv_unary(0b010010, 0b01011, args),
+            Vfwcvtffv(args) => opfvv_unary(0b010010, 0b01100, args),
+            VfwcvtRtzxufv(args) => opfvv_unary(0b010010, 0b01110, args),
+            VfwcvtRtzxfv(args) => opfvv_unary(0b010010, 0b01111, args),
+            Vfncvtxufw(args) => opfvv_unary(0b010010, 0b10000, args),
+            Vfncvtxfw(args) => opfvv_unary(0b010010, 0b10001, args),
+            Vfncvtfxuw(args) => opfvv_unary(0b010010, 0b10010, args),
+            Vfncvtfxw(args) => opfvv_unary(0b010010, 0b10011, args),
+            Vfncvtffw(args) => opfvv_unary(0b010010, 0b10100, args),
+            VfncvtRodffw(args) => opfvv_unary(0b010010, 0b10101, args),
+            VfncvtRtzxufw(args) => opfvv_unary(0b010010, 0b10110, args),
+            VfncvtRtzxfw(args) => opfvv_unary(0b010010, 0b10111, args),
+            Vfsqrtv(args) => opfvv_unary(0b010011, 0b00000, args),
+            Vfrsqrt7v(args) => opfvv_unary(0b010011, 0b00100, args),
+            Vfrec7v(args) => opfvv_unary(0b010011, 0b00101, args),
+            Vfclassv(args) => opfvv_unary(0b010011, 0b10000, args),
+            Vfmergevfm(args) => opfvf(0b010111, &masked(args)),
+            Vfmvvf(args) => opfvf(
+                0b010111,
+                &format::Opfvf {
+                    vs2: 0,
+                    ..unmasked(args)
+                },
+            ),
+
+            Vmfeqvv(args) => opfvv(0b011000, args),
+            Vmfeqvf(args) => opfvf(0b011000, args),
+            Vmflevv(args) => opfvv(0b011001, args),
+            Vmflevf(args) => opfvf(0b011001, args),
+            Vmfltvv(args) => opfvv(0b011011, args),
+            Vmfltvf(args) => opfvf(0b011011, args),
+            Vmfnevv(args) => opfvv(0b011100, args),
+            Vmfnevf(args) => opfvf(0b011100, args),
+            Vmfgtvf(args) => opfvf(0b011101, args),
+            Vmfgevf(args) => opfvf(0b011111, args),
+            Vfdivvv(args) => opfvv(0b100000, args),
+            Vfdivvf(args) => opfvf(0b100000, args),
+            Vfrdivvf(args) => opfvf(0b100001, args),
+            Vfmulvv(args) => opfvv(0b100100, args),
+            Vfmulvf(args) => opfvf(0b100100, args),
+            Vfrsubvf(args) => opfvf(0b100111, args),
+            Vfmaddvv(args) => opfvv(0b101000, args),
+            Vfmaddvf(args) => opfvf(0b101000, args),
+            Vfnmaddvv(args) => opfvv(0b101001, args),
+            Vfnmaddvf(args) => opfvf(0b101001, args),
+            Vfmsubvv(args) => opfvv(0b101010, args),
+            Vfmsubvf(args) => opfvf(0b101010, args),
+            Vfnmsubvv(args) => opfvv(0b101011, args),
+            Vfnmsubvf(args) => opfvf(0b101011, args),
+            Vfmaccvv(args) => opfvv(0b101100, args),
+            Vfmaccvf(args) => opfvf(0b101100, args),
+            Vfnmaccvv(args) => opfvv(0b101101, args),
+            Vfnmaccvf(args) => opfvf(0b101101, args),
+            Vfmsacvv(args) => opfvv(0b101110, args),
+            Vfmsacvf(args) => opfvf(0b101110, args),
+            Vfnmsacvv(args) => opfvv(0b101111, args),
+            Vfnmsacvf(args) => opfvf(0b101111, args),
+            Vfwaddvv(args) => opfvv(0b110000, args),
+            Vfwaddvf(args) => opfvf(0b110000, args),
+            Vfwredusumvs(args) => opfvv(0b110001, args),
+            Vfwsubvv(args) => opfvv(0b110010, args),
+            Vfwsubvf(args) => opfvf(0b110010, args),
+            Vfwredosumvs(args) => opfvv(0b110011, args),
+            Vfwaddwv(args) => opfvv(0b110100, args),
+            Vfwaddwf(args) => opfvf(0b110100, args),
+            Vfwsubwv(args) => opfvv(0b110110, args),
+            Vfwsubwf(args) => opfvf(0b110110, args),
+            Vfwmulvv(args) => opfvv(0b111000, args),
+            Vfwmulvf(args) => opfvf(0b111000, args),
+            Vfwmaccvv(args) => opfvv(0b111100, args),
+            Vfwmaccvf(args) => opfvf(0b111100, args),
+            Vfwnmaccvv(args) => opfvv(0b111101, args),
+            Vfwnmaccvf(args) => opfvf(0b111101, args),
+            Vfwmsacvv(args) => opfvv(0b111110, args),
+            Vfwmsacvf(args) => opfvf(0b111110, args),
+            Vfwnmsacvv(args) => opfvv(0b111111, args),
+            Vfwnmsacvf(args) => opfvf(0b111111, args),
+
+            Fusion(_) => unreachable!("fused instructions are encoded one by one"),
+        })
+    }
+}
+
+fn reg(register: usize, position: u32) -> u32 {
+    (register as u32 & 0b11111) << position
+}
+
+fn signed(value: i64, bits: u32) -> Result<u32, Diagnostic> {
+    let limit = 1 << (bits - 1);
+
+    if (-limit..limit).contains(&value) {
+        Ok(value as u32 & ((1 << bits) - 1))
+    } else {
+        Err(out_of_range(value, bits))
+    }
+}
+
+fn unsigned(value: i64, bits: u32) -> Result<u32, Diagnostic> {
+    if (0..1 << bits).contains(&value) {
+        Ok(value as u32)
+    } else {
+        Err(out_of_range(value, bits))
+    }
+}
+
+fn out_of_range(value: i64, bits: u32) -> Diagnostic {
+    Diagnostic::error(
+        Code::InvalidImmediate,
+        format!("Immediate {} does not fit in {} bits", value, bits),
+    )
+    .with_token(value.to_string())
+}
+
+fn r(opcode: u32, funct3: u32, funct7: u32, format::R { rd, rs1, rs2 }: &format::R) -> u32 {
+    (funct7 << 25) | reg(*rs2, 20) | reg(*rs1, 15) | (funct3 << 12) | reg(*rd, 7) | opcode
+}
+
+fn i(
+    opcode: u32,
+    funct3: u32,
+    format::I { rd, rs1, imm12 }: &format::I,
+) -> Result<u32, Diagnostic> {
+    let imm = signed(i64::from(*imm12), 12)?;
+
+    Ok((imm << 20) | reg(*rs1, 15) | (funct3 << 12) | reg(*rd, 7) | opcode)
+}
+
+fn shift(
+    opcode: u32,
+    funct3: u32,
+    funct6: u32,
+    shamt_bits: u32,
+    format::I { rd, rs1, imm12 }: &format::I,
+) -> Result<u32, Diagnostic> {
+    let shamt = unsigned(i64::from(*imm12), shamt_bits)?;
+
+    Ok((funct6 << 26) | (shamt << 20) | reg(*rs1, 15) | (funct3 << 12) | reg(*rd, 7) | opcode)
+}
+
+fn s(
+    opcode: u32,
+    funct3: u32,
+    format::S { rs1, rs2, imm12 }: &format::S,
+) -> Result<u32, Diagnostic> {
+    let imm = signed(i64::from(*imm12), 12)?;
+
+    Ok(((imm >> 5) << 25)
+        | reg(*rs2, 20)
+        | reg(*rs1, 15)
+        | (funct3 << 12)
+        | ((imm & 0b11111) << 7)
+        | opcode)
+}
+
+fn b(funct3: u32, format::S { rs1, rs2, imm12 }: &format::S) -> Result<u32, Diagnostic> {
+    let imm = signed(i64::from(*imm12), 13)?;
+
+    Ok((((imm >> 12) & 1) << 31)
+        | (((imm >> 5) & 0b111111) << 25)
+        | reg(*rs2, 20)
+        | reg(*rs1, 15)
+        | (funct3 << 12)
+        | (((imm >> 1) & 0b1111) << 8)
+        | (((imm >> 11) & 1) << 7)
+        | BRANCH)
+}
+
+fn u(opcode: u32, format::U { rd, imm20 }: &format::U) -> Result<u32, Diagnostic> {
+    // Upper immediates are accepted both sign-extended and as raw 20-bit values
+    let imm = match *imm20 {
+        imm20 if imm20 < 0 => signed(i64::from(imm20), 20)?,
+        imm20 => unsigned(i64::from(imm20), 20)?,
+    };
+
+    Ok((imm << 12) | reg(*rd, 7) | opcode)
+}
+
+fn j(format::U { rd, imm20 }: &format::U) -> Result<u32, Diagnostic> {
+    let imm = signed(i64::from(*imm20), 21)?;
+
+    Ok((((imm >> 20) & 1) << 31)
+        | (((imm >> 1) & 0b1111111111) << 21)
+        | (((imm >> 11) & 1) << 20)
+        | (((imm >> 12) & 0b11111111) << 12)
+        | reg(*rd, 7)
+        | JAL)
+}
+
+fn csrr(funct3: u32, format::Csrr { rd, rs1, csr }: &format::Csrr) -> Result<u32, Diagnostic> {
+    let csr = unsigned(*csr as i64, 12)?;
+
+    Ok((csr << 20) | reg(*rs1, 15) | (funct3 << 12) | reg(*rd, 7) | SYSTEM)
+}
+
+fn csri(funct3: u32, format::Csri { rd, uimm, csr }: &format::Csri) -> Result<u32, Diagnostic> {
+    let csr = unsigned(*csr as i64, 12)?;
+    let uimm = unsigned(*uimm as i64, 5)?;
+
+    Ok((csr << 20) | (uimm << 15) | (funct3 << 12) | reg(*rd, 7) | SYSTEM)
+}
+
+fn fp(funct5: u32, fmt: u32, rm: u32, format::R { rd, rs1, rs2 }: &format::R) -> u32 {
+    (funct5 << 27) | (fmt << 25) | reg(*rs2, 20) | reg(*rs1, 15) | (rm << 12) | reg(*rd, 7) | OP_FP
+}
+
+/// Operations with a single source operand use rs2 to select a variant
+fn fp_unary(funct5: u32, fmt: u32, rm: u32, variant: usize, args: &format::R) -> u32 {
+    fp(
+        funct5,
+        fmt,
+        rm,
+        &format::R {
+            rs2: variant,
+            ..args.clone()
+        },
+    )
+}
+
+fn r4(opcode: u32, fmt: u32, format::R4 { rd, rs1, rs2, rs3 }: &format::R4) -> u32 {
+    reg(*rs3, 27) | (fmt << 25) | reg(*rs2, 20) | reg(*rs1, 15) | (DYN << 12) | reg(*rd, 7) | opcode
+}
+
+trait Masking: Clone {
+    fn with_mask(&self, masked: bool) -> Self;
+}
+
+macro_rules! impl_masking {
+    ($($format:ty),*) => {
+        $(impl Masking for $format {
+            fn with_mask(&self, vm: bool) -> Self {
+                Self { vm, ..self.clone() }
+            }
+        })*
+    };
+}
+
+impl_masking!(
+    format::Vl,
+    format::Opivv,
+    format::Opivx,
+    format::Opivi,
+    format::Opmvv,
+    format::Opmvx,
+    format::Opfvv,
+    format::Opfvf
+);
+
+fn masked<T: Masking>(args: &T) -> T {
+    args.with_mask(true)
+}
+
+fn unmasked<T: Masking>(args: &T) -> T {
+    args.with_mask(false)
+}
+
+/// The vm bit is set for unmasked instructions
+fn vm(masked: bool) -> u32 {
+    u32::from(!masked) << 25
+}
+
+fn opv(funct6: u32, funct3: u32, masked: bool, vd: usize, vs2: usize, vs1: usize) -> u32 {
+    (funct6 << 26) | vm(masked) | reg(vs2, 20) | reg(vs1, 15) | (funct3 << 12) | reg(vd, 7) | OP_V
+}
+
+fn opivv(funct6: u32, format::Opivv { vd, vs1, vs2, vm }: &format::Opivv) -> u32 {
+    opv(funct6, OPIVV, *vm, *vd, *vs2, *vs1)
+}
+
+fn opivx(funct6: u32, format::Opivx { vd, rs1, vs2, vm }: &format::Opivx) -> u32 {
+    opv(funct6, OPIVX, *vm, *vd, *vs2, *rs1)
+}
+
+fn opivi(
+    funct6: u32,
+    format::Opivi { vd, imm5, vs2, vm }: &format::Opivi,
+) -> Result<u32, Diagnostic> {
+    // Both signed and unsigned 5-bit immediates share the field
+    let imm = match *imm5 {
+        imm5 if imm5 < 0 => signed(i64::from(imm5), 5)?,
+        imm5 => unsigned(i64::from(imm5), 5)?,
+    };
+
+    Ok(opv(funct6, OPIVI, *vm, *vd, *vs2, imm as usize))
+}
+
+fn vmvnr(registers: i32, args: &format::Opivi) -> Result<u32, Diagnostic> {
+    opivi(
+        0b100111,
+        &format::Opivi {
+            imm5: registers - 1,
+            ..unmasked(args)
+        },
+    )
+}
+
+fn opmvv(funct6: u32, format::Opmvv { dest, vs1, vs2, vm }: &format::Opmvv) -> u32 {
+    opv(funct6, OPMVV, *vm, *dest, *vs2, *vs1)
+}
+
+fn opmvv_unary(funct6: u32, variant: usize, args: &format::Opmvv) -> u32 {
+    opmvv(
+        funct6,
+        &format::Opmvv {
+            vs1: variant,
+            ..args.clone()
+        },
+    )
+}
+
+fn opmvx(funct6: u32, format::Opmvx { dest, rs1, vs2, vm }: &format::Opmvx) -> u32 {
+    opv(funct6, OPMVX, *vm, *dest, *vs2, *rs1)
+}
+
+fn opfvv(funct6: u32, format::Opfvv { dest, vs1, vs2, vm }: &format::Opfvv) -> u32 {
+    opv(funct6, OPFVV, *vm, *dest, *vs2, *vs1)
+}
+
+fn opfvf(funct6: u32, format::Opfvf { vd, rs1, vs2, vm }: &format::Opfvf) -> u32 {
+    opv(funct6, OPFVF, *vm, *vd, *vs2, *rs1)
+}
+
+fn opfvv_unary(funct6: u32, variant: usize, args: &format::Opfvv) -> u32 {
+    opfvv(
+        funct6,
+        &format::Opfvv {
+            vs1: variant,
+            ..args.clone()
+        },
+    )
+}
+
+fn width(eew: &BaseSew) -> u32 {
+    match eew {
+        BaseSew::E8 => 0b000,
+        BaseSew::E16 => 0b101,
+        BaseSew::E32 => 0b110,
+        BaseSew::E64 => 0b111,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn vmem(
+    opcode: u32,
+    nf: usize,
+    mop: u32,
+    masked: bool,
+    field: usize,
+    rs1: usize,
+    eew: &BaseSew,
+    vd: usize,
+) -> u32 {
+    ((nf as u32 - 1) << 29)
+        | (mop << 26)
+        | vm(masked)
+        | reg(field, 20)
+        | reg(rs1, 15)
+        | (width(eew) << 12)
+        | reg(vd, 7)
+        | opcode
+}
+
+fn vl(nf: usize, mop: u32, lumop: u32, eew: &BaseSew, args: &format::Vl) -> u32 {
+    vmem(
+        LOAD_FP,
+        nf,
+        mop,
+        args.vm,
+        lumop as usize,
+        args.rs1,
+        eew,
+        args.vd,
+    )
+}
+
+fn vs(nf: usize, mop: u32, sumop: u32, eew: &BaseSew, args: &format::Vs) -> u32 {
+    vmem(
+        STORE_FP,
+        nf,
+        mop,
+        args.vm,
+        sumop as usize,
+        args.rs1,
+        eew,
+        args.vs3,
+    )
+}
+
+fn vls(nf: usize, args: &format::Vls, eew: &BaseSew) -> u32 {
+    vmem(
+        LOAD_FP, nf, STRIDED, args.vm, args.rs2, args.rs1, eew, args.vd,
+    )
+}
+
+fn vss(nf: usize, args: &format::Vss, eew: &BaseSew) -> u32 {
+    vmem(
+        STORE_FP, nf, STRIDED, args.vm, args.rs2, args.rs1, eew, args.vs3,
+    )
+}
+
+fn vlx(nf: usize, mop: u32, args: &format::Vlx, eew: &BaseSew) -> u32 {
+    vmem(LOAD_FP, nf, mop, args.vm, args.vs2, args.rs1, eew, args.vd)
+}
+
+fn vsx(nf: usize, mop: u32, args: &format::Vsx, eew: &BaseSew) -> u32 {
+    vmem(
+        STORE_FP, nf, mop, args.vm, args.vs2, args.rs1, eew, args.vs3,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::interpreter::decoder::{expression::Context, Decoder};
+
+    fn encode(line: &str) -> Vec<u32> {
+        let labels = HashMap::from([("target".to_owned(), 16)]);
+        let instruction =
+            Decoder::decode_text_section(line, &Context::new(&labels, &labels, 8)).unwrap();
+
+        Encoder::encode(&instruction).unwrap()
+    }
+
+    #[test]
+    fn encodes_like_gnu_as() {
+        let expected = [
+            ("addi a0, zero, 1", vec![0x00100513]),
+            ("add a0, a1, a2", vec![0x00c58533]),
+            ("srai a0, a0, 63", vec![0x43f55513]),
+            ("ld a0, 8(sp)", vec![0x00813503]),
+            ("sd ra, 8(sp)", vec![0x00113423]),
+            ("beq a0, a1, target", vec![0x00b50463]),
+            ("bne a0, zero, 4", vec![0xfe051ee3]),
+            ("jal target", vec![0x008000ef]),
+            ("lui a0, 0x12345", vec![0x12345537]),
+            ("rdcycle a0", vec![0xc0002573]),
+            ("csrrwi zero, fcsr, 5", vec![0x0032d073]),
+            ("mul a0, a1, a2", vec![0x02c58533]),
+            ("divuw a0, a1, a2", vec![0x02c5d53b]),
+            ("fadd.s fa0, fa1, fa2", vec![0x00c5f553]),
+            ("fmadd.d fa0, fa1, fa2, fa3", vec![0x6ac5f543]),
+            ("fcvt.d.w fa0, a0", vec![0xd2050553]),
+            ("fmv.x.d a0, fa0", vec![0xe2050553]),
+            ("flw fa0, 4(a0)", vec![0x00452507]),
+            ("vsetvli t0, a0, e32, m1, ta, ma", vec![0x0d0572d7]),
+            ("vsetivli zero, 4, e8, m1, tu, mu", vec![0xc0027057]),
+            ("vle32.v v1, (a0)", vec![0x02056087]),
+            ("vse8.v v1, (a0), v0.t", vec![0x000500a7]),
+            ("vadd.vv v1, v2, v3", vec![0x022180d7]),
+            ("vadd.vi v1, v2, -1", vec![0x022fb0d7]),
+            ("vadc.vvm v1, v2, v3, v0", vec![0x402180d7]),
+            ("vmv.v.x v1, a0", vec![0x5e0540d7]),
+            ("vmv.x.s a0, v1", vec![0x42102557]),
+            ("vfmul.vf v1, v2, fa0", vec![0x922550d7]),
+            ("vfsqrt.v v1, v2", vec![0x4e2010d7]),
+            ("call target", vec![0x00000097, 0x008080e7]),
+        ];
+
+        for (line, words) in expected {
+            assert_eq!(encode(line), words, "{}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_immediates_out_of_range() {
+        let labels = HashMap::new();
+        let ctx = Context::new(&labels, &labels, 0);
+
+        for line in ["addi a0, a0, 2048", "slli a0, a0, 64", "sw a0, -2049(sp)"] {
+            let instruction = Decoder::decode_text_section(line, &ctx).unwrap();
+
+            assert_eq!(
+                Encoder::encode(&instruction).map_err(|err| err.code),
+                Err(Code::InvalidImmediate),
+                "{}",
+                line
+            );
+        }
+    }
+}
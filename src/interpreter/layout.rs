@@ -0,0 +1,211 @@
+use std::{collections::HashMap, ops::Range};
+
+use super::{
+    decoder::Section,
+    diagnostic::{Code, Diagnostic},
+};
+
+/// Addresses a program is assembled for, like a linker script
+///
+/// Instructions below `text_base` are `nop`s, as the simulator fetches `instructions[pc / 4]`,
+/// so it has to be within memory.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct MemoryLayout {
+    /// Address of the first instruction, which is also the entry point
+    pub text_base: usize,
+    /// Address of the first data section in memory, the end of the text aligned to 8 bytes by default
+    pub data_base: Option<usize>,
+    /// Initial stack pointer, the end of memory by default
+    pub stack_top: Option<usize>,
+    /// Start of the heap, the end of the last data section aligned to 8 bytes by default
+    pub heap_base: Option<usize>,
+    /// Addresses of data sections by name, e.g. `.rodata`
+    ///
+    /// Sections without one follow the previous section, aligned to 8 bytes or their largest
+    /// `.align`, in the order `.rodata`, `.data`, named sections, `.sdata`, `.bss`.
+    pub section_bases: HashMap<String, usize>,
+}
+
+/// Data section being assembled, its contents are placed relative to its start
+#[derive(Clone, Debug)]
+pub(super) struct LocationCounter {
+    pub section: Section,
+    pub offset: usize,
+    /// Largest alignment requested in the section, which its start is aligned to
+    pub alignment: usize,
+}
+
+impl LocationCounter {
+    /// Location counter of a data section, starting at 0 when it first appears
+    pub fn of<'a>(counters: &'a mut Vec<Self>, section: &Section) -> &'a mut Self {
+        let index = match counters
+            .iter()
+            .position(|counter| counter.section == *section)
+        {
+            Some(index) => index,
+            None => {
+                counters.push(Self {
+                    section: section.clone(),
+                    offset: 0,
+                    alignment: 1,
+                });
+                counters.len() - 1
+            }
+        };
+
+        &mut counters[index]
+    }
+}
+
+impl MemoryLayout {
+    /// Fills in the defaults, places the data sections and checks that they fit in memory next to `text`
+    pub(super) fn resolve(
+        &self,
+        memory_size: usize,
+        text: Range<usize>,
+        sections: &[LocationCounter],
+    ) -> Result<Self, Diagnostic> {
+        if !self.text_base.is_multiple_of(4) {
+            return Err(Diagnostic::error(
+                Code::InvalidLayout,
+                format!("Text base {:#x} is not aligned to 4 bytes", self.text_base),
+            ));
+        }
+
+        if self.text_base > memory_size {
+            return Err(Diagnostic::error(
+                Code::InvalidLayout,
+                format!(
+                    "Text base {:#x} is outside of {} bytes of memory",
+                    self.text_base, memory_size
+                ),
+            )
+            .with_help("increase the memory size or lower the text base"));
+        }
+
+        let data_base = self.data_base.unwrap_or(text.end.next_multiple_of(8));
+
+        let mut ordered = sections.iter().collect::<Vec<_>>();
+        ordered.sort_by_key(|counter| match counter.section {
+            Section::ReadOnlyData => 0,
+            Section::Data | Section::Text => 1,
+            Section::Named(_) => 2,
+            Section::SmallData => 3,
+            Section::Bss => 4,
+        });
+
+        let mut section_bases = HashMap::new();
+        let mut placed = Vec::new();
+        let mut next_base = data_base;
+
+        for LocationCounter {
+            section,
+            offset: size,
+            alignment,
+        } in ordered
+        {
+            let base = match self.section_bases.get(section.name()) {
+                Some(&base) if base < data_base => {
+                    return Err(Diagnostic::error(
+                        Code::InvalidLayout,
+                        format!(
+                            "Section {} at {:#x} is below the data base {:#x}",
+                            section.name(),
+                            base,
+                            data_base
+                        ),
+                    ));
+                }
+                Some(&base) if !base.is_multiple_of(*alignment) => {
+                    return Err(Diagnostic::error(
+                        Code::InvalidLayout,
+                        format!(
+                            "Section {} at {:#x} is not aligned to {} bytes",
+                            section.name(),
+                            base,
+                            alignment
+                        ),
+                    ));
+                }
+                Some(&base) => base,
+                None => next_base
+                    .checked_next_multiple_of(*alignment)
+                    .unwrap_or(usize::MAX),
+            };
+
+            let Some(end) = base.checked_add(*size).filter(|&end| end <= memory_size) else {
+                return Err(Diagnostic::error(
+                    Code::InvalidLayout,
+                    format!(
+                        "Section {} of {} bytes at {:#x} does not fit in {} bytes of memory",
+                        section.name(),
+                        size,
+                        base,
+                        memory_size
+                    ),
+                )
+                .with_help("increase the memory size or lower the data base"));
+            };
+
+            if base < text.end && text.start < end {
+                return Err(Diagnostic::error(
+                    Code::InvalidLayout,
+                    format!(
+                        "Section {} at {:#x}..{:#x} overlaps .text at {:#x}..{:#x}",
+                        section.name(),
+                        base,
+                        end,
+                        text.start,
+                        text.end
+                    ),
+                )
+                .with_help("place the data base after the end of the text"));
+            }
+
+            if let Some((other, ..)) = placed
+                .iter()
+                .find(|(_, other_base, other_end)| base < *other_end && *other_base < end)
+            {
+                return Err(Diagnostic::error(
+                    Code::InvalidLayout,
+                    format!(
+                        "Section {} at {:#x}..{:#x} overlaps section {}",
+                        section.name(),
+                        base,
+                        end,
+                        other
+                    ),
+                ));
+            }
+
+            section_bases.insert(section.name().to_owned(), base);
+            placed.push((section.name(), base, end));
+            next_base = end.next_multiple_of(8);
+        }
+
+        let data_end = placed
+            .iter()
+            .map(|(.., end)| *end)
+            .max()
+            .unwrap_or(data_base);
+        let stack_top = self.stack_top.unwrap_or(memory_size);
+
+        if stack_top > memory_size {
+            return Err(Diagnostic::error(
+                Code::InvalidLayout,
+                format!(
+                    "Stack top {:#x} is outside of {} bytes of memory",
+                    stack_top, memory_size
+                ),
+            ));
+        }
+
+        Ok(Self {
+            text_base: self.text_base,
+            data_base: Some(data_base),
+            stack_top: Some(stack_top),
+            heap_base: Some(self.heap_base.unwrap_or(data_end.next_multiple_of(8))),
+            section_bases,
+        })
+    }
+}
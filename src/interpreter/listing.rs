@@ -0,0 +1,175 @@
+use std::{collections::HashMap, fmt};
+
+/// Assembly listing, showing where each instruction, data item and label landed
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Listing {
+    /// Rows of `.text`, addressed like machine code
+    pub text: Vec<ListingRow>,
+    /// Rows of `.data`, addressed like `memory`
+    pub data: Vec<ListingRow>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ListingRow {
+    Label {
+        address: usize,
+        name: String,
+    },
+    /// Expanded pseudo-instructions get a row per instruction, with the source on the first one
+    Instruction {
+        address: usize,
+        word: u32,
+        assembly: String,
+        source: Option<String>,
+    },
+    /// Long data is split into rows of up to 8 bytes, with the source on the first one
+    Data {
+        address: usize,
+        bytes: Vec<u8>,
+        source: Option<String>,
+    },
+}
+
+const DATA_ROW_BYTES: usize = 8;
+
+impl Listing {
+    /// Rows of one source line, given the disassembly of its instruction with a line per word
+    pub(super) fn push_instruction(
+        &mut self,
+        address: usize,
+        words: &[u32],
+        disassembly: &str,
+        source: Option<&str>,
+    ) {
+        for (index, (&word, assembly)) in words.iter().zip(disassembly.lines()).enumerate() {
+            self.text.push(ListingRow::Instruction {
+                address: address + index * 4,
+                word,
+                assembly: assembly.to_owned(),
+                source: source
+                    .filter(|_| index == 0)
+                    .map(|text| text.trim().to_owned()),
+            });
+        }
+    }
+
+    pub(super) fn push_data(&mut self, address: usize, bytes: &[u8], source: &str) {
+        for (index, chunk) in bytes.chunks(DATA_ROW_BYTES).enumerate() {
+            self.data.push(ListingRow::Data {
+                address: address + index * DATA_ROW_BYTES,
+                bytes: chunk.to_vec(),
+                source: (index == 0).then(|| source.trim().to_owned()),
+            });
+        }
+    }
+
+    /// Places labels in front of the first row at their address
+    pub(super) fn with_labels(
+        self,
+        text_labels: &HashMap<String, usize>,
+        data_labels: &HashMap<String, usize>,
+    ) -> Self {
+        Self {
+            text: interleave(self.text, text_labels),
+            data: interleave(self.data, data_labels),
+        }
+    }
+}
+
+impl ListingRow {
+    pub fn address(&self) -> usize {
+        match self {
+            Self::Label { address, .. }
+            | Self::Instruction { address, .. }
+            | Self::Data { address, .. } => *address,
+        }
+    }
+}
+
+fn interleave(rows: Vec<ListingRow>, labels: &HashMap<String, usize>) -> Vec<ListingRow> {
+    let mut sorted = labels
+        .iter()
+        .map(|(name, &address)| (address, name))
+        .collect::<Vec<_>>();
+    sorted.sort();
+
+    let mut labels = sorted
+        .into_iter()
+        .map(|(address, name)| ListingRow::Label {
+            address,
+            name: name.clone(),
+        })
+        .peekable();
+    let mut interleaved = Vec::new();
+
+    for row in rows {
+        while let Some(label) = labels.next_if(|label| label.address() <= row.address()) {
+            interleaved.push(label);
+        }
+
+        interleaved.push(row);
+    }
+
+    interleaved.extend(labels);
+    interleaved
+}
+
+impl fmt::Display for ListingRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Label { address, name } => write!(f, "{:08x}  {:23}  {}:", address, "", name),
+            Self::Instruction {
+                address,
+                word,
+                assembly,
+                source,
+            } => write!(
+                f,
+                "{:08x}  {:23}  {:32}  {}",
+                address,
+                format!("{:08x}", word),
+                assembly,
+                source.as_deref().unwrap_or_default()
+            ),
+            Self::Data {
+                address,
+                bytes,
+                source,
+            } => {
+                let bytes = bytes
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                write!(
+                    f,
+                    "{:08x}  {:23}  {:32}  {}",
+                    address,
+                    bytes,
+                    "",
+                    source.as_deref().unwrap_or_default()
+                )
+            }
+        }
+    }
+}
+
+/// Renders `address | encoding | expanded instruction | source` rows, section by section
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (section, rows) in [(".text", &self.text), (".data", &self.data)] {
+            if rows.is_empty() {
+                continue;
+            }
+
+            writeln!(f, "{}", section)?;
+
+            for row in rows {
+                writeln!(f, "{}", row.to_string().trim_end())?;
+            }
+        }
+
+        Ok(())
+    }
+}
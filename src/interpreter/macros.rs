@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use super::{
+    diagnostic::{Code, Diagnostic},
+    source::strip_comment,
+};
+
+struct Macro {
+    parameters: Vec<(String, Option<String>)>,
+    body: Vec<String>,
+}
+
+struct Recording {
+    name: String,
+    definition: Macro,
+    line_address: usize,
+    depth: usize,
+}
+
+#[derive(Default)]
+pub struct Macros {
+    definitions: HashMap<String, Macro>,
+    recording: Option<Recording>,
+    invocations: usize,
+}
+
+impl Macros {
+    pub fn start(
+        &mut self,
+        name: String,
+        parameters: Vec<(String, Option<String>)>,
+        line_address: usize,
+    ) -> Result<(), Diagnostic> {
+        if self.definitions.contains_key(&name) {
+            return Err(Diagnostic::error(
+                Code::InvalidMacro,
+                format!("Redefinition of macro {}", name),
+            )
+            .with_token(name));
+        }
+
+        self.recording = Some(Recording {
+            name,
+            definition: Macro {
+                parameters,
+                body: Vec::new(),
+            },
+            line_address,
+            depth: 0,
+        });
+
+        Ok(())
+    }
+
+    /// Consumes the line if a macro body is being recorded
+    pub fn record(&mut self, line: &str) -> bool {
+        let Some(recording) = &mut self.recording else {
+            return false;
+        };
+
+        let code = strip_comment(line).trim();
+        let directive = code.split_whitespace().next().unwrap_or_default();
+
+        match directive {
+            ".macro" => recording.depth += 1,
+            ".endm" if recording.depth == 0 => {
+                let Recording {
+                    name, definition, ..
+                } = self.recording.take().unwrap();
+                self.definitions.insert(name, definition);
+                return true;
+            }
+            ".endm" => recording.depth -= 1,
+            _ => {}
+        }
+
+        recording.definition.body.push(line.to_owned());
+        true
+    }
+
+    /// Line of the `.macro` directive whose `.endm` is missing
+    pub fn unterminated(&self) -> Option<usize> {
+        self.recording
+            .as_ref()
+            .map(|recording| recording.line_address)
+    }
+
+    pub fn expand(
+        &mut self,
+        name: &str,
+        arguments: &str,
+    ) -> Option<Result<Vec<String>, Diagnostic>> {
+        let definition = self.definitions.get(name)?;
+
+        // Empty arguments keep their position, e.g. `m x, , y` leaves the second parameter to its default
+        let arguments = match arguments.trim() {
+            "" => Vec::new(),
+            arguments => arguments.split(',').map(str::trim).collect::<Vec<_>>(),
+        };
+
+        if arguments.len() > definition.parameters.len() {
+            return Some(Err(Diagnostic::error(
+                Code::InvalidMacro,
+                format!(
+                    "Macro {} takes {} arguments, got {}",
+                    name,
+                    definition.parameters.len(),
+                    arguments.len()
+                ),
+            )
+            .with_token(name)));
+        }
+
+        let mut values = HashMap::new();
+
+        for (index, (parameter, default)) in definition.parameters.iter().enumerate() {
+            let argument = arguments.get(index).filter(|argument| !argument.is_empty());
+
+            let value = match (argument, default) {
+                (Some(argument), _) => argument.to_string(),
+                (None, Some(default)) => default.clone(),
+                (None, None) => {
+                    return Some(Err(Diagnostic::error(
+                        Code::InvalidMacro,
+                        format!(
+                            "Missing value for parameter {} of macro {}",
+                            parameter, name
+                        ),
+                    )
+                    .with_token(name)))
+                }
+            };
+
+            values.insert(parameter.as_str(), value);
+        }
+
+        let counter = self.invocations.to_string();
+        self.invocations += 1;
+
+        Some(Ok(definition
+            .body
+            .iter()
+            .map(|line| substitute(line, &values, &counter))
+            .collect()))
+    }
+}
+
+fn substitute(line: &str, values: &HashMap<&str, String>, counter: &str) -> String {
+    let mut substituted = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(position) = rest.find('\\') {
+        substituted.push_str(&rest[..position]);
+        rest = &rest[position + 1..];
+
+        if let Some(tail) = rest.strip_prefix('@') {
+            substituted.push_str(counter);
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("()") {
+            rest = tail;
+        } else {
+            let length = rest
+                .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+                .unwrap_or(rest.len());
+            let (parameter, tail) = rest.split_at(length);
+
+            // Like in GNU as, backslashes not naming a parameter are kept, e.g. string escapes
+            match values.get(parameter) {
+                Some(value) => substituted.push_str(value),
+                None => {
+                    substituted.push('\\');
+                    substituted.push_str(parameter);
+                }
+            }
+
+            rest = tail;
+        }
+    }
+
+    substituted.push_str(rest);
+
+    substituted
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use super::{
+    decoder::{AssemblerDirective, Decoder, LineClassification, PreprocDirective},
+    diagnostic::{Code, Diagnostic},
+    source::{strip_comment, ColumnMap},
+};
+
+pub struct Preprocessor {
+    defines: HashMap<String, (String, usize)>,
+    declarations: HashMap<String, usize>,
+}
+
+impl Preprocessor {
+    pub fn new(program: &str) -> Self {
+        let mut declarations = HashMap::new();
+        // Defines in conditional blocks and macro bodies may never be assembled, so they are not declared
+        let mut depth = 0_usize;
+
+        for (line_address, line) in program.lines().enumerate() {
+            match Decoder::classify(line) {
+                Ok(LineClassification::AssemblerDirective(
+                    AssemblerDirective::If(_) | AssemblerDirective::Macro(..),
+                )) => depth += 1,
+                Ok(LineClassification::AssemblerDirective(
+                    AssemblerDirective::EndIf | AssemblerDirective::EndMacro,
+                )) => depth = depth.saturating_sub(1),
+                Ok(LineClassification::PreprocDirective(PreprocDirective::Define(name, _)))
+                    if depth == 0 =>
+                {
+                    declarations.entry(name).or_insert(line_address);
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            defines: HashMap::new(),
+            declarations,
+        }
+    }
+
+    pub fn define(
+        &mut self,
+        name: String,
+        value: String,
+        line_address: usize,
+    ) -> Result<(), Diagnostic> {
+        if let Some((_, previous_line)) = self.defines.get(&name) {
+            return Err(Diagnostic::error(
+                Code::Preprocessor,
+                format!(
+                    "Redefinition of {} (previously defined on line {})",
+                    name,
+                    previous_line + 1
+                ),
+            )
+            .with_token(name));
+        }
+
+        let mut substituted = String::with_capacity(value.len());
+        self.substitute(
+            &value,
+            0,
+            line_address,
+            &mut substituted,
+            &mut ColumnMap::default(),
+        )?;
+        self.defines.insert(name, (substituted, line_address));
+
+        Ok(())
+    }
+
+    /// Substitutes the defines used by the operands of `line`, mapping the result back to `line`
+    pub fn expand(
+        &self,
+        line: &str,
+        line_address: usize,
+    ) -> Result<(String, ColumnMap), Diagnostic> {
+        let mut columns = ColumnMap::default();
+
+        if Decoder::is_preproc_directive(line) {
+            columns.copy(0..line.len(), 0);
+            return Ok((line.to_owned(), columns));
+        }
+
+        let stripped = strip_comment(line);
+        let code = stripped.trim();
+        let code_column = stripped.len() - stripped.trim_start().len();
+
+        let mut lane = code.splitn(2, char::is_whitespace);
+        let mnemonic = lane.next().unwrap_or_default();
+        let rest = lane.next().unwrap_or_default();
+        let operands = rest.trim();
+
+        // Labels are kept as they are, and like #ifdef in C, the operand names a define rather than using its value
+        if code.ends_with(':') || operands.is_empty() || matches!(mnemonic, ".ifdef" | ".ifndef") {
+            columns.copy(0..code.len(), code_column);
+            return Ok((code.to_owned(), columns));
+        }
+
+        let mut expanded = format!("{} ", mnemonic);
+        columns.copy(0..expanded.len(), code_column);
+
+        let operands_column =
+            code_column + mnemonic.len() + 1 + rest.len() - rest.trim_start().len();
+        self.substitute(
+            operands,
+            operands_column,
+            line_address,
+            &mut expanded,
+            &mut columns,
+        )?;
+
+        Ok((expanded, columns))
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.defines.contains_key(name)
+    }
+
+    /// Appends `text` with defines substituted, which starts at `column` of the source line
+    fn substitute(
+        &self,
+        text: &str,
+        mut column: usize,
+        line_address: usize,
+        substituted: &mut String,
+        columns: &mut ColumnMap,
+    ) -> Result<(), Diagnostic> {
+        let mut rest = text;
+
+        while let Some(ch) = rest.chars().next() {
+            let length = if ch == '"' || ch == '\'' {
+                quoted_length(rest)
+            } else if is_word_char(ch) {
+                rest.find(|ch| !is_word_char(ch)).unwrap_or(rest.len())
+            } else {
+                ch.len_utf8()
+            };
+
+            let (token, tail) = rest.split_at(length);
+            let start = substituted.len();
+
+            match self.lookup(token, line_address)? {
+                Some(value) => {
+                    substituted.push_str(value);
+                    columns.substitute(start..substituted.len(), column..column + length);
+                }
+                None => {
+                    substituted.push_str(token);
+                    columns.copy(start..substituted.len(), column);
+                }
+            }
+
+            column += length;
+            rest = tail;
+        }
+
+        Ok(())
+    }
+
+    fn lookup(&self, token: &str, line_address: usize) -> Result<Option<&str>, Diagnostic> {
+        if let Some((value, _)) = self.defines.get(token) {
+            return Ok(Some(value));
+        }
+
+        match self.declarations.get(token) {
+            Some(&define_line) if define_line > line_address => Err(Diagnostic::error(
+                Code::Preprocessor,
+                format!(
+                    "{} is used before its #define on line {}",
+                    token,
+                    define_line + 1
+                ),
+            )
+            .with_token(token)
+            .with_help("move the #define above its first use")),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Length of the literal `text` starts with, up to and including its unescaped closing quote
+fn quoted_length(text: &str) -> usize {
+    let quote = text.chars().next().unwrap_or_default();
+    let mut escaped = false;
+
+    for (index, ch) in text.char_indices().skip(1) {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if ch == quote => return index + ch.len_utf8(),
+            _ => {}
+        }
+    }
+
+    text.len()
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_' || ch == '.'
+}
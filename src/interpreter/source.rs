@@ -0,0 +1,251 @@
+use std::{
+    collections::HashMap,
+    fs,
+    ops::{Index, Range},
+    sync::Arc,
+};
+
+use super::diagnostic::{Code, Diagnostic};
+
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+/// Resolves files named by `.include` and `.incbin` directives
+pub trait SourceProvider {
+    /// Returns the contents of the file, or the reason it cannot be read
+    fn read(&self, path: &str) -> Result<String, String>;
+
+    /// Returns the raw contents of a binary file, by default those of `read`
+    fn read_bytes(&self, path: &str) -> Result<Vec<u8>, String> {
+        self.read(path).map(String::into_bytes)
+    }
+}
+
+/// In-memory files keyed by path, e.g. for the wasm build
+impl SourceProvider for HashMap<String, String> {
+    fn read(&self, path: &str) -> Result<String, String> {
+        self.get(path)
+            .cloned()
+            .ok_or_else(|| "no such file".to_owned())
+    }
+}
+
+/// Reads files from disk, relative paths are resolved against the working directory
+pub struct FileSystem;
+
+impl SourceProvider for FileSystem {
+    fn read(&self, path: &str) -> Result<String, String> {
+        fs::read_to_string(path).map_err(|err| err.to_string())
+    }
+
+    fn read_bytes(&self, path: &str) -> Result<Vec<u8>, String> {
+        fs::read(path).map_err(|err| err.to_string())
+    }
+}
+
+#[derive(Clone)]
+pub struct SourceLine {
+    /// `None` for the program passed to `Interpreter::compile`
+    pub file: Option<Arc<str>>,
+    /// Line index within `file`
+    pub line: usize,
+    pub text: Arc<str>,
+    /// Source line of the `.include` directive which pulled this line in
+    parent: Option<usize>,
+}
+
+/// Maps byte offsets of a preprocessed line to columns of its source line
+#[derive(Clone, Default, Debug)]
+pub struct ColumnMap {
+    /// Consecutive pieces of the preprocessed line, sorted by offset
+    pieces: Vec<Piece>,
+}
+
+#[derive(Clone, Debug)]
+struct Piece {
+    offsets: Range<usize>,
+    columns: Range<usize>,
+    /// Substituted `#define`s map as a whole to their name
+    substituted: bool,
+}
+
+impl ColumnMap {
+    /// Text copied from the source line, starting at `column`
+    pub fn copy(&mut self, offsets: Range<usize>, column: usize) {
+        let columns = column..column + offsets.len();
+
+        match self.pieces.last_mut() {
+            Some(last)
+                if !last.substituted
+                    && last.offsets.end == offsets.start
+                    && last.columns.end == columns.start =>
+            {
+                last.offsets.end = offsets.end;
+                last.columns.end = columns.end;
+            }
+            _ => self.pieces.push(Piece {
+                offsets,
+                columns,
+                substituted: false,
+            }),
+        }
+    }
+
+    /// Text replacing the name at `columns` of the source line
+    pub fn substitute(&mut self, offsets: Range<usize>, columns: Range<usize>) {
+        self.pieces.push(Piece {
+            offsets,
+            columns,
+            substituted: true,
+        });
+    }
+
+    /// Columns of the source line which `offsets` of the preprocessed line come from
+    pub fn resolve(&self, offsets: Range<usize>) -> Option<Range<usize>> {
+        if offsets.is_empty() {
+            return None;
+        }
+
+        let first = self
+            .pieces
+            .iter()
+            .find(|piece| piece.offsets.contains(&offsets.start))?;
+        let last = self
+            .pieces
+            .iter()
+            .find(|piece| piece.offsets.start < offsets.end && offsets.end <= piece.offsets.end)?;
+
+        let start = match first.substituted {
+            true => first.columns.start,
+            false => first.columns.start + offsets.start - first.offsets.start,
+        };
+        let end = match last.substituted {
+            true => last.columns.end,
+            false => last.columns.start + offsets.end - last.offsets.start,
+        };
+
+        Some(start..end)
+    }
+}
+
+/// Lines of the program and every file included into it, addressed by their order of inclusion
+pub struct Sources {
+    lines: Vec<SourceLine>,
+}
+
+impl Sources {
+    pub fn new(program: &str) -> Self {
+        Self {
+            lines: Self::lines(program, None, None).collect(),
+        }
+    }
+
+    /// Line of the program passed to `Interpreter::compile` which the source line originates from
+    pub fn origin(&self, mut line_address: usize) -> usize {
+        while let Some(parent) = self.lines[line_address].parent {
+            line_address = parent;
+        }
+
+        line_address
+    }
+
+    /// Appends the lines of `path` included from `parent`, returning their addresses
+    pub fn include(
+        &mut self,
+        path: &str,
+        parent: usize,
+        provider: Option<&dyn SourceProvider>,
+    ) -> Result<Range<usize>, Diagnostic> {
+        let Some(provider) = provider else {
+            return Err(Diagnostic::error(
+                Code::InvalidInclude,
+                format!("Cannot include {}: no source provider is configured", path),
+            )
+            .with_token(path)
+            .with_help("set CompileOptions::source_provider"));
+        };
+
+        let mut ancestor = Some(parent);
+        let mut depth = 0;
+
+        while let Some(line_address) = ancestor {
+            if self.lines[line_address].file.as_deref() == Some(path) {
+                return Err(Diagnostic::error(
+                    Code::InvalidInclude,
+                    format!("Recursive inclusion of {}", path),
+                )
+                .with_token(path));
+            }
+
+            ancestor = self.lines[line_address].parent;
+            depth += 1;
+        }
+
+        // The program itself has no path, and a file may be included under several paths
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(Diagnostic::error(
+                Code::InvalidInclude,
+                format!("Inclusion of {} is nested too deeply", path),
+            )
+            .with_token(path)
+            .with_help("check for a file including itself"));
+        }
+
+        let contents = provider.read(path).map_err(|reason| {
+            Diagnostic::error(
+                Code::InvalidInclude,
+                format!("Cannot include {}: {}", path, reason),
+            )
+            .with_token(path)
+        })?;
+
+        let start = self.lines.len();
+        self.lines
+            .extend(Self::lines(&contents, Some(path.into()), Some(parent)));
+
+        Ok(start..self.lines.len())
+    }
+
+    fn lines(
+        contents: &str,
+        file: Option<Arc<str>>,
+        parent: Option<usize>,
+    ) -> impl Iterator<Item = SourceLine> + '_ {
+        contents
+            .lines()
+            .enumerate()
+            .map(move |(line, text)| SourceLine {
+                file: file.clone(),
+                line,
+                text: text.into(),
+                parent,
+            })
+    }
+}
+
+impl Index<usize> for Sources {
+    type Output = SourceLine;
+
+    fn index(&self, line_address: usize) -> &SourceLine {
+        &self.lines[line_address]
+    }
+}
+
+/// Drops a trailing `#` comment, keeping `#` inside character and string literals
+pub fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (index, ch) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if ch == '\\' => escaped = true,
+            Some(open) if ch == open => quote = None,
+            Some(_) => {}
+            None if ch == '"' || ch == '\'' => quote = Some(ch),
+            None if ch == '#' => return &line[..index],
+            None => {}
+        }
+    }
+
+    line
+}
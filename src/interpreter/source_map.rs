@@ -0,0 +1,122 @@
+use std::{ops::Range, sync::Arc};
+
+use super::{diagnostic::code_span, source::SourceLine};
+
+/// Position of an instruction or data directive in the sources
+#[derive(Clone, PartialEq, Debug)]
+pub struct SourceLocation {
+    /// Path of the included file, `None` for the compiled program itself
+    pub file: Option<Arc<str>>,
+    /// Line index within `file`
+    pub line: usize,
+    /// Byte offsets of the code within the line
+    pub columns: Range<usize>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mapping {
+    pub addresses: Range<usize>,
+    /// Bytes of `text` the instruction was encoded to, the same as `addresses` for data
+    pub machine_addresses: Range<usize>,
+    pub location: SourceLocation,
+}
+
+/// Maps addresses to source locations and back
+///
+/// Text is addressed like `instructions`, so expanded pseudo-instructions map to a single PC,
+/// while every instruction of a macro expansion maps to the invocation. Each mapping also holds
+/// the machine code addresses, which the listing and ELF files use.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct SourceMap {
+    /// Sorted by address
+    pub text: Vec<Mapping>,
+    /// Sorted by address
+    pub data: Vec<Mapping>,
+}
+
+impl SourceLocation {
+    pub(super) fn of(source: &SourceLine) -> Self {
+        Self {
+            file: source.file.clone(),
+            line: source.line,
+            columns: code_span(&source.text),
+        }
+    }
+
+    fn is_at(&self, file: Option<&str>, line: usize) -> bool {
+        self.file.as_deref() == file && self.line == line
+    }
+}
+
+impl SourceMap {
+    /// Location of the instruction at `pc`
+    pub fn location(&self, pc: usize) -> Option<&SourceLocation> {
+        find(&self.text, pc)
+    }
+
+    /// Location of the data directive which defined the byte at `address`
+    pub fn data_location(&self, address: usize) -> Option<&SourceLocation> {
+        find(&self.data, address)
+    }
+
+    /// PCs of the instructions assembled from the line
+    pub fn pcs(&self, file: Option<&str>, line: usize) -> Vec<usize> {
+        self.text
+            .iter()
+            .filter(|mapping| mapping.location.is_at(file, line))
+            .map(|mapping| mapping.addresses.start)
+            .collect()
+    }
+
+    /// Bytes defined by data directives of the line
+    pub fn data_addresses(&self, file: Option<&str>, line: usize) -> Vec<Range<usize>> {
+        self.data
+            .iter()
+            .filter(|mapping| mapping.location.is_at(file, line))
+            .map(|mapping| mapping.addresses.clone())
+            .collect()
+    }
+
+    /// First PC of the line, or of the closest following line of the file with instructions
+    pub fn breakpoint(&self, file: Option<&str>, line: usize) -> Option<usize> {
+        self.text
+            .iter()
+            .filter(|mapping| {
+                mapping.location.file.as_deref() == file && mapping.location.line >= line
+            })
+            .min_by_key(|mapping| (mapping.location.line, mapping.addresses.start))
+            .map(|mapping| mapping.addresses.start)
+    }
+
+    pub(super) fn push_text(
+        &mut self,
+        pc: usize,
+        machine_addresses: Range<usize>,
+        source: &SourceLine,
+    ) {
+        self.text.push(Mapping {
+            addresses: pc..pc + 4,
+            machine_addresses,
+            location: SourceLocation::of(source),
+        });
+    }
+
+    pub(super) fn push_data(&mut self, addresses: Range<usize>, source: &SourceLine) {
+        if !addresses.is_empty() {
+            self.data.push(Mapping {
+                machine_addresses: addresses.clone(),
+                addresses,
+                location: SourceLocation::of(source),
+            });
+        }
+    }
+}
+
+fn find(mappings: &[Mapping], address: usize) -> Option<&SourceLocation> {
+    let index = mappings.partition_point(|mapping| mapping.addresses.end <= address);
+
+    mappings
+        .get(index)
+        .filter(|mapping| mapping.addresses.contains(&address))
+        .map(|mapping| &mapping.location)
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use super::{decoder::Section, source_map::SourceLocation};
+
+/// Label of a compiled program or a symbol of a loaded ELF file
+#[derive(Clone, PartialEq, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub section: Section,
+    /// Addressed like `instructions` for text symbols and like `memory` for data symbols
+    pub address: usize,
+    /// Address in `text` and the listing, which is past `address` after fused pseudo-instructions
+    ///
+    /// Same as `address` for data symbols and for symbols loaded from ELF files.
+    pub machine_address: usize,
+    /// Bytes up to the next symbol of the section, `None` if unknown
+    pub size: Option<usize>,
+    /// Line defining the label, `None` for symbols loaded from ELF files
+    pub line: Option<SourceLocation>,
+    /// Lines whose expressions resolve the label, sorted
+    pub references: Vec<SourceLocation>,
+}
+
+impl Symbol {
+    /// Symbols of one section, sized up to the next address with a label or up to `end`
+    pub(super) fn section(
+        labels: &HashMap<String, usize>,
+        section: Section,
+        end: usize,
+    ) -> Vec<Self> {
+        let mut symbols = labels
+            .iter()
+            .map(|(name, &address)| Self {
+                name: name.clone(),
+                section: section.clone(),
+                address,
+                machine_address: address,
+                size: None,
+                line: None,
+                references: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        symbols.sort_by(|a, b| (a.address, &a.name).cmp(&(b.address, &b.name)));
+
+        let mut next = end;
+        let mut previous = end;
+
+        for symbol in symbols.iter_mut().rev() {
+            if symbol.address < previous {
+                next = previous;
+                previous = symbol.address;
+            }
+
+            symbol.size = Some(next.saturating_sub(symbol.address));
+        }
+
+        symbols
+    }
+}
@@ -1,5 +1,2 @@
-#![feature(ascii_char)]
-#![feature(ascii_char_variants)]
-
 mod interpreter;
 pub mod prelude;
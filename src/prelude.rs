@@ -1 +1,5 @@
-pub use crate::interpreter::Interpreter;
+pub use crate::interpreter::{
+    BinaryDecoder, Code, CompilationResult, CompileOptions, Diagnostic, Disassembler, FileSystem,
+    Interpreter, Listing, ListingRow, Mapping, MemoryLayout, Section, Severity, SourceLocation,
+    SourceMap, SourceProvider, Symbol,
+};